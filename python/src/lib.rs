@@ -0,0 +1,133 @@
+//! Python bindings of the parallelhash library, built with maturin.
+
+use parallelhash::file_processing::{compute_file_hashes, ReadOptions};
+use parallelhash::hash_algorithms::HashAlgorithm;
+use parallelhash::output::format_digests;
+use parallelhash::utils::HashError;
+use parallelhash::{FileHashResult, Hasher};
+use pyo3::exceptions::{
+    PyFileNotFoundError, PyOSError, PyPermissionError, PyRuntimeError, PyValueError,
+};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Hashes the file at `path` with each of `algorithms`, e.g.
+/// `["sha256", "md5"]`, and returns a dict of their digests by algorithm
+/// name.
+#[pyfunction]
+fn hash_file<'py>(
+    py: Python<'py>,
+    path: PathBuf,
+    algorithms: Vec<String>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let algorithms = parse_algorithms(&algorithms)?;
+    let result = py.detach(|| {
+        compute_file_hashes(&path, &algorithms, &ReadOptions::default(), &mut |_| Ok(()))
+    });
+    let result = result.into_result().map_err(to_py_error)?;
+    digests_dict(py, &result)
+}
+
+/// Hashes the files at `paths`, descending into directories, on background
+/// threads and returns an iterator over their results as dicts with `path`,
+/// `size`, `hashes` and `error` keys. `progress`, if given, is called after
+/// each result with the number of files and bytes done so far.
+#[pyfunction]
+#[pyo3(signature = (paths, algorithms, progress=None, jobs=None, follow_symlinks=true))]
+fn hash_paths(
+    paths: Vec<PathBuf>,
+    algorithms: Vec<String>,
+    progress: Option<Py<PyAny>>,
+    jobs: Option<usize>,
+    follow_symlinks: bool,
+) -> PyResult<Results> {
+    let mut builder = Hasher::builder()
+        .algorithms(parse_algorithms(&algorithms)?)
+        .follow_symlinks(follow_symlinks);
+    if let Some(jobs) = jobs {
+        builder = builder.jobs(jobs);
+    }
+    Ok(Results {
+        results: Box::new(Arc::new(builder.build()).results(paths)),
+        progress,
+        files: 0,
+        bytes: 0,
+    })
+}
+
+/// Iterator returned by `hash_paths`.
+#[pyclass]
+struct Results {
+    results: Box<dyn Iterator<Item = FileHashResult> + Send + Sync>,
+    progress: Option<Py<PyAny>>,
+    files: u64,
+    bytes: u64,
+}
+
+#[pymethods]
+impl Results {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let results = &mut self.results;
+        let Some(mut result) = py.detach(|| results.next()) else {
+            return Ok(None);
+        };
+        self.files += 1;
+        self.bytes += result.size;
+        if let Some(progress) = &self.progress {
+            progress.call1(py, (self.files, self.bytes))?;
+        }
+
+        let entry = PyDict::new(py);
+        entry.set_item("path", &result.path)?;
+        entry.set_item("size", result.size)?;
+        entry.set_item("hashes", digests_dict(py, &result)?)?;
+        let error = match result.error.take() {
+            Some(error) => Some(format!("{:#}", anyhow::Error::from(error))),
+            None => result.note.map(str::to_string),
+        };
+        entry.set_item("error", error)?;
+        Ok(Some(entry))
+    }
+}
+
+fn parse_algorithms(algorithms: &[String]) -> PyResult<Vec<HashAlgorithm>> {
+    algorithms
+        .iter()
+        .map(|algorithm| {
+            HashAlgorithm::new(algorithm).map_err(|e| PyValueError::new_err(e.to_string()))
+        })
+        .collect()
+}
+
+fn digests_dict<'py>(py: Python<'py>, result: &FileHashResult) -> PyResult<Bound<'py, PyDict>> {
+    let digests = PyDict::new(py);
+    for ((algorithm, _), digest) in result.digests.iter().zip(format_digests(&result.digests)) {
+        digests.set_item(algorithm.to_string(), digest)?;
+    }
+    Ok(digests)
+}
+
+/// Raises the Python exception matching a hashing failure.
+fn to_py_error(error: HashError) -> PyErr {
+    let exception: fn(String) -> PyErr = match &error {
+        HashError::NotFound(_) => PyFileNotFoundError::new_err,
+        HashError::PermissionDenied(_) => PyPermissionError::new_err,
+        HashError::ReadError { .. } | HashError::OutputError(_) => PyOSError::new_err,
+        _ => PyRuntimeError::new_err,
+    };
+    exception(format!("{:#}", anyhow::Error::from(error)))
+}
+
+#[pymodule(name = "parallelhash")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(hash_file, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_paths, m)?)?;
+    m.add_class::<Results>()?;
+    Ok(())
+}
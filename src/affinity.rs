@@ -0,0 +1,33 @@
+use core_affinity::CoreId;
+use std::cell::Cell;
+use std::sync::OnceLock;
+
+thread_local! {
+    /// Index of the core the current thread has been pinned to, if any.
+    static PINNED_CORE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn core_ids() -> &'static [CoreId] {
+    static CORE_IDS: OnceLock<Vec<CoreId>> = OnceLock::new();
+    CORE_IDS.get_or_init(|| core_affinity::get_core_ids().unwrap_or_default())
+}
+
+/// Pins the current thread to core `index`, wrapping around the number of
+/// cores. Pinning is best effort: on platforms without affinity support the
+/// thread keeps running wherever the scheduler puts it.
+pub fn pin_current_thread(index: usize) {
+    let cores = core_ids();
+    if cores.is_empty() {
+        return;
+    }
+    let index = index % cores.len();
+    if core_affinity::set_for_current(cores[index]) {
+        PINNED_CORE.with(|core| core.set(Some(index)));
+    }
+}
+
+/// Returns the core index the current thread was pinned to with
+/// `pin_current_thread`.
+pub fn pinned_core() -> Option<usize> {
+    PINNED_CORE.with(Cell::get)
+}
@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+/// Lists the named data streams of the file at `path` as `path:name` paths,
+/// which can be opened like regular files. The unnamed default stream is
+/// left out. Files whose streams cannot be listed, e.g. on file systems
+/// other than NTFS, have none.
+#[cfg(windows)]
+pub fn stream_paths(path: &Path) -> Vec<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+        WIN32_FIND_STREAM_DATA,
+    };
+
+    let wide_path: Vec<u16> = extended_path(path)
+        .as_os_str()
+        .encode_wide()
+        .chain([0])
+        .collect();
+    let mut data = WIN32_FIND_STREAM_DATA::default();
+    // SAFETY: the path is NUL-terminated and `data` is the structure expected
+    // for FindStreamInfoStandard
+    let find = unsafe {
+        FindFirstStreamW(
+            wide_path.as_ptr(),
+            FindStreamInfoStandard,
+            &mut data as *mut _ as *mut _,
+            0,
+        )
+    };
+    if find == INVALID_HANDLE_VALUE {
+        return Vec::new();
+    }
+
+    let mut streams = Vec::new();
+    loop {
+        // Names have the form ":name:$DATA", and "::$DATA" for the default
+        // stream
+        let length = data
+            .cStreamName
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(data.cStreamName.len());
+        let name = OsString::from_wide(&data.cStreamName[..length]);
+        if let Some(name) = name
+            .to_str()
+            .and_then(|name| name.strip_suffix(":$DATA"))
+            .filter(|name| name.len() > 1)
+        {
+            let mut stream = path.as_os_str().to_owned();
+            stream.push(name);
+            streams.push(PathBuf::from(stream));
+        }
+        // SAFETY: `find` is a valid stream search handle
+        if unsafe { FindNextStreamW(find, &mut data as *mut _ as *mut _) } == 0 {
+            break;
+        }
+    }
+    // SAFETY: `find` is closed once, after its last use
+    unsafe { FindClose(find) };
+    streams
+}
+
+/// `path` in the extended `\\?\` form, which Win32 calls need for paths
+/// longer than MAX_PATH: `std::fs` adds it itself, but `FindFirstStreamW`
+/// gets the path as it is. The path is made absolute first, as extended
+/// paths are not normalized.
+#[cfg(windows)]
+fn extended_path(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Component, Prefix};
+
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let wide: Vec<u16> = absolute.as_os_str().encode_wide().collect();
+    // The extended prefix, and how much of the path it replaces
+    let (prefix, replaced) = match absolute.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => (r"\\?\", 0),
+            // \\server\share becomes \\?\UNC\server\share
+            Prefix::UNC(..) => (r"\\?\UNC", 1),
+            // Already extended, or a device
+            _ => return absolute,
+        },
+        _ => return absolute,
+    };
+    let mut extended = OsString::from(prefix);
+    extended.push(OsString::from_wide(&wide[replaced..]));
+    PathBuf::from(extended)
+}
+
+#[cfg(not(windows))]
+pub fn stream_paths(_path: &Path) -> Vec<PathBuf> {
+    Vec::new()
+}
@@ -0,0 +1,313 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::file_processing::{open_input, ReadOptions, STDIN_PATH};
+use crate::hash_algorithms::{digests, HashAlgorithm};
+use crate::results::FileHashResult;
+use crate::utils::HashError;
+
+/// Archive formats whose members are hashed individually.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveFormat {
+    /// Tar archives (.tar), or a tar stream read from standard input
+    Tar,
+    /// Zip archives (.zip) with stored or deflated members
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Picks the format of the file at `path` among the enabled `formats`,
+    /// from its extension.
+    pub fn detect(path: &Path, formats: &[ArchiveFormat]) -> Option<ArchiveFormat> {
+        formats.iter().copied().find(|format| format.matches(path))
+    }
+
+    fn matches(self, path: &Path) -> bool {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match self {
+            ArchiveFormat::Tar => {
+                path.as_os_str() == STDIN_PATH
+                    || extension.is_some_and(|extension| extension.eq_ignore_ascii_case("tar"))
+            }
+            ArchiveFormat::Zip => {
+                extension.is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+            }
+        }
+    }
+}
+
+/// Compression formats whose decompressed content is hashed along with the
+/// file itself, picked from the extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    pub fn detect(path: &Path) -> Option<Compression> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gz" => Some(Compression::Gzip),
+            "xz" => Some(Compression::Xz),
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Digests of an archive and the results of each of its members.
+pub struct ArchiveHashes {
+    pub hashes: Vec<Vec<u8>>,
+    /// Result of each regular file inside the archive, with its path inside
+    /// the archive.
+    pub members: Vec<FileHashResult>,
+}
+
+impl From<Vec<Vec<u8>>> for ArchiveHashes {
+    /// Digests of a file that is not read as an archive.
+    fn from(hashes: Vec<Vec<u8>>) -> Self {
+        Self {
+            hashes,
+            members: Vec::new(),
+        }
+    }
+}
+
+/// Names a member as `archive!member`.
+pub fn member_path(archive: &Path, member: &Path) -> PathBuf {
+    let mut path = OsString::from(archive);
+    path.push("!");
+    path.push(member);
+    path.into()
+}
+
+/// Hashes the archive at `path` along with each regular file it contains, in
+/// a single pass over the archive.
+pub fn hash_archive(
+    path: &Path,
+    format: ArchiveFormat,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<ArchiveHashes, HashError> {
+    let file = open_input(path)?;
+    let mut on_bytes = read_options.throttled(on_bytes);
+    let mut reader = HashingReader {
+        inner: BufReader::with_capacity(read_options.chunk_size, file),
+        hashers: algorithms.to_vec(),
+        on_bytes: &mut on_bytes,
+    };
+    let members = match format {
+        ArchiveFormat::Tar => tar_members(&mut reader, algorithms, read_options)
+            .with_context(|| format!("Failed to read tar archive: {}", path.display())),
+        ArchiveFormat::Zip => zip_members(&mut reader, algorithms, read_options)
+            .with_context(|| format!("Failed to read zip archive: {}", path.display())),
+    };
+    // Hash whatever follows the last member, such as the end-of-archive
+    // blocks, or the rest of an archive whose members could not be read
+    io::copy(&mut reader, &mut io::sink())
+        .with_context(|| format!("Failed to read from file: {}", path.display()))?;
+    let members = match members {
+        Ok(members) => members,
+        // Members written with a data descriptor, as in streamed zips, have
+        // no length in their local header, but a file has it in its central
+        // directory
+        Err(_) if format == ArchiveFormat::Zip && path.as_os_str() != STDIN_PATH => {
+            zip_directory_members(path, algorithms, read_options)
+                .with_context(|| format!("Failed to read zip archive: {}", path.display()))
+                .unwrap_or_else(|e| vec![FileHashResult::failed(PathBuf::new(), e.into())])
+        }
+        // The archive itself is still hashed; its members are reported as
+        // failed, as a single result named after it
+        Err(e) => vec![FileHashResult::failed(PathBuf::new(), e.into())],
+    };
+    Ok(ArchiveHashes {
+        hashes: digests(&mut reader.hashers),
+        members,
+    })
+}
+
+/// Hashes the compressed file at `path` along with its decompressed content,
+/// in a single pass over the file. The content is listed as a member named
+/// after the file without its compression extension.
+pub fn hash_compressed(
+    path: &Path,
+    compression: Compression,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<ArchiveHashes, HashError> {
+    let file = open_input(path)?;
+    let mut on_bytes = read_options.throttled(on_bytes);
+    let mut reader = BufReader::with_capacity(
+        read_options.chunk_size,
+        HashingReader {
+            inner: file,
+            hashers: algorithms.to_vec(),
+            on_bytes: &mut on_bytes,
+        },
+    );
+    let mut content = HashingWriter {
+        hashers: algorithms.to_vec(),
+        size: 0,
+    };
+    let decompressed = match compression {
+        Compression::Gzip => io::copy(&mut MultiGzDecoder::new(&mut reader), &mut content),
+        Compression::Xz => lzma_rs::xz_decompress(&mut reader, &mut content)
+            .map(|()| 0)
+            .map_err(io::Error::other),
+        Compression::Zstd => StreamingDecoder::new(&mut reader)
+            .map_err(io::Error::other)
+            .and_then(|mut decoder| io::copy(&mut decoder, &mut content)),
+    };
+    decompressed.with_context(|| format!("Failed to decompress file: {}", path.display()))?;
+    // Hash whatever follows the compressed data
+    io::copy(&mut reader, &mut io::sink())
+        .with_context(|| format!("Failed to read from file: {}", path.display()))?;
+    let member = PathBuf::from(path.file_stem().unwrap_or_default());
+    let content = FileHashResult::new(
+        member,
+        algorithms,
+        digests(&mut content.hashers),
+        content.size,
+    );
+    Ok(ArchiveHashes {
+        hashes: digests(&mut reader.into_inner().hashers),
+        members: vec![content],
+    })
+}
+
+fn tar_members(
+    reader: &mut dyn Read,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+) -> Result<Vec<FileHashResult>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut members = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let member = entry.path()?.into_owned();
+        let (hashes, size) = hash_reader(&mut entry, algorithms, read_options.chunk_size)
+            .with_context(|| format!("Failed to read member {}", member.display()))?;
+        members.push(FileHashResult::new(member, algorithms, hashes, size));
+    }
+    Ok(members)
+}
+
+/// Reads the members of a zip archive from their local headers, front to back,
+/// instead of seeking to the central directory. The CRC32 stored for each
+/// member is checked against its content as it is read.
+fn zip_members(
+    mut reader: &mut dyn Read,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+) -> Result<Vec<FileHashResult>> {
+    let mut members = Vec::new();
+    while let Some(mut entry) = zip::read::read_zipfile_from_stream(&mut reader)? {
+        if !entry.is_file() {
+            continue;
+        }
+        let member = PathBuf::from(entry.name());
+        let (hashes, size) = hash_reader(&mut entry, algorithms, read_options.chunk_size)
+            .with_context(|| format!("Failed to read member {}", member.display()))?;
+        members.push(FileHashResult::new(member, algorithms, hashes, size));
+    }
+    Ok(members)
+}
+
+/// Reads the members of the zip archive at `path` from its central
+/// directory, as a second pass over the archive.
+fn zip_directory_members(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+) -> Result<Vec<FileHashResult>> {
+    let file = File::open(path)?;
+    let mut archive =
+        zip::ZipArchive::new(BufReader::with_capacity(read_options.chunk_size, file))?;
+    let mut members = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if !entry.is_file() {
+            continue;
+        }
+        let member = PathBuf::from(entry.name());
+        let (hashes, size) = hash_reader(&mut entry, algorithms, read_options.chunk_size)
+            .with_context(|| format!("Failed to read member {}", member.display()))?;
+        members.push(FileHashResult::new(member, algorithms, hashes, size));
+    }
+    Ok(members)
+}
+
+/// Hashes everything `reader` yields, returning the digests and the number
+/// of bytes read.
+fn hash_reader(
+    reader: &mut dyn Read,
+    algorithms: &[HashAlgorithm],
+    chunk_size: usize,
+) -> io::Result<(Vec<Vec<u8>>, u64)> {
+    let mut hashers = algorithms.to_vec();
+    let mut buffer = vec![0; chunk_size];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        for hasher in &mut hashers {
+            hasher.update(&buffer[..read]);
+        }
+        size += read as u64;
+    }
+    Ok((digests(&mut hashers), size))
+}
+
+/// Passes reads through while hashing the data and counting it for the
+/// progress, so an archive is hashed as a whole while its members are read.
+struct HashingReader<'a, R> {
+    inner: R,
+    hashers: Vec<HashAlgorithm>,
+    on_bytes: &'a mut dyn FnMut(u64) -> Result<()>,
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        for hasher in &mut self.hashers {
+            hasher.update(&buf[..read]);
+        }
+        (self.on_bytes)(read as u64).map_err(io::Error::other)?;
+        Ok(read)
+    }
+}
+
+/// Hashes and counts the data written to it, such as decompressed content.
+struct HashingWriter {
+    hashers: Vec<HashAlgorithm>,
+    size: u64,
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for hasher in &mut self.hashers {
+            hasher.update(buf);
+        }
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+use std::future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio::task;
+
+use crate::file_processing::{read_error, Walked};
+use crate::hash_algorithms::{digests, HashAlgorithm};
+use crate::hasher::Hasher;
+use crate::results::FileHashResult;
+use crate::utils::HashError;
+
+/// Number of results waiting to be received before hashing pauses.
+const RESULTS_CAPACITY: usize = 64;
+
+/// Hashes the files at `paths`, descending into directories as
+/// `Hasher::results` does, and sends their results in input order to the
+/// returned receiver. Each file is read with `hash_file_async`, up to the
+/// `jobs` of the hasher at once, so an async service can await the results
+/// without tying up blocking threads for whole files. Archives are hashed as
+/// plain files, and standard input, URLs and the hash cache are not used.
+/// Dropping the receiver stops the hashing. Must be called from within a
+/// tokio runtime.
+pub fn compute_hashes_async(
+    hasher: Arc<Hasher>,
+    paths: Vec<PathBuf>,
+) -> mpsc::Receiver<FileHashResult> {
+    let (sender, receiver) = mpsc::channel(RESULTS_CAPACITY);
+    let jobs = hasher.jobs().max(1);
+    let (item_sender, mut items) = mpsc::channel(jobs * 2);
+    let walker = Arc::clone(&hasher);
+    // Directories are listed with blocking calls
+    task::spawn_blocking(move || {
+        for item in walker.walk_files(&paths) {
+            if item_sender.blocking_send(item).is_err() {
+                return;
+            }
+        }
+    });
+    tokio::spawn(async move {
+        let mut pending = VecDeque::new();
+        let mut walking = true;
+        loop {
+            while walking && pending.len() < jobs && !hasher.read_options().cancel.is_cancelled() {
+                match items.recv().await {
+                    Some(Walked::File(path)) => {
+                        let hasher = Arc::clone(&hasher);
+                        let file = path.clone();
+                        let task = tokio::spawn(async move {
+                            let chunk_size = hasher.read_options().chunk_size;
+                            hash_file_async(&file, hasher.algorithms(), chunk_size).await
+                        });
+                        pending.push_back((path, task));
+                    }
+                    Some(Walked::Done(result)) => {
+                        let path = result.path.clone();
+                        pending.push_back((path, tokio::spawn(future::ready(result))));
+                    }
+                    None => walking = false,
+                }
+            }
+            let Some((path, task)) = pending.pop_front() else {
+                return;
+            };
+            let result = task.await.unwrap_or_else(|_| {
+                FileHashResult::failed(path.clone(), HashError::WorkerPanic(path))
+            });
+            if sender.send(result).await.is_err() {
+                for (_, task) in pending {
+                    task.abort();
+                }
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+/// Hashes the file at `path` with tokio's file I/O, updating the hashers on
+/// a blocking thread while the next chunk is read.
+pub async fn hash_file_async(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    chunk_size: usize,
+) -> FileHashResult {
+    match hash_file_inner(path, algorithms, chunk_size).await {
+        Ok((digests, size)) => FileHashResult::new(path.to_path_buf(), algorithms, digests, size),
+        Err(error) => FileHashResult::failed(path.to_path_buf(), error),
+    }
+}
+
+async fn hash_file_inner(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    chunk_size: usize,
+) -> Result<(Vec<Vec<u8>>, u64), HashError> {
+    let mut file = File::open(path).await?;
+    let mut buffer = vec![0; chunk_size];
+    // The hashers and a spare buffer, handed to each blocking task and back
+    let mut state = (algorithms.to_vec(), vec![0; chunk_size]);
+    let mut hashing = None;
+    let mut size = 0;
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .await
+            .map_err(|e| read_error(path, size, e))?;
+        if let Some(task) = hashing.take() {
+            state = wait(task, path).await?;
+        }
+        if read == 0 {
+            break;
+        }
+        size += read as u64;
+        let (mut hashers, spare) = std::mem::take(&mut state);
+        let chunk = std::mem::replace(&mut buffer, spare);
+        hashing = Some(task::spawn_blocking(move || {
+            for hasher in &mut hashers {
+                hasher.update(&chunk[..read]);
+            }
+            (hashers, chunk)
+        }));
+    }
+    Ok((digests(&mut state.0), size))
+}
+
+async fn wait<T>(task: task::JoinHandle<T>, path: &Path) -> Result<T, HashError> {
+    task.await
+        .map_err(|_| HashError::WorkerPanic(path.to_path_buf()))
+}
@@ -0,0 +1,169 @@
+use anyhow::{bail, Result};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::file_processing::ReadOptions;
+use crate::hash_algorithms::{HashAlgorithm, BUILTIN_ALGORITHMS};
+use crate::hasher::{default_jobs, Hasher};
+use crate::output::format_bytes;
+
+/// Chunk sizes compared by `--bench`, around the default of 1 MiB.
+const CHUNK_SIZES: [usize; 4] = [64 << 10, 256 << 10, 1 << 20, 4 << 20];
+/// Size of the synthetic data hashed in memory.
+const SYNTHETIC_SIZE: usize = 64 << 20;
+
+/// Measures the throughput of `algorithms`, or of every built-in algorithm
+/// when none are given, and writes it to `writer` as tables: each algorithm
+/// alone on synthetic data in memory, then all of them together on the files
+/// at `samples` through the whole pipeline, for several numbers of jobs.
+pub fn run_bench(
+    algorithms: &[HashAlgorithm],
+    samples: &[PathBuf],
+    read_options: &ReadOptions,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let algorithms = match algorithms {
+        [] => BUILTIN_ALGORITHMS
+            .iter()
+            .map(|name| HashAlgorithm::new(name))
+            .collect::<Result<Vec<_>>>()?,
+        algorithms => algorithms.to_vec(),
+    };
+
+    writeln!(
+        writer,
+        "Synthetic data ({} in memory, one thread), MiB/s:",
+        format_bytes(SYNTHETIC_SIZE as u64)
+    )?;
+    write_table_header(writer, "ALGORITHM")?;
+    let data = synthetic_data(SYNTHETIC_SIZE);
+    for algorithm in &algorithms {
+        let speeds = CHUNK_SIZES
+            .iter()
+            .map(|&chunk_size| {
+                let mut hasher = algorithm.clone();
+                let start = Instant::now();
+                for chunk in data.chunks(chunk_size) {
+                    hasher.update(chunk);
+                }
+                hasher.finalize_reset();
+                throughput(data.len() as u64, start.elapsed())
+            })
+            .collect::<Vec<_>>();
+        write_table_row(writer, &algorithm.to_string(), &speeds)?;
+    }
+
+    if samples.is_empty() {
+        return Ok(());
+    }
+    // A first pass brings the samples into the page cache, so that the runs
+    // compare the hashing rather than whatever the disk cache held
+    let bytes = hash_samples(&algorithms, samples, read_options, default_jobs())?;
+    let names = algorithms
+        .iter()
+        .map(|algo| algo.to_string())
+        .collect::<Vec<_>>();
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "Sample files ({}, {}), MiB/s:",
+        format_bytes(bytes),
+        names.join(", ")
+    )?;
+    write_table_header(writer, "JOBS")?;
+    for jobs in job_counts() {
+        let speeds = CHUNK_SIZES
+            .iter()
+            .map(|&chunk_size| {
+                let read_options = ReadOptions {
+                    chunk_size,
+                    adaptive_chunk_size: false,
+                    ..read_options.clone()
+                };
+                let start = Instant::now();
+                let bytes = hash_samples(&algorithms, samples, &read_options, jobs)?;
+                Ok(throughput(bytes, start.elapsed()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        write_table_row(writer, &jobs.to_string(), &speeds)?;
+    }
+    Ok(())
+}
+
+/// Hashes `samples` with `jobs` files at a time, discarding the digests, and
+/// returns the number of bytes hashed.
+fn hash_samples(
+    algorithms: &[HashAlgorithm],
+    samples: &[PathBuf],
+    read_options: &ReadOptions,
+    jobs: usize,
+) -> Result<u64> {
+    let hasher = Hasher::builder()
+        .algorithms(algorithms.to_vec())
+        .jobs(jobs)
+        .read_options(read_options.clone())
+        .build();
+    let mut bytes = 0;
+    for result in Arc::new(hasher).results(samples.to_vec()) {
+        let result = result.into_result()?;
+        bytes += result.size;
+    }
+    if bytes == 0 {
+        bail!("The sample files are empty");
+    }
+    Ok(bytes)
+}
+
+/// Powers of two up to the number of CPUs, and the number of CPUs itself.
+fn job_counts() -> Vec<usize> {
+    let cpus = default_jobs();
+    let mut counts = std::iter::successors(Some(1), |jobs| Some(jobs * 2))
+        .take_while(|&jobs| jobs < cpus)
+        .collect::<Vec<_>>();
+    counts.push(cpus);
+    counts
+}
+
+/// Bytes that do not compress or repeat, from a xorshift generator, so that
+/// no algorithm gets a shortcut.
+pub(crate) fn synthetic_data(size: usize) -> Vec<u8> {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut data = Vec::with_capacity(size + 8);
+    while data.len() < size {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        data.extend_from_slice(&state.to_le_bytes());
+    }
+    data.truncate(size);
+    data
+}
+
+fn throughput(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64().max(1e-9)
+}
+
+fn write_table_header(writer: &mut dyn Write, label: &str) -> Result<()> {
+    write!(writer, "{:<20}", label)?;
+    for chunk_size in CHUNK_SIZES {
+        let label = if chunk_size >= 1 << 20 {
+            format!("{}M", chunk_size >> 20)
+        } else {
+            format!("{}K", chunk_size >> 10)
+        };
+        write!(writer, "{:>12}", label)?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn write_table_row(writer: &mut dyn Write, label: &str, speeds: &[f64]) -> Result<()> {
+    write!(writer, "{:<20}", label)?;
+    for speed in speeds {
+        write!(writer, "{:>12.1}", speed)?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use std::ops::{Deref, Range};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A free list of read buffers. Buffers handed out by `get` come back to the
+/// pool when the `PooledBuffer` wrapping them is dropped, so a file of any size
+/// is read with only as many allocations as there are buffers in flight.
+pub struct BufferPool {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+    buffer_size: AtomicUsize,
+}
+
+impl BufferPool {
+    pub fn new(buffer_size: usize) -> Self {
+        let (sender, receiver) = unbounded();
+        Self {
+            sender,
+            receiver,
+            buffer_size: AtomicUsize::new(buffer_size),
+        }
+    }
+
+    /// Returns a recycled buffer if one is available, or a new one.
+    pub fn get(&self) -> Vec<u8> {
+        let mut buffer = self.receiver.try_recv().unwrap_or_default();
+        buffer.resize(self.buffer_size.load(Ordering::Relaxed), 0);
+        buffer
+    }
+
+    /// Changes the size of the buffers handed out from now on.
+    pub fn set_buffer_size(&self, buffer_size: usize) {
+        self.buffer_size.store(buffer_size, Ordering::Relaxed);
+    }
+
+    /// Returns a buffer to the pool.
+    pub fn put(&self, buffer: Vec<u8>) {
+        // Cannot fail: the pool holds the receiving end
+        let _ = self.sender.send(buffer);
+    }
+
+    /// Wraps the `range` of `buffer` holding data so that the buffer is
+    /// returned to the pool once it is no longer used.
+    pub fn wrap(&self, buffer: Vec<u8>, range: Range<usize>) -> PooledBuffer {
+        PooledBuffer {
+            buffer,
+            range,
+            pool: self.sender.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    range: Range<usize>,
+    pool: Sender<Vec<u8>>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[self.range.clone()]
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        // The pool may already be gone if the reader has finished
+        let _ = self.pool.send(std::mem::take(&mut self.buffer));
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "native")]
+use crate::utils::HashError;
+
+/// Stops a hashing run from another thread. Files being hashed are
+/// abandoned at their next chunk and no new ones are started, while the
+/// results of the files already done are still delivered. Clones share the
+/// same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fails with `HashError::Cancelled` once the run has been cancelled.
+    #[cfg(feature = "native")]
+    pub(crate) fn check(&self) -> Result<(), HashError> {
+        if self.is_cancelled() {
+            return Err(HashError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    /// Uses a flag the caller already has, e.g. one set by a signal handler.
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        Self(flag)
+    }
+}
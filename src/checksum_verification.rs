@@ -1,22 +1,101 @@
-use anyhow::{anyhow, Context, Result};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::{Path, PathBuf};
-
-use crate::file_processing::compute_file_hashes;
-use crate::hash_algorithms::HashAlgorithm;
-use crate::utils::HashError;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+
+use crate::file_processing::{compute_file_hashes, ReadOptions};
+use crate::file_reader::CacheAdvice;
+use crate::hash_algorithms::{AlgorithmOptions, HashAlgorithm};
+use crate::output::{format_digests, Highlight};
+use crate::path_escape::{parse_path, result_line, strip_marker};
+use crate::utils::{path_from_bytes, HashError};
 use crate::OutputManager;
 
+const UTF8_BOM: &[u8] = "\u{feff}".as_bytes();
+
+/// First line of hashdeep output.
+const HASHDEEP_HEADER: &str = "%%%% HASHDEEP-1.0";
+
+/// Layouts of the checksum files read by `--check`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CheckFormat {
+    /// Told from the first line of the file
+    #[default]
+    Auto,
+    /// Digest columns followed by the path, as written by the text format
+    Native,
+    /// md5sum and sha256sum output: the digest and the path
+    Coreutils,
+    /// Lines naming their algorithm: ALGORITHM (path) = digest
+    Bsd,
+    /// Simple File Verification: the path and its CRC32
+    Sfv,
+    /// hashdeep output: size, digests and path, with the columns in a header
+    Hashdeep,
+    /// rhash output: the digest and the path, or the path and its digests
+    Rhash,
+}
+
+/// Settings for verifying a checksum file.
+#[derive(Clone, Debug, Default)]
+pub struct CheckOptions {
+    /// Layout of the checksum file.
+    pub format: CheckFormat,
+    /// Only verify these columns of the checksum file, named by its header.
+    pub verify_only: Vec<String>,
+    /// Compare hex digests regardless of case.
+    pub ignore_hash_case: bool,
+    /// Look for files whose path differs only in case from the one listed,
+    /// when the listed one does not exist.
+    pub ignore_path_case: bool,
+    /// Directory the relative paths of the checksum file are resolved
+    /// against, instead of the current directory.
+    pub base_dir: Option<PathBuf>,
+    /// Only verify the entries whose path, as listed, matches.
+    pub filter: Option<GlobSet>,
+    /// Only write the entries that failed verification.
+    pub failed_only: bool,
+    /// How many times files that fail verification are read and hashed
+    /// again before they are reported as failed.
+    pub recheck_failures: u32,
+}
+
+/// Builds the filter of `CheckOptions` from glob patterns, any of which
+/// selects an entry.
+pub fn glob_filter(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).with_context(|| format!("Invalid pattern: {}", pattern))?);
+    }
+    Ok(builder.build()?)
+}
+
 pub fn verify_checksums(
     check_file: &Path,
     algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
+    check_options: &CheckOptions,
     show_headers: bool,
-    channel_size: usize,
-    chunk_size: usize,
+    read_options: &ReadOptions,
     output_manager: &mut OutputManager,
 ) -> Result<()> {
-    let (entries, detected_algorithms) = parse_checksum_file(check_file, algorithms)?;
+    let selected = check_options
+        .verify_only
+        .iter()
+        .map(|name| HashAlgorithm::with_options(name, options))
+        .collect::<Result<Vec<_>>>()?;
+    let algorithms = if selected.is_empty() {
+        algorithms
+    } else {
+        &selected
+    };
+    let (entries, detected_algorithms) =
+        parse_checksum_file(check_file, check_options.format, algorithms, options)?;
+    if !selected.is_empty() && detected_algorithms.is_empty() {
+        bail!("--verify-only needs a checksum file with a header naming its columns");
+    }
     let algorithms = if !algorithms.is_empty() {
         algorithms
     } else {
@@ -32,62 +111,246 @@ pub fn verify_checksums(
                 .collect::<Vec<_>>()
                 .join("  ")
         );
-        output_manager.write_result(&header)?;
+        output_manager.write_result_header(&header)?;
     }
 
+    let mut files = Vec::new();
     for entry in entries {
-        match compute_file_hashes(
-            &entry.path,
-            algorithms,
-            channel_size,
-            chunk_size,
-            output_manager,
-        ) {
+        if let Some(filter) = &check_options.filter {
+            if !filter.is_match(&entry.path) {
+                continue;
+            }
+        }
+        let mut path = output_manager.path_rewrite().reverse(&entry.path);
+        if let Some(base_dir) = &check_options.base_dir {
+            if path.is_relative() {
+                path = base_dir.join(path);
+            }
+        }
+        if check_options.ignore_path_case {
+            if let Some(found) = find_ignoring_case(&path) {
+                path = found;
+            }
+        }
+        files.push((entry, path));
+    }
+    // The sizes give the progress its total and percentage; missing files
+    // count for nothing
+    let total_bytes = files
+        .iter()
+        .filter_map(|(_, path)| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    output_manager.add_discovered(files.len(), total_bytes)?;
+    output_manager.finish_discovery();
+
+    for (entry, path) in files {
+        let algorithms = entry
+            .algorithm
+            .as_ref()
+            .map_or(algorithms, std::slice::from_ref);
+        output_manager.start_file(&path);
+        let matches = |computed: &[String]| {
+            entry.hashes.iter().zip(computed).all(|(a, b)| {
+                a == b || (check_options.ignore_hash_case && a.eq_ignore_ascii_case(b))
+            })
+        };
+        let mut outcome = hash_entry(&path, algorithms, read_options, output_manager);
+        for attempt in 1..=check_options.recheck_failures {
+            if !outcome.as_ref().is_ok_and(|computed| !matches(computed)) {
+                break;
+            }
+            // Read from the storage again, not from the page cache
+            if let Ok(file) = File::open(&path) {
+                drop(CacheAdvice::new(&file, true));
+            }
+            // The file is read once more, which the progress has to expect
+            if let Ok(metadata) = fs::metadata(&path) {
+                output_manager.add_discovered(0, metadata.len())?;
+            }
+            outcome = hash_entry(&path, algorithms, read_options, output_manager);
+            if outcome.as_ref().is_ok_and(|computed| matches(computed)) {
+                eprintln!(
+                    "Warning: {} failed verification, then matched when read again ({} of {})",
+                    path.display(),
+                    attempt,
+                    check_options.recheck_failures
+                );
+            }
+        }
+        match outcome {
             Ok(computed_hashes) => {
-                let result = entry
-                    .hashes
-                    .iter()
-                    .zip(computed_hashes.iter())
-                    .all(|(a, b)| a == b);
-                let status = if result { "OK" } else { "FAILED" };
-                let output = format!(
-                    "{}  {}  {}",
-                    status,
-                    computed_hashes.join("  "),
-                    entry.path.display()
+                let result = matches(&computed_hashes);
+                if result && check_options.failed_only {
+                    output_manager.count_file();
+                    continue;
+                }
+                let status = if result {
+                    output_manager.highlight("OK", Highlight::Success)
+                } else {
+                    output_manager.highlight("FAILED", Highlight::Failure)
+                };
+                let output = result_line(
+                    &format!("{}  {}", status, computed_hashes.join("  ")),
+                    &entry.path,
                 );
                 output_manager.write_result(&output)?;
             }
-            Err(HashError::FileNotFound(_)) => {
-                let output = format!(
-                    "FAILED  {}  {}",
-                    vec!["N/A"; algorithms.len()].join("  "),
-                    entry.path.display()
+            Err(HashError::NotFound(_)) => {
+                let output = result_line(
+                    &format!(
+                        "{}  {}",
+                        output_manager.highlight("FAILED", Highlight::Failure),
+                        vec!["N/A"; algorithms.len()].join("  ")
+                    ),
+                    &entry.path,
                 );
                 output_manager.write_result(&output)?;
             }
-            Err(HashError::Other(e)) => {
-                eprintln!("Error computing hashes for {}: {}", entry.path.display(), e);
+            Err(e) => {
+                if !output_manager.log_error(&entry.path, &e)? {
+                    eprintln!("Error computing hashes for {}: {}", entry.path.display(), e);
+                }
             }
         }
     }
-
+    output_manager.finish()?;
     Ok(())
 }
 
-fn parse_checksum_file(
+/// Hashes the file of an entry, returning the digests as written in checksum
+/// files.
+fn hash_entry(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    output_manager: &mut OutputManager,
+) -> Result<Vec<String>, HashError> {
+    compute_file_hashes(path, algorithms, read_options, &mut |bytes| {
+        output_manager.update_bytes(bytes)
+    })
+    .into_result()
+    .map(|computed| format_digests(&computed.digests))
+}
+
+/// Finds the file at `path`, or else one whose path differs only in case,
+/// looking through the directories along the way, as on case-insensitive
+/// filesystems mounted where lookups are case-sensitive.
+fn find_ignoring_case(path: &Path) -> Option<PathBuf> {
+    if path.symlink_metadata().is_ok() {
+        return Some(path.to_path_buf());
+    }
+    let mut found = PathBuf::new();
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            found.push(component);
+            continue;
+        };
+        let exact = found.join(name);
+        if exact.symlink_metadata().is_ok() {
+            found = exact;
+            continue;
+        }
+        let dir = if found.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &found
+        };
+        let name = name.to_str()?.to_lowercase();
+        let entry = fs::read_dir(dir).ok()?.flatten().find(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|candidate| candidate.to_lowercase() == name)
+        })?;
+        found.push(entry.file_name());
+    }
+    Some(found)
+}
+
+pub(crate) fn parse_checksum_file(
     path: &Path,
+    format: CheckFormat,
     algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
 ) -> Result<(Vec<ChecksumEntry>, Vec<HashAlgorithm>)> {
     let file = File::open(path).context("Failed to open checksum file")?;
-    let reader = BufReader::new(file);
+    let mut reader = BufReader::new(file);
+    let start = reader.fill_buf().context("Failed to read checksum file")?;
+    let reader: Box<dyn BufRead> = match utf16_byte_order(start) {
+        Some(big_endian) => {
+            let mut data = Vec::new();
+            reader
+                .read_to_end(&mut data)
+                .context("Failed to read checksum file")?;
+            let text = decode_utf16(&data, big_endian).context("Invalid UTF-16 checksum file")?;
+            Box::new(Cursor::new(text.into_bytes()))
+        }
+        None => Box::new(reader),
+    };
+    let mut lines = reader
+        .split(b'\n')
+        .map(|line| line.map(Line::new))
+        .enumerate()
+        .filter(|(_, line)| !line.as_ref().is_ok_and(|line| is_comment(&line.text)))
+        .peekable();
+    let format = match (format, lines.peek()) {
+        (CheckFormat::Auto, Some((_, Ok(line)))) => detect_format(&line.text, algorithms, options),
+        (format, _) => format,
+    };
+    let (entries, detected_algorithms) = match format {
+        CheckFormat::Auto | CheckFormat::Native => parse_native_lines(lines, algorithms, options)?,
+        CheckFormat::Coreutils => parse_coreutils_lines(lines, algorithms, options)?,
+        CheckFormat::Bsd => parse_bsd_lines(lines, options)?,
+        CheckFormat::Sfv => parse_sfv_lines(lines, options)?,
+        CheckFormat::Hashdeep => parse_hashdeep_lines(lines, options)?,
+        CheckFormat::Rhash => parse_rhash_lines(lines, algorithms, options)?,
+    };
+    select_columns(entries, detected_algorithms, algorithms)
+}
+
+/// Tells the layout of a checksum file from its first line. Lines naming
+/// their algorithm are left to the native format, which reads them mixed
+/// with digest columns, and so are lines of several digest columns or when
+/// `algorithms` gives several columns. The SFV and rhash layouts, told by
+/// the digests ending the line, are only guessed after these, as a path can
+/// look like a digest.
+fn detect_format(
+    line: &str,
+    algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
+) -> CheckFormat {
+    let last_token = line.rsplit(' ').next().unwrap_or_default();
+    if line.starts_with(HASHDEEP_HEADER) {
+        CheckFormat::Hashdeep
+    } else if parse_header(line, options).is_some()
+        || parse_tagged_line(line, options).is_some()
+        || algorithms.len() > 1
+        || digest_columns(strip_marker(line).0) > 1
+    {
+        CheckFormat::Native
+    } else if coreutils_line(strip_marker(line).0).is_some() {
+        CheckFormat::Coreutils
+    } else if last_token.len() == 8 && is_hex_digest(last_token) {
+        CheckFormat::Sfv
+    } else if is_hex_digest(last_token) {
+        CheckFormat::Rhash
+    } else {
+        CheckFormat::Native
+    }
+}
+
+fn parse_native_lines(
+    mut lines: impl Iterator<Item = (usize, io::Result<Line>)>,
+    algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
+) -> Result<(Vec<ChecksumEntry>, Vec<HashAlgorithm>)> {
     let mut entries = Vec::new();
-    let mut lines = reader.lines();
     let mut detected_algorithms = Vec::new();
 
     // Check for header
-    if let Some(Ok(first_line)) = lines.next() {
-        if let Some(header_algorithms) = parse_header(&first_line) {
+    if let Some((i, Ok(first_line))) = lines.next() {
+        if let Some(header_algorithms) = parse_header(&first_line.text, options) {
             detected_algorithms = header_algorithms;
         } else {
             // If it's not a header, parse it as a regular line
@@ -95,38 +358,403 @@ fn parse_checksum_file(
                 &first_line,
                 algorithms,
                 &detected_algorithms,
+                options,
                 &mut entries,
-                1,
+                i + 1,
             )?;
         }
     }
 
-    let algorithms_to_use = if !algorithms.is_empty() {
-        algorithms
-    } else {
+    // A header names the columns, so given algorithms select among them
+    let algorithms_to_use = if !detected_algorithms.is_empty() {
         &detected_algorithms
+    } else {
+        algorithms
     };
 
-    for (i, line) in lines.enumerate() {
-        let line = line.context(format!("Failed to read line {} from checksum file", i + 2))?;
+    for (i, line) in lines {
+        let line = line.context(format!("Failed to read line {} from checksum file", i + 1))?;
         parse_line(
             &line,
             algorithms_to_use,
             &detected_algorithms,
+            options,
             &mut entries,
-            i + 2,
+            i + 1,
         )?;
     }
+    Ok((entries, detected_algorithms))
+}
+
+/// Narrows the entries of a checksum file whose header names its columns to
+/// the columns of `algorithms`, in their order.
+fn select_columns(
+    mut entries: Vec<ChecksumEntry>,
+    mut detected_algorithms: Vec<HashAlgorithm>,
+    algorithms: &[HashAlgorithm],
+) -> Result<(Vec<ChecksumEntry>, Vec<HashAlgorithm>)> {
+    if !algorithms.is_empty() && !detected_algorithms.is_empty() {
+        let columns = algorithms
+            .iter()
+            .map(|algo| {
+                let name = algo.to_string();
+                detected_algorithms
+                    .iter()
+                    .position(|column| column.to_string() == name)
+                    .ok_or_else(|| anyhow!("{} is not a column of the checksum file", name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for entry in entries.iter_mut().filter(|entry| entry.algorithm.is_none()) {
+            entry.hashes = columns.iter().map(|&c| entry.hashes[c].clone()).collect();
+        }
+        detected_algorithms = columns
+            .iter()
+            .map(|&c| detected_algorithms[c].clone())
+            .collect();
+    }
+
+    Ok((entries, detected_algorithms))
+}
+
+/// Tells UTF-16 checksum files, as PowerShell writes when redirecting output,
+/// from their first bytes: their byte order mark, or without one, the zero
+/// byte of the first character, as lines start with ASCII. Returns whether
+/// they are big-endian.
+fn utf16_byte_order(start: &[u8]) -> Option<bool> {
+    match start {
+        [0xff, 0xfe, ..] => Some(false),
+        [0xfe, 0xff, ..] => Some(true),
+        [first, 0, ..] if *first != 0 => Some(false),
+        [0, second, ..] if *second != 0 => Some(true),
+        _ => None,
+    }
+}
+
+fn decode_utf16(data: &[u8], big_endian: bool) -> Result<String> {
+    if !data.len().is_multiple_of(2) {
+        bail!("Odd number of bytes");
+    }
+    let units = data.chunks_exact(2).map(|pair| {
+        let pair = [pair[0], pair[1]];
+        if big_endian {
+            u16::from_be_bytes(pair)
+        } else {
+            u16::from_le_bytes(pair)
+        }
+    });
+    Ok(char::decode_utf16(units).collect::<Result<String, _>>()?)
+}
+
+/// Reads the output of rhash, which has no header: `digest  path` lines (or
+/// `digest *path`) when rhash computed one algorithm, like md5sum, and
+/// `path digest...` lines when it computed several, like SFV files. Without
+/// `algorithms`, the algorithms are told apart by the length of the digests
+/// of the first entry, taking the SHA-2 algorithms for 64, 96 and 128 digits.
+fn parse_rhash_lines(
+    lines: impl Iterator<Item = (usize, io::Result<Line>)>,
+    algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
+) -> Result<(Vec<ChecksumEntry>, Vec<HashAlgorithm>)> {
+    let mut entries = Vec::new();
+    let mut detected_algorithms = Vec::new();
+    for (i, line) in lines {
+        let line = line.context(format!("Failed to read line {} from checksum file", i + 1))?;
+        let text = line.text.as_str();
+        let digest_first = text
+            .split_once(' ')
+            .filter(|(digest, path)| is_hex_digest(digest) && path.starts_with([' ', '*']));
+        let (hashes, path) = match digest_first {
+            Some((digest, path)) => (vec![digest], &path[1..]),
+            None => {
+                let columns = match (algorithms.len(), detected_algorithms.len()) {
+                    (0, 0) => text
+                        .split(' ')
+                        .rev()
+                        .take_while(|token| is_hex_digest(token))
+                        .count(),
+                    (0, detected) => detected,
+                    (given, _) => given,
+                };
+                let mut parts = text.rsplitn(columns + 1, ' ').collect::<Vec<_>>();
+                let path = parts.pop().unwrap_or_default().trim_end();
+                parts.reverse();
+                (parts, path)
+            }
+        };
+        let expected = match (algorithms.len(), detected_algorithms.len()) {
+            (0, detected) => detected,
+            (given, _) => given,
+        };
+        if path.is_empty()
+            || hashes.is_empty()
+            || !hashes.iter().all(|digest| is_hex_digest(digest))
+            || (expected != 0 && hashes.len() != expected)
+        {
+            bail!("Invalid rhash line at line {}", i + 1);
+        }
+        if expected == 0 {
+            detected_algorithms = hashes
+                .iter()
+                .map(|digest| digest_algorithm(digest.len(), options))
+                .collect::<Option<_>>()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Cannot tell the algorithms of the digests at line {}, give them with -a",
+                        i + 1
+                    )
+                })?;
+        }
+        entries.push(ChecksumEntry {
+            hashes: hashes
+                .iter()
+                .map(|digest| digest.to_ascii_lowercase())
+                .collect(),
+            path: line.path(path),
+            algorithm: None,
+        });
+    }
+    Ok((entries, detected_algorithms))
+}
+
+/// How many digest columns, separated by two spaces, start a line of the
+/// native format, before its path.
+fn digest_columns(line: &str) -> usize {
+    let parts = line.split("  ").collect::<Vec<_>>();
+    parts[..parts.len() - 1]
+        .iter()
+        .take_while(|part| is_hex_digest(part))
+        .count()
+}
 
+/// Splits a line of md5sum or sha256sum output, `digest  path` or, for files
+/// read in binary mode, `digest *path`, into the digest and the path.
+fn coreutils_line(line: &str) -> Option<(&str, &str)> {
+    let (digest, path) = line.split_once(' ')?;
+    let path = path.strip_prefix([' ', '*'])?;
+    (is_hex_digest(digest) && !path.is_empty()).then_some((digest, path))
+}
+
+/// Reads md5sum and sha256sum output, whose paths are escaped as
+/// `result_line` does. Without `algorithms`, the algorithm is told from the
+/// length of the first digest, as for rhash output.
+fn parse_coreutils_lines(
+    lines: impl Iterator<Item = (usize, io::Result<Line>)>,
+    algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
+) -> Result<(Vec<ChecksumEntry>, Vec<HashAlgorithm>)> {
+    if algorithms.len() > 1 {
+        bail!("Coreutils checksum files have one digest per line, give a single algorithm with -a");
+    }
+    let mut entries = Vec::new();
+    let mut detected_algorithms = Vec::new();
+    for (i, line) in lines {
+        let line = line.context(format!("Failed to read line {} from checksum file", i + 1))?;
+        let (text, escaped) = strip_marker(&line.text);
+        let (digest, path) = coreutils_line(text)
+            .ok_or_else(|| anyhow!("Invalid coreutils checksum line at line {}", i + 1))?;
+        if algorithms.is_empty() && detected_algorithms.is_empty() {
+            let algorithm = digest_algorithm(digest.len(), options).ok_or_else(|| {
+                anyhow!(
+                    "Cannot tell the algorithm of the digest at line {}, give it with -a",
+                    i + 1
+                )
+            })?;
+            detected_algorithms.push(algorithm);
+        }
+        entries.push(ChecksumEntry {
+            hashes: vec![digest.to_ascii_lowercase()],
+            path: line
+                .parse_path(path, escaped)
+                .with_context(|| format!("Invalid path at line {}", i + 1))?,
+            algorithm: None,
+        });
+    }
     Ok((entries, detected_algorithms))
 }
 
-fn parse_header(line: &str) -> Option<Vec<HashAlgorithm>> {
+/// Reads files whose every line names its algorithm, as written by BSD tools
+/// and `sha256sum --tag`.
+fn parse_bsd_lines(
+    lines: impl Iterator<Item = (usize, io::Result<Line>)>,
+    options: &AlgorithmOptions,
+) -> Result<(Vec<ChecksumEntry>, Vec<HashAlgorithm>)> {
+    let mut entries = Vec::new();
+    for (i, line) in lines {
+        let line = line.context(format!("Failed to read line {} from checksum file", i + 1))?;
+        let (algorithm, digest, path) = parse_tagged_line(&line.text, options)
+            .ok_or_else(|| anyhow!("Invalid BSD-style checksum line at line {}", i + 1))?;
+        entries.push(ChecksumEntry {
+            hashes: vec![digest.to_ascii_lowercase()],
+            path: line.path(path),
+            algorithm: Some(algorithm),
+        });
+    }
+    Ok((entries, Vec::new()))
+}
+
+/// Reads SFV files: the path, a space and the CRC32 of the file, often in
+/// uppercase.
+fn parse_sfv_lines(
+    lines: impl Iterator<Item = (usize, io::Result<Line>)>,
+    options: &AlgorithmOptions,
+) -> Result<(Vec<ChecksumEntry>, Vec<HashAlgorithm>)> {
+    let mut entries = Vec::new();
+    for (i, line) in lines {
+        let line = line.context(format!("Failed to read line {} from checksum file", i + 1))?;
+        let (path, digest) = line
+            .text
+            .rsplit_once(' ')
+            .map(|(path, digest)| (path.trim_end(), digest))
+            .filter(|(path, digest)| !path.is_empty() && digest.len() == 8 && is_hex_digest(digest))
+            .ok_or_else(|| anyhow!("Invalid SFV line at line {}", i + 1))?;
+        entries.push(ChecksumEntry {
+            hashes: vec![digest.to_ascii_lowercase()],
+            path: line.path(path),
+            algorithm: None,
+        });
+    }
+    Ok((
+        entries,
+        vec![HashAlgorithm::with_options("crc32", options)?],
+    ))
+}
+
+/// Reads hashdeep output: a `%%%% HASHDEEP-1.0` line, a `%%%% size,...,filename`
+/// line naming the columns, `##` comments, then a comma-separated line per
+/// file. Columns of algorithms parallelhash lacks, such as tiger and
+/// whirlpool, are left out, and so are the sizes, which the digests cover.
+fn parse_hashdeep_lines(
+    mut lines: impl Iterator<Item = (usize, io::Result<Line>)>,
+    options: &AlgorithmOptions,
+) -> Result<(Vec<ChecksumEntry>, Vec<HashAlgorithm>)> {
+    let columns = match (lines.next(), lines.next()) {
+        (Some((_, Ok(first))), Some((_, Ok(second))))
+            if first.text.starts_with(HASHDEEP_HEADER) =>
+        {
+            second.text.strip_prefix("%%%% ").map(|columns| {
+                columns
+                    .split(',')
+                    .map(|column| column.trim().to_string())
+                    .collect::<Vec<_>>()
+            })
+        }
+        _ => None,
+    }
+    .filter(|columns| {
+        columns.len() >= 3
+            && columns.first().is_some_and(|column| column == "size")
+            && columns.last().is_some_and(|column| column == "filename")
+    })
+    .ok_or_else(|| anyhow!("Invalid hashdeep header"))?;
+
+    let mut digest_columns = Vec::new();
+    let mut detected_algorithms = Vec::new();
+    for (index, name) in columns.iter().enumerate().take(columns.len() - 1).skip(1) {
+        match HashAlgorithm::with_options(name, options) {
+            Ok(algorithm) => {
+                digest_columns.push(index);
+                detected_algorithms.push(algorithm);
+            }
+            Err(_) => eprintln!("Warning: skipping unsupported hashdeep column {}", name),
+        }
+    }
+    if detected_algorithms.is_empty() {
+        bail!("No hashdeep column has an algorithm parallelhash computes");
+    }
+
+    let mut entries = Vec::new();
+    for (i, line) in lines {
+        let line = line.context(format!("Failed to read line {} from checksum file", i + 1))?;
+        // The file name comes last and may itself contain commas
+        let parts = line.text.splitn(columns.len(), ',').collect::<Vec<_>>();
+        if parts.len() != columns.len() || parts[columns.len() - 1].is_empty() {
+            bail!("Invalid hashdeep line at line {}", i + 1);
+        }
+        entries.push(ChecksumEntry {
+            hashes: digest_columns
+                .iter()
+                .map(|&index| parts[index].to_ascii_lowercase())
+                .collect(),
+            path: line.path(parts[columns.len() - 1]),
+            algorithm: None,
+        });
+    }
+    Ok((entries, detected_algorithms))
+}
+
+/// Whether `token` can be a hex digest: SFV files have 8 digits, the shortest.
+fn is_hex_digest(token: &str) -> bool {
+    token.len() >= 8 && token.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The algorithm of a hex digest of `digits` digits, among those coreutils,
+/// rhash and parallelhash all compute.
+fn digest_algorithm(digits: usize, options: &AlgorithmOptions) -> Option<HashAlgorithm> {
+    let name = match digits {
+        32 => "md5",
+        40 => "sha1",
+        64 => "sha256",
+        96 => "sha384",
+        128 => "sha512",
+        _ => return None,
+    };
+    HashAlgorithm::with_options(name, options).ok()
+}
+
+/// A line of a checksum file, read as bytes so that paths that are not
+/// valid UTF-8, as coreutils writes them unescaped, are kept as they are.
+struct Line {
+    /// The line to parse, with each byte that is not valid UTF-8 replaced by
+    /// `?`, so that its parts are at the same offsets as in `bytes`.
+    text: String,
+    bytes: Vec<u8>,
+}
+
+impl Line {
+    /// Drops the byte order marks and carriage returns of files saved by
+    /// Windows editors, which concatenated files have on any line.
+    fn new(mut bytes: Vec<u8>) -> Self {
+        while bytes.starts_with(UTF8_BOM) {
+            bytes.drain(..UTF8_BOM.len());
+        }
+        bytes.retain(|&b| b != b'\r');
+        let mut text = String::with_capacity(bytes.len());
+        for chunk in bytes.utf8_chunks() {
+            text.push_str(chunk.valid());
+            text.extend(chunk.invalid().iter().map(|_| '?'));
+        }
+        Line { text, bytes }
+    }
+
+    /// The path made of the bytes of `path`, a part of `self.text`.
+    fn path(&self, path: &str) -> PathBuf {
+        let start = path.as_ptr() as usize - self.text.as_ptr() as usize;
+        path_from_bytes(&self.bytes[start..start + path.len()])
+    }
+
+    /// Reads back a path written by `result_line`, as `parse_path` does.
+    fn parse_path(&self, path: &str, escaped: bool) -> Result<PathBuf> {
+        if escaped {
+            parse_path(path, true)
+        } else {
+            Ok(self.path(path))
+        }
+    }
+}
+
+/// Whether a line of a checksum file is left out: a comment starting with
+/// `#` (or `;` as in SFV files), or a blank line.
+pub(crate) fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty() || line.starts_with(['#', ';'])
+}
+
+fn parse_header(line: &str, options: &AlgorithmOptions) -> Option<Vec<HashAlgorithm>> {
     let parts: Vec<&str> = line.split("  ").collect();
     if parts.last() == Some(&"path") {
         let algorithms: Result<Vec<HashAlgorithm>, _> = parts[..parts.len() - 1]
             .iter()
-            .map(|&s| HashAlgorithm::new(s))
+            .map(|&s| HashAlgorithm::with_options(s, options))
             .collect();
         algorithms.ok()
     } else {
@@ -134,13 +762,50 @@ fn parse_header(line: &str) -> Option<Vec<HashAlgorithm>> {
     }
 }
 
+/// Parses a line that names its algorithm, so that files can mix them:
+/// `SHA256 (path) = digest` as written by BSD tools and `sha256sum --tag`,
+/// `SHA256(path)= digest` as written by openssl, or `sha256:digest  path`.
+/// Returns `None` for other lines, and for algorithms parallelhash lacks.
+fn parse_tagged_line<'a>(
+    line: &'a str,
+    options: &AlgorithmOptions,
+) -> Option<(HashAlgorithm, &'a str, &'a str)> {
+    let (name, digest, path) = match line.rfind(") = ").or_else(|| line.rfind(")= ")) {
+        Some(end) => {
+            let (name, path) = line[..end].split_once('(')?;
+            let digest = line[end + 1..].trim_start_matches([' ', '=']);
+            (name.trim_end(), digest, path)
+        }
+        None => {
+            let (tag, path) = line.split_once("  ")?;
+            let (name, digest) = tag.split_once(':')?;
+            (name, digest, path)
+        }
+    };
+    if name.is_empty() || name.contains(' ') || digest.is_empty() || digest.contains(' ') {
+        return None;
+    }
+    let algorithm = HashAlgorithm::with_options(name, options).ok()?;
+    Some((algorithm, digest, path))
+}
+
 fn parse_line(
-    line: &str,
+    line: &Line,
     algorithms: &[HashAlgorithm],
     detected_algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
     entries: &mut Vec<ChecksumEntry>,
     line_number: usize,
 ) -> Result<()> {
+    if let Some((algorithm, digest, path)) = parse_tagged_line(&line.text, options) {
+        entries.push(ChecksumEntry {
+            hashes: vec![digest.to_string()],
+            path: line.path(path),
+            algorithm: Some(algorithm),
+        });
+        return Ok(());
+    }
+
     let num_fields = if !algorithms.is_empty() {
         algorithms.len()
     } else if !detected_algorithms.is_empty() {
@@ -149,7 +814,8 @@ fn parse_line(
         return Err(anyhow!("No algorithms specified or detected"));
     };
 
-    let parts: Vec<&str> = line.splitn(num_fields + 1, "  ").collect();
+    let (text, escaped) = strip_marker(&line.text);
+    let parts: Vec<&str> = text.splitn(num_fields + 1, "  ").collect();
     if parts.len() != num_fields + 1 {
         return Err(anyhow!(
             "Invalid checksum file format at line {}",
@@ -159,14 +825,20 @@ fn parse_line(
 
     entries.push(ChecksumEntry {
         hashes: parts[..num_fields].iter().map(|&s| s.to_string()).collect(),
-        path: PathBuf::from(parts[num_fields].trim_end_matches(['\r', '\n'])),
+        path: line
+            .parse_path(parts[num_fields], escaped)
+            .with_context(|| format!("Invalid path at line {}", line_number))?,
+        algorithm: None,
     });
 
     Ok(())
 }
 
 #[derive(Debug)]
-struct ChecksumEntry {
-    hashes: Vec<String>,
-    path: PathBuf,
+pub(crate) struct ChecksumEntry {
+    pub(crate) hashes: Vec<String>,
+    pub(crate) path: PathBuf,
+    /// The algorithm named by the line itself, instead of the columns of the
+    /// file.
+    pub(crate) algorithm: Option<HashAlgorithm>,
 }
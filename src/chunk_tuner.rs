@@ -0,0 +1,101 @@
+use std::str::FromStr;
+use std::time::Instant;
+
+/// The `--chunk-size` setting: a fixed number of bytes, or `auto`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkSize {
+    Fixed(usize),
+    Auto,
+}
+
+impl FromStr for ChunkSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(ChunkSize::Auto);
+        }
+        match s.parse::<usize>() {
+            Ok(0) => Err("chunk size must be greater than zero".to_string()),
+            Ok(size) => Ok(ChunkSize::Fixed(size)),
+            Err(_) => Err(format!("expected a number of bytes or 'auto', got '{}'", s)),
+        }
+    }
+}
+
+const MIN_CHUNK_SIZE: usize = 4 * 1024;
+const START_CHUNK_SIZE: usize = 256 * 1024;
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+/// Number of full chunks measured before the chunk size is settled.
+const PROBES: u32 = 8;
+
+/// Picks the chunk size for one file. Files smaller than the starting size are
+/// read with a buffer just large enough to hold them. For larger files, the
+/// chunk size doubles as long as doing so improves the measured throughput,
+/// and falls back to the best size seen once it stops improving.
+///
+/// Each measurement spans from the start of one read to the start of the next,
+/// so it covers both reading the chunk and waiting for the hash workers to
+/// accept it.
+pub struct ChunkTuner {
+    size: usize,
+    best: (usize, f64),
+    probes_left: u32,
+    started: Option<Instant>,
+    previous: Option<(Instant, usize)>,
+}
+
+impl ChunkTuner {
+    pub fn new(file_len: Option<u64>) -> Self {
+        let size = match file_len {
+            Some(len) if len < START_CHUNK_SIZE as u64 => {
+                (len as usize).next_power_of_two().max(MIN_CHUNK_SIZE)
+            }
+            _ => START_CHUNK_SIZE,
+        };
+        Self {
+            size,
+            best: (size, 0.0),
+            probes_left: PROBES,
+            started: None,
+            previous: None,
+        }
+    }
+
+    /// Returns the current chunk size.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the size to use for the next read.
+    pub fn next_size(&mut self) -> usize {
+        let now = Instant::now();
+        if let Some((started, bytes)) = self.previous.take() {
+            // Short reads say nothing about how the chunk size performs
+            if self.probes_left > 0 && bytes == self.size {
+                self.probes_left -= 1;
+                let elapsed = now.duration_since(started).as_secs_f64().max(1e-9);
+                let throughput = bytes as f64 / elapsed;
+                if throughput > self.best.1 * 1.1 {
+                    self.best = (self.size, throughput);
+                    if self.size < MAX_CHUNK_SIZE {
+                        self.size *= 2;
+                    }
+                } else {
+                    self.size = self.best.0;
+                    self.probes_left = 0;
+                }
+            }
+        }
+        self.started = Some(now);
+        self.size
+    }
+
+    /// Records the number of bytes returned by the read started after the
+    /// last call to `next_size`.
+    pub fn record(&mut self, bytes: usize) {
+        if let Some(started) = self.started.take() {
+            self.previous = Some((started, bytes));
+        }
+    }
+}
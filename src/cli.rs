@@ -1,41 +1,316 @@
+use clap::builder::BoolishValueParser;
 use clap::Parser;
 use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::archives::ArchiveFormat;
+use crate::checksum_verification::CheckFormat;
+use crate::chunk_tuner::ChunkSize;
+use crate::device_limiter::PerDeviceJobs;
+use crate::file_processing::{FileOrder, SpecialFiles, Symlinks};
+use crate::file_reader::IoBackend;
+use crate::output::{ColorMode, ProgressFormat, ProgressMode};
+use crate::output_format::ResultFormat;
+use crate::stats::StatsFormat;
+use crate::utils::{parse_size, parse_throughput, parse_time};
 
 #[derive(Parser, Debug)]
 #[command(
     author,
     version,
     about = "ParallelHash: Efficiently calculate cryptographic hashes of files using multiple algorithms in parallel",
-    long_about = "ParallelHash is a command-line application that calculates cryptographic hashes of files using multiple algorithms in parallel. It can process individual files or entire directories, and supports MD5, SHA1, SHA256, and SHA512 algorithms. The application is designed to optimize both I/O operations and CPU utilization, making it efficient for various file sizes and storage types."
+    long_about = "ParallelHash is a command-line application that calculates cryptographic hashes of files using multiple algorithms in parallel. It can process individual files or entire directories, and supports MD5, SHA1, the SHA2 and SHA3 families, BLAKE3 and Adler-32. The application is designed to optimize both I/O operations and CPU utilization, making it efficient for various file sizes and storage types."
 )]
 pub struct Args {
     #[arg(
         short,
         long,
+        env = "PARALLELHASH_ALGORITHMS",
         value_delimiter = ',',
-        help = "Comma-separated list of hash algorithms to use (md5, sha1, sha256, sha384, sha512, sha3-256, sha3-384, sha3-512, blake3)",
-        long_help = "Specify a comma-separated list of hash algorithms to use. Supported algorithms are md5, sha1, sha256 (or sha2-256), sha384 (or sha2-384), sha512 (or sha2-512), sha3-256, sha3-384, sha3-512, and blake3. Example: -a md5,sha256,blake3"
+        help = "Comma-separated list of hash algorithms to use (md5, sha1, sha256, sha384, sha512, sha3-256, sha3-384, sha3-512, blake3, adler32, crc32, highway64, highway128, highway256, ssdeep)",
+        long_help = "Specify a comma-separated list of hash algorithms to use. Supported algorithms are md5, sha1, sha256 (or sha2-256), sha384 (or sha2-384), sha512 (or sha2-512), sha3-256, sha3-384, sha3-512, blake3, adler32 (a lightweight non-cryptographic checksum, as used by zlib), crc32 (the checksum of SFV and ZIP files), and highway64, highway128 and highway256 (fast keyed non-cryptographic hashes, see --highway-key), and ssdeep (context-triggered piecewise fuzzy hash, see --fuzzy-match). Example: -a md5,sha256,blake3"
     )]
     pub algorithms: Vec<String>,
 
+    #[arg(
+        long,
+        env = "PARALLELHASH_HIGHWAY_KEY",
+        value_name = "HEX",
+        help = "256-bit key for HighwayHash, as 64 hex digits",
+        long_help = "Set the 256-bit key used by the highway64, highway128 and highway256 algorithms, given as 64 hex digits. If not provided, an all-zero key is used. The same key must be given when verifying checksums."
+    )]
+    pub highway_key: Option<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_HMAC_KEY",
+        value_name = "FILE",
+        conflicts_with = "hmac_key_hex",
+        help = "Wrap every selected md5, sha1, sha2 or sha3 digest in HMAC using the key read from FILE",
+        long_help = "Compute HMACs instead of plain digests, using the raw contents of FILE as the key. Every selected algorithm is wrapped, which md5, sha1, sha2-256, sha2-384, sha2-512, sha3-256, sha3-384 and sha3-512 support; selecting any other is an error, except entropy, which stays unkeyed. Manifests produced this way cannot be regenerated without the key, and the same key must be given when verifying them."
+    )]
+    pub hmac_key: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_HMAC_KEY_HEX",
+        value_name = "HEX",
+        help = "Wrap every selected md5, sha1, sha2 or sha3 digest in HMAC using a hex-encoded key",
+        long_help = "Same as --hmac-key, but the key is given on the command line as hex digits. Note that command lines may be visible to other users of the system."
+    )]
+    pub hmac_key_hex: Option<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_BLAKE3_KEY",
+        value_name = "HEX",
+        help = "Use BLAKE3's native keyed mode with a 256-bit key given as 64 hex digits",
+        long_help = "Use BLAKE3's native keyed mode with the given 256-bit key, given as 64 hex digits. This produces authenticated fingerprints without the overhead of the HMAC construction. The column is labeled BLAKE3-KEYED, and the same key must be given when verifying checksums."
+    )]
+    pub blake3_key: Option<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_BLAKE3_DERIVE_KEY",
+        value_name = "CONTEXT",
+        conflicts_with = "blake3_key",
+        help = "Use BLAKE3's derive_key mode with the given context string",
+        long_help = "Use BLAKE3's derive_key mode with the given context string, so the same files produce independent fingerprints for each application domain. The context should be hardcoded, globally unique and application-specific, e.g. \"example.com 2024-01-01 backup catalog\". The column is labeled BLAKE3-DERIVE-KEY, and the same context must be given when verifying checksums."
+    )]
+    pub blake3_derive_key: Option<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_BLAKE3_THREADS",
+        value_name = "N",
+        help = "Maximum number of threads BLAKE3 uses to hash each chunk (default: number of CPUs)",
+        long_help = "Limit BLAKE3's internal parallelism to N threads, shared by all files being hashed. By default BLAKE3 spreads each chunk over one thread per CPU, which competes with --jobs when many files are hashed at once. Use 1 to hash each chunk on its worker thread only."
+    )]
+    pub blake3_threads: Option<usize>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_ENTROPY",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Add a column with the Shannon entropy of each file",
+        long_help = "Compute the Shannon entropy of the byte distribution of each file (0 to 8 bits per byte) in the same read pass as the hashes, and add it as an ENTROPY output column. Values close to 8 indicate encrypted or compressed content."
+    )]
+    pub entropy: bool,
+
     #[arg(
         short,
         long,
+        env = "PARALLELHASH_CHECK",
         help = "Verify checksums from the specified file instead of computing new hashes",
-        long_help = "Verify checksums from the specified file instead of computing new hashes. The file should contain checksums in the same format as the output of this program."
+        long_help = "Verify checksums from the specified file instead of computing new hashes. The file should contain checksums in the same format as the output of this program, or as written by other tools, see --check-format. With a header, -a selects which of its columns are verified; without one, -a gives the algorithms of its columns. Files saved by Windows editors, with CRLF line endings and a byte order mark, are read too, and so are UTF-16 files, as written by PowerShell redirections. Lines may also name their own algorithm, which is then used for them only, so that files can mix algorithms: SHA256 (path) = digest as written by BSD tools and sha256sum --tag, SHA256(path)= digest as written by openssl, or sha256:digest  path."
     )]
     pub check: Option<PathBuf>,
 
     #[arg(
-        required_unless_present = "check",
+        long,
+        env = "PARALLELHASH_CHECK_FORMAT",
+        value_enum,
+        default_value_t = CheckFormat::Auto,
+        requires = "check",
+        help = "Format of the --check file",
+        long_help = "Select the format of the --check file. 'auto' (default) tells it from the first line of the file. 'native' reads the output of the text format, with lines that name their algorithm. 'coreutils' reads md5sum and sha256sum output, digest  path lines, telling the algorithm from the length of the digests as for rhash. 'bsd' reads lines that all name their algorithm, ALGORITHM (path) = digest, as BSD tools and sha256sum --tag write. 'sfv' reads Simple File Verification files, the path followed by its CRC32. 'hashdeep' reads hashdeep output, whose header names its columns; columns of algorithms parallelhash lacks, such as tiger, are skipped. 'rhash' reads catalogs written by rhash (or --format rhash): digest  path lines, as rhash writes for a single algorithm, or lines with the path followed by the digests, as it writes for several. As rhash writes no header, the algorithms are told apart by the length of the digests, taking SHA2-256, SHA2-384 and SHA2-512 for 64, 96 and 128 hex digits; give them with -a otherwise, e.g. for SHA3. Uppercase digests are accepted."
+    )]
+    pub check_format: CheckFormat,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_VERIFY_ONLY",
+        value_name = "ALGORITHMS",
+        value_delimiter = ',',
+        requires = "check",
+        conflicts_with = "algorithms",
+        help = "Only verify these columns of the --check file",
+        long_help = "Only verify the columns of the --check file for these comma-separated algorithms, named by its header, e.g. --verify-only sha256 to check a manifest with MD5, SHA1 and SHA2-256 columns faster. -a does the same with files that have a header; without one, -a gives the columns of the file instead."
+    )]
+    pub verify_only: Vec<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CHECK_DIR",
+        value_name = "DIR",
+        requires = "check",
+        help = "Resolve the relative paths of the --check file against DIR",
+        long_help = "Resolve the relative paths of the --check file against DIR instead of the current directory, to verify a tree that was moved or copied elsewhere without changing directory. Paths are still reported as listed. Absolute paths are left alone; see --strip-prefix and --add-prefix for those."
+    )]
+    pub check_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CHECK_FILTER",
+        value_name = "GLOB",
+        requires = "check",
+        help = "Only verify the entries of the --check file matching GLOB",
+        long_help = "Only verify the entries of the --check file whose path, as listed, matches the glob pattern GLOB, e.g. '*.iso', to check a subset of a large manifest without editing it. * and ? also match /, so '*.iso' selects ISO images in every directory; use ** and {a,b} as in gitignore patterns. Can be given several times to verify the entries matching any of them."
+    )]
+    pub check_filter: Vec<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_FAILED_ONLY",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        requires = "check",
+        help = "Only print the entries of the --check file that failed",
+        long_help = "Only print the FAILED lines of --check, for files whose contents differ or that are missing, leaving out the OK ones, which are what drowns the failures when verifying millions of entries. The files verified are still counted in the summary."
+    )]
+    pub failed_only: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_RECHECK_FAILURES",
+        value_name = "N",
+        default_value_t = 0,
+        requires = "check",
+        help = "Read and hash files that fail --check up to N more times",
+        long_help = "Read and hash each file whose digests do not match during --check up to N more times before reporting it as FAILED, so that transient read corruption, e.g. on network filesystems, is told apart from real mismatches. Its cached pages are dropped first (on Linux) so that the file is read from its storage again. A warning names the files that only matched when read again. Default is 0."
+    )]
+    pub recheck_failures: u32,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_IGNORE_HASH_CASE",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        requires = "check",
+        help = "Compare the digests of the --check file regardless of case",
+        long_help = "Compare the digests of the --check file with the computed ones regardless of case, for checksum files written with uppercase hex digits."
+    )]
+    pub ignore_hash_case: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_IGNORE_PATH_CASE",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        requires = "check",
+        help = "Find the files of the --check file whose path differs in case",
+        long_help = "When a file listed in the --check file does not exist, look for one whose path differs only in case, directory by directory, so that manifests made on a case-insensitive filesystem such as NTFS verify on a copy where lookups are case-sensitive, e.g. through a Samba mount. The path is still reported as listed. When several entries of a directory match, the first one found is used."
+    )]
+    pub ignore_path_case: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_VERIFY_SIGNATURE",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        requires = "check",
+        help = "Check the gpg signature of the checksum file before verifying it",
+        long_help = "Before verifying the checksums, check the gpg signature of the checksum file, looked for at the same path with .asc appended as written by --sign, and stop with an error if it is missing or bad. Both detached and clearsigned signatures are accepted; a clearsigned one must hold exactly the contents of the checksum file. Requires gpg on the PATH, with the signer's public key in its keyring."
+    )]
+    pub verify_signature: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_VERIFY_MINISIGN",
+        value_name = "PUBKEY",
+        requires = "check",
+        help = "Check the minisign or signify signature of the checksum file before verifying it",
+        long_help = "Before verifying the checksums, check the signature of the checksum file, looked for at the same path with .minisig or .sig appended, with the minisign or signify public key PUBKEY: a key file, or the key itself in base64 as printed by minisign. Stop with an error if the signature is missing or bad, and print the trusted comment of minisign signatures. No external program is needed."
+    )]
+    pub verify_minisign: Option<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CHECK_XATTR",
+        value_name = "DIR",
+        conflicts_with_all = ["check", "paths"],
+        help = "Verify the files under DIR against the hashes stored with --write-xattr (Unix only)",
+        long_help = "Hash the files under DIR again with the algorithms stored in their extended attributes by --write-xattr, and report each one as OK, FAILED when its content changed while its size and modification time did not (the classic signature of bit rot), or MODIFIED when it was changed since it was hashed. Files without stored hashes are skipped. Unix only."
+    )]
+    pub check_xattr: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CHECK_TORRENT",
+        num_args = 2,
+        value_names = ["TORRENT", "DIR"],
+        conflicts_with_all = ["check", "check_xattr", "paths"],
+        help = "Verify the files under DIR against the piece hashes of a .torrent file",
+        long_help = "Verify the files of the .torrent file TORRENT, downloaded under DIR, against its piece hashes, and report for each one how many of its pieces are complete, incomplete (data missing, from a missing or truncated file) and corrupt: OK when all are complete, INCOMPLETE when some are missing, FAILED when some are corrupt. v2 and hybrid torrents are verified file by file with their SHA-256 merkle trees. v1 torrents are verified with the SHA-1 of their pieces, which run across the files end to end, so a piece shared by two files counts for both."
+    )]
+    pub check_torrent: Option<Vec<PathBuf>>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CHECK_PAR2",
+        value_name = "PAR2",
+        conflicts_with_all = ["check", "check_xattr", "check_torrent", "paths"],
+        help = "Verify the data files of a PAR2 recovery set",
+        long_help = "Verify the data files of the PAR2 recovery set described by the .par2 file PAR2, which are looked for next to it, against the MD5 of each file, of its first 16 KiB and of each of its slices, and report for each one how many of its slices are complete, incomplete (data missing, from a missing or truncated file) and corrupt: OK when the file is whole, INCOMPLETE when it is missing or too short, FAILED when its contents differ. Damaged packets are skipped. Nothing is repaired; use par2 repair with the recovery volumes for that."
+    )]
+    pub check_par2: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_FUZZY_MATCH",
+        value_name = "MANIFEST",
+        conflicts_with = "check",
+        help = "Report ssdeep similarity scores of the given files against a manifest",
+        long_help = "Compute the ssdeep fuzzy hash of the given files and compare it against the SSDEEP column of MANIFEST (a file produced with -a ssdeep and --show-headers). Every pair with a non-zero similarity score (1-100) is reported, best matches first. Useful for malware triage and near-duplicate detection."
+    )]
+    pub fuzzy_match: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_SERVE",
+        value_name = "ADDRESS",
+        conflicts_with_all = ["check", "fuzzy_match", "paths"],
+        help = "Run as a daemon serving hash requests over HTTP on ADDRESS (e.g. 127.0.0.1:8080)",
+        long_help = "Run as a long-lived daemon serving hash requests over HTTP on ADDRESS, e.g. 127.0.0.1:8080, so other services can request hashes without starting a process per file. POST /hash with a JSON body {\"path\": \"...\", \"algorithms\": [\"sha256\"]} queues a file (algorithms default to -a) and returns its job id; GET /jobs/<id> returns the job status and, once done, its hashes. Up to --jobs files are hashed at once. Any file readable by the daemon can be hashed, so only listen on addresses trusted clients can reach. Needs the serve feature, which is off by default."
+    )]
+    pub serve: Option<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_BENCH",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        conflicts_with_all = ["check", "check_xattr", "check_torrent", "check_par2", "fuzzy_match", "serve"],
+        help = "Measure the throughput of the algorithms across chunk sizes and numbers of jobs",
+        long_help = "Instead of printing hashes, measure how fast each algorithm given with -a (or every built-in one) hashes synthetic data in memory at several chunk sizes, then, if PATHS are given, how fast all of them hash those sample files together at each chunk size with 1, 2, 4... up to one job per CPU, and print the results as tables in MiB/s. The samples are hashed once beforehand so that they are read from the page cache. Use it to pick --chunk-size and --jobs for your hardware."
+    )]
+    pub bench: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_SELF_TEST",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        conflicts_with_all = ["check", "check_xattr", "check_torrent", "check_par2", "fuzzy_match", "serve", "bench", "paths"],
+        help = "Check every algorithm against known answer vectors and report pass or fail",
+        long_help = "Instead of hashing files, check every built-in algorithm against published test vectors (the FIPS 180 and FIPS 202 examples, RFC 1321, and the BLAKE3, HighwayHash and Adler-32 reference vectors), then hash files of 0 bytes, 1 byte and sizes on both sides of the chunk boundaries, read and memory-mapped, and compare the digests with hashing the same data in one piece. A PASS or FAIL line is printed per check, and the exit status is 1 if any check failed. Useful to validate a build in regulated environments."
+    )]
+    pub self_test: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_DRY_RUN",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        conflicts_with_all = ["check", "check_xattr", "check_torrent", "check_par2", "fuzzy_match", "serve", "bench", "self_test"],
+        help = "List the files that would be hashed, with their sizes, without reading them",
+        long_help = "Instead of hashing files, walk PATHS with the same traversal and filtering options and print the size in bytes and path of each file that would be hashed, then the number of files and their total size on stderr. Nothing is read or written, not even the --output file. Use it to check include and exclude rules before committing to a long run."
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        required_unless_present_any = ["check", "check_xattr", "check_torrent", "check_par2", "serve", "bench", "self_test", "stdin", "files_from"],
         help = "File or directory paths to process",
-        long_help = "Specify one or more file or directory paths to process. If a directory is specified, all files within it (including subdirectories) will be processed."
+        long_help = "Specify one or more file or directory paths to process. If a directory is specified, all files within it (including subdirectories) will be processed. Use - to hash data piped on standard input. http:// and https:// URLs are downloaded and hashed as they stream in, without saving them; an interrupted download is resumed with a range request where the server supports it. s3://bucket/key URLs are read with the AWS SDK, using the usual AWS credentials and region settings. URLs need the http and s3 features, which are off by default."
     )]
     pub paths: Vec<PathBuf>,
 
     #[arg(
         short,
         long,
+        env = "PARALLELHASH_SHOW_HEADERS",
+        value_parser = BoolishValueParser::new(),
         default_value_t = false,
         help = "Show column headers in the output"
     )]
@@ -43,6 +318,28 @@ pub struct Args {
 
     #[arg(
         long,
+        env = "PARALLELHASH_METADATA",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        requires = "show_headers",
+        help = "Describe the run in comment lines above the header",
+        long_help = "Write comment lines starting with # above the header, with the parallelhash version, the date, the host name, each input path, the algorithms and the command line, so that manifests describe how they were made. --check, --resume and --fuzzy-match skip them. Only the text format has comments."
+    )]
+    pub metadata: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_COMMENT",
+        value_name = "TEXT",
+        help = "Write TEXT as a comment line at the top of the results",
+        long_help = "Write TEXT as a comment line starting with # at the top of the results, after the --metadata lines and before the header, e.g. to note why or for whom a manifest was made. Can be given several times. --check skips comment lines, as well as lines starting with ; (as in SFV files) and blank lines, so manifests can also be annotated by hand. Only the text format has comments."
+    )]
+    pub comment: Vec<String>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CONTINUE_ON_ERROR",
+        value_parser = BoolishValueParser::new(),
         default_value_t = false,
         help = "Continue processing files even if an error occurs",
         long_help = "Continue processing remaining files even if an error occurs while processing a file. By default, the program stops on the first error."
@@ -51,14 +348,267 @@ pub struct Args {
 
     #[arg(
         long,
+        env = "PARALLELHASH_SYMLINKS",
+        value_enum,
+        default_value_t = Symlinks::Follow,
+        help = "How symbolic links are handled",
+        long_help = "Select how symbolic links, both given as paths and found in directories, are handled. 'follow' (default) hashes the files they point to and descends into the directories they point to, except links leading back to one of their parent directories, which are listed with a (symlink loop) note instead of being walked forever. 'skip' leaves them out. 'report' lists them without hashing, with a (symlink) note. 'hash-link-text' hashes the target path stored in each link instead of what it points to, as some archival standards require."
+    )]
+    pub symlinks: Symlinks,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_NO_FOLLOW_SYMLINKS",
+        value_parser = BoolishValueParser::new(),
         default_value_t = false,
-        help = "Do not follow symbolic links when processing directories",
-        long_help = "Do not follow symbolic links when processing directories. By default, symbolic links are followed."
+        conflicts_with = "symlinks",
+        help = "Do not follow symbolic links (same as --symlinks report)",
+        long_help = "Do not follow symbolic links, listing them without hashing. This is the same as --symlinks report."
     )]
     pub no_follow_symlinks: bool,
 
     #[arg(
         long,
+        env = "PARALLELHASH_NO_DEDUP_HARDLINKS",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Hash every hard link to a file instead of reading each file once",
+        long_help = "Read and hash every hard link to a file separately. By default, the content of a file with several hard links is read once and its hashes are reused for the other links, which saves time on heavily hard-linked trees such as Maildir folders or backup snapshots. Unix only."
+    )]
+    pub no_dedup_hardlinks: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_WRITE_XATTR",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Store the hashes of each file in its extended attributes (Unix only)",
+        long_help = "Store the hashes of each file in its user.parallelhash.* extended attributes, one per algorithm (e.g. user.parallelhash.sha2-256), along with the size and modification time (user.parallelhash.size and user.parallelhash.mtime, in nanoseconds) the file had when it was hashed, so files carry their own checksums for later spot verification. Files whose attributes cannot be written are reported with a warning. Unix only."
+    )]
+    pub write_xattr: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_RESPECT_GITIGNORE",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Skip files matched by .gitignore and .ignore rules in directories",
+        long_help = "Skip files matched by .gitignore and .ignore rules (and .git/info/exclude) when processing directories, along with .git directories, so manifests of source trees leave out build artifacts. Rules in the parent directories of a given path apply too. Paths given explicitly are always processed."
+    )]
+    pub respect_gitignore: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_MAX_DEPTH",
+        value_name = "N",
+        help = "Descend at most N levels into directories",
+        long_help = "Descend at most N levels into directories, so only the top levels of a huge tree are hashed. With 1, only the files directly inside the given directories are processed; 0 processes none of their contents. By default there is no limit."
+    )]
+    pub max_depth: Option<usize>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_SKIP_HIDDEN",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Skip hidden files and directories",
+        long_help = "Skip hidden files and directories when processing directories: names starting with a dot and, on Windows, entries with the hidden attribute. Paths given explicitly are always processed."
+    )]
+    pub skip_hidden: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_ADS",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Also hash the alternate data streams of each file (Windows only)",
+        long_help = "Also hash the NTFS alternate data streams of each file, listed after the file as path:streamname, for forensic completeness. Windows only."
+    )]
+    pub ads: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_SPECIAL_FILES",
+        value_enum,
+        default_value_t = SpecialFiles::Skip,
+        help = "How FIFOs, sockets and device nodes found in directories are handled",
+        long_help = "Select how FIFOs, sockets and device nodes found in directories are handled. 'skip' (default) leaves them out, so a FIFO in the tree cannot hang the run. 'report' lists them without hashing, with their kind after the path. 'hash' hashes whatever can be read from them, which blocks on a FIFO until it is written to and closed. Paths given explicitly are always processed."
+    )]
+    pub special_files: SpecialFiles,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_ORDER",
+        value_enum,
+        default_value_t = FileOrder::Discovery,
+        help = "Order in which files are hashed",
+        long_help = "Select the order in which files are handed to the --jobs file workers. 'discovery' (default) hashes them as they are found. 'largest-first' waits for the whole walk, then starts with the largest files, so that a huge file does not start last and leave a long tail with one busy worker. 'smallest-first' does the opposite, e.g. to get through many small files quickly. Results are still written in traversal order unless --unordered is given."
+    )]
+    pub order: FileOrder,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_SORT",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Walk directories in sorted order for reproducible manifests",
+        long_help = "Walk the entries of each directory sorted by the bytes of their names, independent of the locale, instead of in the order the filesystem lists them, which differs between e.g. ext4 and XFS. Identical trees then always produce identical manifests. Input paths keep the order they are given in. Has no effect on the output order with --unordered."
+    )]
+    pub sort: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_MIN_SIZE",
+        value_name = "SIZE",
+        value_parser = parse_size,
+        help = "Skip files smaller than SIZE (e.g. 1, 10K, 1.5GiB)",
+        long_help = "Skip files smaller than SIZE, e.g. to leave out zero-byte placeholder files with --min-size 1. SIZE is a number of bytes with an optional unit: K, M, G and T (or KiB, MiB, ...) are powers of 1024, kB, MB, GB and TB powers of 1000."
+    )]
+    pub min_size: Option<u64>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_MAX_SIZE",
+        value_name = "SIZE",
+        value_parser = parse_size,
+        help = "Skip files larger than SIZE (e.g. 4G)",
+        long_help = "Skip files larger than SIZE, e.g. to leave out enormous disk images during sweeps. SIZE takes the same units as --min-size."
+    )]
+    pub max_size: Option<u64>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_NEWER_THAN",
+        value_name = "TIME",
+        value_parser = parse_time,
+        help = "Skip files last modified before TIME (e.g. 2024-05-01 or 7d)",
+        long_help = "Skip files last modified before TIME, so only recently changed files get hashed during incremental audits. TIME is either a UTC timestamp such as 2024-05-01, 2024-05-01 12:00:00 or 2024-05-01T12:00:00Z, or a duration before now such as 7d, 12h or 1h 30m."
+    )]
+    pub newer_than: Option<SystemTime>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_OLDER_THAN",
+        value_name = "TIME",
+        value_parser = parse_time,
+        help = "Skip files last modified after TIME (e.g. 2024-05-01 or 30d)",
+        long_help = "Skip files last modified after TIME, e.g. to leave out files still being written. TIME takes the same forms as --newer-than."
+    )]
+    pub older_than: Option<SystemTime>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_STDIN",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        conflicts_with = "serve",
+        help = "Hash data piped on standard input, same as giving - as a path",
+        long_help = "Hash data piped on standard input, in addition to any given paths. This is the same as giving - as a path."
+    )]
+    pub stdin: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_STDIN_NAME",
+        value_name = "NAME",
+        default_value = "-",
+        help = "Path printed for data read from standard input",
+        long_help = "Set the path printed in the results for data read from standard input, so piped streams can be recorded under a meaningful name. Default is -."
+    )]
+    pub stdin_name: String,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_ARCHIVE",
+        value_enum,
+        value_delimiter = ',',
+        value_name = "FORMAT",
+        help = "Also hash each member of archives of these formats (tar, zip)",
+        long_help = "Also hash each regular file inside archives of the given formats, listed after the archive as archive.tar!member/path, so archives do not have to be extracted for cataloging. The archive itself is hashed in the same pass. 'tar' reads .tar files, and data piped on standard input as a tar stream. 'zip' reads .zip files front to back without extracting them, checking the CRC32 stored for each member; members must be stored or deflated. Zip files whose members have no size in their local header, as streamed zips are written, are read again from their central directory. When the members of an archive cannot be read, the archive itself is still hashed and the failure is reported for its members only."
+    )]
+    pub archive: Vec<ArchiveFormat>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_DECOMPRESS",
+        value_parser = BoolishValueParser::new(),
+        help = "Also hash the decompressed content of .gz, .xz and .zst files",
+        long_help = "Also hash the decompressed content of .gz, .xz and .zst files, listed after the file as file.txt.gz!file.txt, so compressed logs can be verified against manifests made before compression. The compressed file itself is hashed in the same pass, and nothing is written to disk."
+    )]
+    pub decompress: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_S3_CHECK_ETAG",
+        value_parser = BoolishValueParser::new(),
+        help = "Check that s3:// objects match their ETag",
+        long_help = "Check that the content of each s3:// object matches its ETag, which S3 sets to the MD5 of the object, or for multipart uploads to the MD5 of the MD5s of its parts followed by the number of parts, so uploads can be validated. The part size is read from the first part of the object. Objects whose ETag does not match are reported as errors. Objects encrypted with SSE-KMS or SSE-C have ETags that are not MD5s and always fail this check."
+    )]
+    pub s3_check_etag: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_FILES_FROM",
+        value_name = "FILE",
+        conflicts_with_all = ["check", "fuzzy_match", "serve"],
+        help = "Read the paths to process from FILE, one per line (- for stdin)",
+        long_help = "Read the paths to process from FILE, one per line, in addition to any given paths, so an externally generated file list can be hashed exactly. Use - to read the list from standard input. Listed directories are traversed like directories given on the command line."
+    )]
+    pub files_from: Option<PathBuf>,
+
+    #[arg(
+        short = '0',
+        long = "null",
+        env = "PARALLELHASH_NULL",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        requires = "files_from",
+        help = "Paths in the --files-from list are separated by NUL bytes",
+        long_help = "Paths in the --files-from list are separated by NUL bytes instead of newlines, as written by find -print0, so names containing newlines are read correctly."
+    )]
+    pub null: bool,
+
+    #[arg(
+        short,
+        long,
+        env = "PARALLELHASH_JOBS",
+        help = "Number of files to hash concurrently (default: number of CPUs)",
+        long_help = "Set the number of files hashed concurrently. Each file is still hashed with all selected algorithms in parallel. Higher values help with directories of many small files; use 1 to process files strictly one at a time. Defaults to the number of CPUs."
+    )]
+    pub jobs: Option<usize>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_PER_DEVICE_JOBS",
+        value_name = "JOBS",
+        help = "Maximum number of files read at once from each device, or 'auto'",
+        long_help = "Limit the number of files read concurrently from each device (mount), so spinning disks are not thrashed by seeks while fast devices are still read by every job. With 'auto', spinning disks (as reported by the kernel, Linux only) are read one file at a time and other devices are not limited. By default there is no per-device limit."
+    )]
+    pub per_device_jobs: Option<PerDeviceJobs>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_UNORDERED",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Write results as soon as each file is done instead of in traversal order",
+        long_help = "Write each result as soon as its file has been hashed. By default, results of files hashed concurrently are buffered and written in traversal order so manifests are reproducible; this flag trades that for lower memory use and earlier output."
+    )]
+    pub unordered: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_PIN_THREADS",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Pin reading and hashing threads to their own CPU cores",
+        long_help = "Pin each file's reading thread and its hash workers to separate CPU cores instead of letting the scheduler move them around. This reduces cache thrashing on machines with many cores or several NUMA nodes. Threads wrap around when there are more of them than cores."
+    )]
+    pub pin_threads: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CHANNEL_SIZE",
         default_value_t = 10,
         help = "Size of the channel queue for parallel processing",
         long_help = "Set the size of the channel queue used for parallel processing. A larger value may improve performance but will use more memory. Default is 10."
@@ -67,17 +617,324 @@ pub struct Args {
 
     #[arg(
         long,
-        default_value_t = 1024 * 1024,
-        help = "Size of each chunk in bytes for file processing (default: 1MB)",
-        long_help = "Set the size of each chunk in bytes for file processing. Larger chunks may improve performance but will use more memory. Default is 1MB (1048576 bytes)."
+        env = "PARALLELHASH_CHUNK_SIZE",
+        default_value = "1048576",
+        help = "Size of each chunk in bytes for file processing, or 'auto' (default: 1MB)",
+        long_help = "Set the size of each chunk in bytes for file processing. Larger chunks may improve performance but will use more memory. Default is 1MB (1048576 bytes). With 'auto', the chunk size is picked per file: tiny files are read with a buffer just large enough to hold them, and for larger files the chunk size grows during the first chunks as long as the measured throughput improves. Memory-mapped, O_DIRECT and io_uring reads use the default size."
+    )]
+    pub chunk_size: ChunkSize,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_MMAP",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Memory-map large files instead of reading them into buffers",
+        long_help = "Hash files at least --mmap-threshold bytes large through a memory mapping instead of buffered reads. This avoids copying the data into userspace buffers and can improve throughput on fast storage. A file truncated by another process while it is being hashed may crash the program."
+    )]
+    pub mmap: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_MMAP_THRESHOLD",
+        default_value_t = 16 * 1024 * 1024,
+        requires = "mmap",
+        help = "Minimum file size in bytes for --mmap (default: 16MB)",
+        long_help = "Set the minimum size in bytes of files hashed through a memory mapping when --mmap is given. Smaller files are read normally, since mapping them costs more than it saves. Default is 16MB (16777216 bytes)."
+    )]
+    pub mmap_threshold: u64,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_IO_BACKEND",
+        value_enum,
+        default_value_t = IoBackend::Read,
+        help = "How file contents are read",
+        long_help = "Select how file contents are read. 'read' uses sequential buffered reads. 'uring' (Linux only) keeps several overlapping reads in flight through io_uring so the device queue stays full while earlier chunks are being hashed, which helps saturate fast SSDs."
+    )]
+    pub io_backend: IoBackend,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_DIRECT_IO",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        conflicts_with = "mmap",
+        help = "Read files with O_DIRECT, bypassing the page cache (Linux only)",
+        long_help = "Read files with O_DIRECT and aligned buffers, bypassing the page cache, so hashing datasets that will not be read again does not evict the rest of the system's cached data. Takes precedence over --io-backend. Files on filesystems without O_DIRECT support are read normally. Has no effect on other platforms."
+    )]
+    pub direct_io: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_FADVISE",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Hint sequential reads and drop hashed files from the page cache (Linux only)",
+        long_help = "Advise the kernel that each file is read sequentially when it is opened, and that its cached pages are no longer needed once it has been hashed, so large hashing or verification sweeps do not evict the rest of the system's page cache. Has no effect on other platforms."
+    )]
+    pub fadvise: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_RETRIES",
+        value_name = "N",
+        default_value_t = 0,
+        help = "Read a file again up to N times after a transient read error",
+        long_help = "When reading a file fails with an I/O error (EIO), a stale file handle or a timeout, as happens on flaky network filesystems, read it again from the start up to N times before reporting it as failed. A warning is printed for every retry. Standard input is never read again, and URLs resume their downloads instead. Default is 0."
+    )]
+    pub retries: u32,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_RETRY_DELAY",
+        value_name = "MS",
+        default_value_t = 1000,
+        help = "Milliseconds to wait before each retry",
+        long_help = "Wait this many milliseconds before reading a file again after a transient read error (see --retries). Default is 1000."
+    )]
+    pub retry_delay: u64,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_MAX_THROUGHPUT",
+        value_name = "MB/S",
+        value_parser = parse_throughput,
+        help = "Limit reading to this many MiB per second, e.g. 50",
+        long_help = "Limit the rate at which all files together are read to this many MiB per second, e.g. 50, or to a rate given with a unit as for --min-size, e.g. 500K for 500 KiB/s. Reading threads sleep as needed to keep within the limit, so background integrity scans do not starve other workloads sharing the same storage. Not limited by default."
+    )]
+    pub max_throughput: Option<u64>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_BACKGROUND",
+        value_parser = BoolishValueParser::new(),
+        help = "Run at the lowest CPU and I/O priority",
+        long_help = "Lower the CPU priority of the process to the lowest (nice 19) and, on Linux, put its reads in the idle I/O scheduling class, like `nice -n 19 ionice -c 3`. Long verification sweeps then only use the disks and processors when nothing else needs them, which makes them polite on shared servers. On Windows the process enters background mode, which lowers both."
     )]
-    pub chunk_size: usize,
+    pub background: bool,
 
     #[arg(
         short,
         long,
+        env = "PARALLELHASH_OUTPUT",
         help = "Output file path for results",
         long_help = "Specify a file path to write the results. If not provided, results will be written to stdout."
     )]
     pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_RELATIVE_TO",
+        value_name = "DIR",
+        help = "Write paths relative to DIR",
+        long_help = "Write the path of each file relative to DIR instead of in the form it was found in, e.g. data/file rather than /mnt/backup/data/file with --relative-to /mnt/backup, so manifests stay valid when the files move to another machine or mount point. Paths outside DIR start with ../ as needed. With --check, relative paths in the checksum file are read relative to DIR. DIR itself does not need to exist."
+    )]
+    pub relative_to: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_SIGN",
+        value_name = "KEYID",
+        num_args = 0..=1,
+        requires = "output",
+        conflicts_with_all = ["check", "check_xattr", "check_torrent", "check_par2", "fuzzy_match", "serve", "bench", "self_test", "dry_run"],
+        help = "Sign the finished output file with gpg",
+        long_help = "Once the run is finished, sign the --output file with gpg, using the key KEYID or gpg's default key when none is given, and write the ASCII-armored detached signature next to it with .asc appended, e.g. manifest.txt.asc. gpg asks for the passphrase itself if the key needs one. Check the signature later with --check --verify-signature. Requires gpg on the PATH."
+    )]
+    pub sign: Option<Option<String>>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CLEARSIGN",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        requires = "sign",
+        help = "Write a clearsigned copy of the output file instead of a detached signature",
+        long_help = "With --sign, write the .asc file as a clearsigned copy of the output file, readable on its own with the signature around it, instead of a detached signature."
+    )]
+    pub clearsign: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_MINISIGN",
+        value_name = "SECKEY",
+        requires = "output",
+        conflicts_with_all = ["check", "check_xattr", "check_torrent", "check_par2", "fuzzy_match", "serve", "bench", "self_test", "dry_run"],
+        help = "Sign the finished output file with a minisign or signify secret key",
+        long_help = "Once the run is finished, sign the --output file with the minisign or signify secret key in the file SECKEY, without any external program, for environments where gpg is unwelcome. The signature is written next to the output file in the format of the key: with .minisig appended for minisign keys (prehashed, with the time and file name as trusted comment), or .sig for signify keys, so that minisign -V or signify -V can check it too. The password of an encrypted minisign key is asked for on the terminal. Encrypted signify keys are not supported; create them with signify -n."
+    )]
+    pub minisign: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_STRIP_PREFIX",
+        value_name = "OLD",
+        help = "Remove the leading directories OLD from written paths",
+        long_help = "Remove the leading directories OLD from the path of each file that starts with them, after --relative-to, e.g. /mnt/a/data/file becomes data/file with --strip-prefix /mnt/a. Whole path components are matched, so /mnt/ab is left alone. Combine with --add-prefix to move paths to another mount point. With --check the rewriting is reversed: paths in the checksum file that start with the --add-prefix get it replaced with OLD again before the files are read."
+    )]
+    pub strip_prefix: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_ADD_PREFIX",
+        value_name = "NEW",
+        help = "Put the directories NEW in front of written paths",
+        long_help = "Put the directories NEW in front of the path of each file, after --strip-prefix (and only of the paths that started with its OLD when given, or of relative paths otherwise), e.g. /mnt/a/data/file is written as /srv/b/data/file with --strip-prefix /mnt/a --add-prefix /srv/b, for manifests created on one mount point and verified on another. With --check the rewriting is reversed, so that the same options read the manifest back."
+    )]
+    pub add_prefix: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_NORMALIZE_PATHS",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Read the paths of checksum files made on another system with its separators",
+        long_help = "Convert the path separators of the checksum file to those of this system when resolving its paths, \\ to / on Linux and macOS, so that manifests created on Windows verify there and the other way around. Applies to --check and --resume, before the reverse of --strip-prefix and --add-prefix, which are then given with the separators of this system."
+    )]
+    pub normalize_paths: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_FORMAT",
+        value_enum,
+        default_value_t = ResultFormat::Text,
+        conflicts_with_all = ["check", "check_xattr", "check_torrent", "check_par2", "fuzzy_match", "serve", "resume"],
+        help = "Format of the hashing results",
+        long_help = "Select the format of the hashing results. 'text' (default) writes the digests followed by the path, one file per line, as read back by --check. 'json' writes one JSON object per line with the path, size and digests of each file, or why it was not hashed, and a last object with the totals under \"summary\". 'csv' writes a path,size,<algorithm>...,note row per file, with a header row when --show-headers is given. Paths that are not valid UTF-8 are written with replacement characters in json and csv. 'digest' writes the values of HTTP integrity headers for uploading files to HTTP APIs, followed by the path as in the text format: a Content-MD5 line (RFC 1864) when MD5 is among the algorithms, then a Digest line (RFC 3230) such as Digest: MD5=<base64>,SHA-256=<base64>. Only MD5, SHA1 (named SHA), SHA2-256 and SHA2-512 have Digest names; other algorithms are left out. 'openssl' writes an ALGORITHM(path)= digest line per algorithm and file, as openssl dgst does, for scripts that expect its output verbatim. The algorithms have their OpenSSL 1.x names, e.g. SHA256 where OpenSSL 3 writes SHA2-256, the name parallelhash uses. 'rhash' writes what rhash does, for catalogs that rhash checks and --check-format rhash reads back: digest  path lines with a single algorithm, and the path followed by the digests, separated by spaces, with several."
+    )]
+    pub format: ResultFormat,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_STATS",
+        value_name = "FILE",
+        conflicts_with_all = ["check", "check_xattr", "check_torrent", "check_par2", "fuzzy_match", "serve", "bench", "self_test"],
+        help = "Write the time taken and throughput of each file to FILE",
+        long_help = "Record the wall time spent reading and hashing each file, with its size and throughput in MiB/s, and write them to FILE once the run finishes, as CSV or JSON Lines (see --stats-format). Useful to spot slow files or degraded disks during large sweeps. Archive members are not timed separately from their archive."
+    )]
+    pub stats: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_STATS_FORMAT",
+        value_enum,
+        default_value_t = StatsFormat::Csv,
+        requires = "stats",
+        help = "Format of the --stats file",
+        long_help = "Select the format of the --stats file. 'csv' (default) writes a header row and then a path,size,seconds,mib_per_second row per file. 'json' writes one JSON object per line with the same fields."
+    )]
+    pub stats_format: StatsFormat,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_SUMMARY_JSON",
+        value_name = "FILE",
+        help = "Write a JSON summary of the run to FILE, or to stderr for -",
+        long_help = "When the run finishes, write a JSON object with the number of files, bytes and errors, the elapsed time and throughput, and the time spent in each algorithm (summed over all threads) to FILE, or to stderr when FILE is -, so orchestration systems can record run metrics."
+    )]
+    pub summary_json: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_ERRORS_TO",
+        value_name = "FILE",
+        help = "Write per-file errors to FILE as JSON Lines instead of stderr",
+        long_help = "Write the error of every file that could not be hashed or verified, e.g. because it is unreadable, vanished while it was read or its permissions deny access, to FILE instead of stderr, so errors are not interleaved with progress output. Each line is a JSON object with the path, the kind of failure (not_found, permission_denied, read_error, ...), the byte the read failed at for read errors, and the error message."
+    )]
+    pub errors_to: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_RESUME",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        requires = "output",
+        conflicts_with_all = ["check", "fuzzy_match"],
+        help = "Continue an interrupted run, skipping files already in the output file",
+        long_help = "Continue a run that was interrupted: the files already listed in the --output file are skipped, and the results for the remaining files are appended to it. A last line cut short by the interruption is removed first. The same algorithms and paths as in the interrupted run must be given."
+    )]
+    pub resume: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CACHE",
+        value_name = "FILE",
+        conflicts_with_all = ["check", "fuzzy_match"],
+        help = "Reuse and store results in an SQLite cache, skipping unchanged files",
+        long_help = "Keep the results in an SQLite database at FILE, keyed by device, inode, size and modification time, and skip hashing files that have not changed since they were cached. Repeat runs over mostly static archives only hash what changed. Results for keyed algorithms are only reused with the same key."
+    )]
+    pub cache: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_CACHE_VERIFY",
+        value_name = "PERCENT",
+        requires = "cache",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        help = "Hash again a sample of the cache hits to check them (default: 1%)",
+        long_help = "Hash again PERCENT of the files found in the --cache (1% if no value is given), spread evenly over the run, and warn if the cached results no longer match the contents, e.g. because a file was modified without changing its modification time."
+    )]
+    pub cache_verify: Option<u8>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_COLOR",
+        value_enum,
+        default_value_t = ColorMode::Auto,
+        help = "Color OK and FAILED in check results",
+        long_help = "Color OK green and FAILED red in the results of --check. 'auto' (default) colors only when results are written to a terminal and the NO_COLOR environment variable is not set."
+    )]
+    pub color: ColorMode,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_PROGRESS",
+        value_enum,
+        default_value_t = ProgressMode::Auto,
+        help = "How to report progress on stderr",
+        long_help = "Select how progress is reported on stderr. 'auto' (default) shows a progress bar when stderr is a terminal and a plain line every ten seconds when it is redirected, so logs do not fill with redrawn frames. 'bar' and 'lines' force either form, and 'off' is the same as --no-progress."
+    )]
+    pub progress: ProgressMode,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_PROGRESS_FORMAT",
+        value_enum,
+        default_value_t = ProgressFormat::Text,
+        help = "Format of periodic progress reports",
+        long_help = "Select the format of periodic progress reports. 'json' writes one JSON object per second with the files and bytes done, the totals once known, the throughput and the file being hashed, for GUIs and orchestration tools. JSON reports replace the progress bar and are written even when stderr is a terminal, unless progress is turned off."
+    )]
+    pub progress_format: ProgressFormat,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_PROGRESS_FD",
+        value_name = "FD",
+        help = "Write progress reports to this file descriptor instead of stderr (Unix only)",
+        long_help = "Write periodic progress reports (plain lines or JSON) to the given open file descriptor instead of stderr, keeping them apart from warnings and errors. The progress bar is always drawn on stderr. Unix only."
+    )]
+    pub progress_fd: Option<i32>,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_NO_PROGRESS",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Do not show the progress bar on stderr",
+        long_help = "Do not show the progress bar on stderr. The summary line printed when hashing finishes is still shown."
+    )]
+    pub no_progress: bool,
+
+    #[arg(
+        long,
+        env = "PARALLELHASH_SILENT",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false,
+        help = "Show neither the progress bar nor the final summary on stderr",
+        long_help = "Show neither the progress bar nor the summary line printed when hashing finishes. Errors and warnings are still written to stderr."
+    )]
+    pub silent: bool,
 }
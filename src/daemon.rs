@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Context, Result};
+use crossbeam::channel::{unbounded, Receiver};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::file_processing::{compute_file_hashes, ReadOptions};
+use crate::hash_algorithms::{AlgorithmOptions, HashAlgorithm};
+use crate::output::format_digests;
+use crate::utils::{validate_algorithms, HashError};
+
+/// Number of finished jobs kept for `GET /jobs/<id>`; older ones are dropped.
+const MAX_FINISHED_JOBS: usize = 10_000;
+
+enum JobStatus {
+    Pending,
+    Running,
+    Done(Vec<String>),
+    Failed(String),
+}
+
+struct Job {
+    path: PathBuf,
+    algorithms: Vec<HashAlgorithm>,
+    status: JobStatus,
+}
+
+impl Job {
+    fn to_json(&self, id: u64) -> Value {
+        let mut value = json!({
+            "id": id,
+            "path": self.path.display().to_string(),
+        });
+        let (status, result) = match &self.status {
+            JobStatus::Pending => ("pending", None),
+            JobStatus::Running => ("running", None),
+            JobStatus::Done(hashes) => {
+                let hashes: serde_json::Map<String, Value> = self
+                    .algorithms
+                    .iter()
+                    .zip(hashes)
+                    .map(|(algo, hash)| (algo.to_string(), Value::from(hash.as_str())))
+                    .collect();
+                ("done", Some(("hashes", Value::Object(hashes))))
+            }
+            JobStatus::Failed(error) => ("failed", Some(("error", Value::from(error.as_str())))),
+        };
+        value["status"] = Value::from(status);
+        if let Some((key, result)) = result {
+            value[key] = result;
+        }
+        value
+    }
+}
+
+#[derive(Default)]
+struct JobTable {
+    jobs: BTreeMap<u64, Job>,
+    next_id: u64,
+    finished: usize,
+}
+
+impl JobTable {
+    fn set_status(&mut self, id: u64, status: JobStatus) {
+        let finished = matches!(status, JobStatus::Done(_) | JobStatus::Failed(_));
+        if let Some(job) = self.jobs.get_mut(&id) {
+            job.status = status;
+        }
+        if finished {
+            self.finished += 1;
+            // Job ids increase, so the first finished jobs are the oldest
+            while self.finished > MAX_FINISHED_JOBS {
+                let oldest = self.jobs.iter().find_map(|(&id, job)| {
+                    matches!(job.status, JobStatus::Done(_) | JobStatus::Failed(_)).then_some(id)
+                });
+                match oldest {
+                    Some(id) => {
+                        self.jobs.remove(&id);
+                        self.finished -= 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+/// Serves hash requests over HTTP on `address` until the process is stopped:
+///
+/// - `POST /hash` with a JSON body `{"path": "...", "algorithms": [...]}`
+///   queues a file and answers with its job id. `algorithms` is optional and
+///   defaults to the algorithms given on the command line.
+/// - `GET /jobs/<id>` returns the status of a job and, once it is done, its
+///   hashes.
+///
+/// Up to `jobs` files are hashed at once.
+pub fn serve(
+    address: &str,
+    algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
+    read_options: &ReadOptions,
+    jobs: usize,
+) -> Result<()> {
+    let server =
+        Server::http(address).map_err(|e| anyhow!("Failed to listen on {}: {}", address, e))?;
+    eprintln!("Listening on http://{}", server.server_addr());
+
+    let table = Mutex::new(JobTable::default());
+    let (work_sender, work_receiver) = unbounded::<u64>();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            let work_receiver = work_receiver.clone();
+            let table = &table;
+            scope.spawn(move || hash_jobs(table, read_options, work_receiver));
+        }
+
+        for mut request in server.incoming_requests() {
+            let (status, body) = match handle_request(&mut request, &table, algorithms, options) {
+                Ok((status, body, Some(queued))) => {
+                    // Cannot fail: the workers live as long as this loop
+                    let _ = work_sender.send(queued);
+                    (status, body)
+                }
+                Ok((status, body, None)) => (status, body),
+                Err(e) => (400, json!({ "error": format!("{:#}", e) })),
+            };
+            let response = Response::from_string(body.to_string())
+                .with_status_code(status)
+                .with_header(
+                    Header::from_bytes("Content-Type", "application/json").expect("valid header"),
+                );
+            if let Err(e) = request.respond(response) {
+                eprintln!("Failed to send response: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Returns the status code and body of the response, and the id of the job
+/// to queue if one was created.
+fn handle_request(
+    request: &mut Request,
+    table: &Mutex<JobTable>,
+    algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
+) -> Result<(u16, Value, Option<u64>)> {
+    let url = request.url().to_string();
+    match (request.method(), url.as_str()) {
+        (Method::Post, "/hash") => {
+            let mut body = String::new();
+            request
+                .as_reader()
+                .read_to_string(&mut body)
+                .context("Failed to read request body")?;
+            let body: Value = serde_json::from_str(&body).context("Invalid JSON body")?;
+            let path = body["path"]
+                .as_str()
+                .ok_or_else(|| anyhow!("Missing \"path\""))?;
+            let algorithms = match &body["algorithms"] {
+                Value::Null => algorithms.to_vec(),
+                Value::Array(names) => {
+                    let names = names
+                        .iter()
+                        .map(|name| name.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| anyhow!("\"algorithms\" must be a list of names"))?;
+                    validate_algorithms(&names, options)?
+                }
+                _ => return Err(anyhow!("\"algorithms\" must be a list of names")),
+            };
+            if algorithms.is_empty() {
+                return Err(anyhow!("No algorithms selected"));
+            }
+
+            let mut table = table.lock().unwrap_or_else(|e| e.into_inner());
+            let id = table.next_id;
+            table.next_id += 1;
+            let job = Job {
+                path: PathBuf::from(path),
+                algorithms,
+                status: JobStatus::Pending,
+            };
+            let body = job.to_json(id);
+            table.jobs.insert(id, job);
+            Ok((202, body, Some(id)))
+        }
+        (Method::Get, url) if url.starts_with("/jobs/") => {
+            let job = url["/jobs/".len()..].parse::<u64>().ok().and_then(|id| {
+                let table = table.lock().unwrap_or_else(|e| e.into_inner());
+                table.jobs.get(&id).map(|job| job.to_json(id))
+            });
+            match job {
+                Some(job) => Ok((200, job, None)),
+                None => Ok((404, json!({ "error": "No such job" }), None)),
+            }
+        }
+        _ => Ok((404, json!({ "error": "Not found" }), None)),
+    }
+}
+
+fn hash_jobs(table: &Mutex<JobTable>, read_options: &ReadOptions, work_receiver: Receiver<u64>) {
+    let lock = || table.lock().unwrap_or_else(|e| e.into_inner());
+    for id in work_receiver {
+        let Some((path, algorithms)) = lock().jobs.get_mut(&id).map(|job| {
+            job.status = JobStatus::Running;
+            (job.path.clone(), job.algorithms.clone())
+        }) else {
+            continue;
+        };
+        let status = match compute_file_hashes(&path, &algorithms, read_options, &mut |_| Ok(()))
+            .into_result()
+        {
+            Ok(result) => JobStatus::Done(format_digests(&result.digests)),
+            Err(HashError::NotFound(e)) => JobStatus::Failed(format!("File not found: {}", e)),
+            Err(e) => JobStatus::Failed(format!("{:#}", anyhow::Error::from(e))),
+        };
+        lock().set_status(id, status);
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Condvar, Mutex};
+
+/// The `--per-device-jobs` setting: a fixed number of files read at once from
+/// each device, or `auto` to pick it from the device type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PerDeviceJobs {
+    Fixed(usize),
+    Auto,
+}
+
+impl FromStr for PerDeviceJobs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(PerDeviceJobs::Auto);
+        }
+        match s.parse::<usize>() {
+            Ok(0) => Err("per-device jobs must be greater than zero".to_string()),
+            Ok(jobs) => Ok(PerDeviceJobs::Fixed(jobs)),
+            Err(_) => Err(format!("expected a number of jobs or 'auto', got '{}'", s)),
+        }
+    }
+}
+
+/// Caps the number of files read concurrently from each device, so that a
+/// spinning disk is read one stream at a time while other devices are still
+/// read by every job.
+pub struct DeviceLimiter {
+    setting: PerDeviceJobs,
+    /// Number of files being read and concurrency limit, per device id
+    devices: Mutex<HashMap<u64, (usize, usize)>>,
+    released: Condvar,
+}
+
+/// Holds a device slot until dropped.
+pub struct DevicePermit<'a> {
+    limiter: &'a DeviceLimiter,
+    device: Option<u64>,
+}
+
+impl DeviceLimiter {
+    pub fn new(setting: PerDeviceJobs) -> Self {
+        Self {
+            setting,
+            devices: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until the device holding `path` has a free slot. Paths whose
+    /// device cannot be determined are not limited.
+    pub fn acquire(&self, path: &Path) -> DevicePermit<'_> {
+        let Some(device) = device_id(path) else {
+            return DevicePermit {
+                limiter: self,
+                device: None,
+            };
+        };
+        let mut devices = self.devices.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let (active, limit) = devices
+                .entry(device)
+                .or_insert_with(|| (0, device_limit(self.setting, device)));
+            if *active < *limit {
+                *active += 1;
+                break;
+            }
+            devices = self
+                .released
+                .wait(devices)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        DevicePermit {
+            limiter: self,
+            device: Some(device),
+        }
+    }
+}
+
+impl Drop for DevicePermit<'_> {
+    fn drop(&mut self) {
+        let Some(device) = self.device else {
+            return;
+        };
+        let mut devices = self
+            .limiter
+            .devices
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some((active, _)) = devices.get_mut(&device) {
+            *active -= 1;
+        }
+        self.limiter.released.notify_all();
+    }
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+fn device_limit(setting: PerDeviceJobs, device: u64) -> usize {
+    match setting {
+        PerDeviceJobs::Fixed(jobs) => jobs,
+        PerDeviceJobs::Auto if is_rotational(device) => 1,
+        PerDeviceJobs::Auto => usize::MAX,
+    }
+}
+
+/// Whether the block device is a spinning disk, according to sysfs. Devices
+/// that cannot be looked up (network and virtual filesystems, other
+/// platforms) are treated as non-rotational.
+#[cfg(target_os = "linux")]
+fn is_rotational(device: u64) -> bool {
+    let (major, minor) = (libc::major(device), libc::minor(device));
+    // Partitions have no queue directory of their own; it lives in the
+    // parent disk's directory
+    ["queue/rotational", "../queue/rotational"]
+        .iter()
+        .filter_map(|file| {
+            std::fs::read_to_string(format!("/sys/dev/block/{}:{}/{}", major, minor, file)).ok()
+        })
+        .next()
+        .is_some_and(|value| value.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_rotational(_device: u64) -> bool {
+    false
+}
@@ -0,0 +1,54 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::os::unix::io::AsRawFd;
+
+use crate::buffer_pool::BufferPool;
+
+/// Alignment required by O_DIRECT for buffers, offsets and lengths. 4 KiB
+/// covers the logical block size of practically all current devices.
+const ALIGNMENT: usize = 4096;
+
+/// Reads a regular file with O_DIRECT, bypassing the page cache so that
+/// hashing large datasets does not evict everything else from memory.
+pub struct DirectReader {
+    file: File,
+    read_size: usize,
+    at_end: bool,
+}
+
+impl DirectReader {
+    /// Switches `file` to O_DIRECT. Fails if the filesystem does not support it.
+    pub fn new(file: &File, chunk_size: usize) -> io::Result<Self> {
+        let file = file.try_clone()?;
+        let fd = file.as_raw_fd();
+        // SAFETY: plain fcntl calls on a file descriptor we own
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_DIRECT) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(Self {
+            file,
+            read_size: chunk_size.div_ceil(ALIGNMENT) * ALIGNMENT,
+            at_end: false,
+        })
+    }
+
+    pub fn next_chunk(&mut self, pool: &BufferPool) -> io::Result<(Vec<u8>, Range<usize>)> {
+        let mut buffer = pool.get();
+        if self.at_end {
+            return Ok((buffer, 0..0));
+        }
+        // Pool buffers keep their capacity, so this only allocates the first
+        // time a buffer goes through here
+        buffer.resize(self.read_size + ALIGNMENT, 0);
+        let start = buffer.as_ptr().align_offset(ALIGNMENT);
+        let bytes_read = self.file.read(&mut buffer[start..start + self.read_size])?;
+        // A short read means the end of the file; reading again would be at an
+        // unaligned offset, which O_DIRECT rejects
+        self.at_end = bytes_read < self.read_size;
+        Ok((buffer, start..start + bytes_read))
+    }
+}
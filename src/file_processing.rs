@@ -1,156 +1,1072 @@
 use anyhow::{anyhow, Context, Result};
-use crossbeam::channel::{bounded, Receiver, RecvError, Sender};
-use std::fs::File;
-use std::io::{BufReader, Read};
+use clap::ValueEnum;
+use crossbeam::channel::{bounded, Receiver, Sender};
+use ignore::WalkBuilder;
+use memmap2::Mmap;
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs::{File, FileType};
+use std::io::{Seek, SeekFrom, Write};
+use std::iter;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use walkdir::WalkDir;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::hash_algorithms::{FileChunk, HashAlgorithm};
+use crate::affinity;
+use crate::alternate_streams;
+use crate::archives::{
+    hash_archive, hash_compressed, member_path, ArchiveFormat, ArchiveHashes, Compression,
+};
+use crate::buffer_pool::BufferPool;
+use crate::cancel::CancellationToken;
+use crate::device_limiter::{DeviceLimiter, PerDeviceJobs};
+use crate::file_reader::{CacheAdvice, FileReader, IoBackend};
+use crate::hard_links::HardLinks;
+use crate::hash_algorithms::{ChunkData, FileChunk, HashAlgorithm};
+use crate::hash_cache::{FileKey, HashCache};
 use crate::output::OutputManager;
+use crate::path_escape::result_line;
+use crate::progress::ProgressSink;
+use crate::remote;
+use crate::results::FileHashResult;
+use crate::stats::AlgorithmTimings;
+use crate::throttle::Throttle;
 use crate::utils::HashError;
+use crate::xattrs;
 
-pub fn compute_hashes(
+pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Settings for reading files and distributing their contents to the hash
+/// workers.
+#[derive(Clone, Debug)]
+pub struct ReadOptions {
+    /// Capacity of each hash worker's chunk queue.
+    pub channel_size: usize,
+    /// Number of bytes read and hashed at a time.
+    pub chunk_size: usize,
+    /// Let a `ChunkTuner` pick the chunk size of each buffered read instead
+    /// of always using `chunk_size`.
+    pub adaptive_chunk_size: bool,
+    /// When set, files at least this large are memory-mapped instead of read.
+    pub mmap_threshold: Option<u64>,
+    /// How files that are not memory-mapped are read.
+    pub io_backend: IoBackend,
+    /// Read files with O_DIRECT where supported (Linux only).
+    pub direct_io: bool,
+    /// Pin the reading thread and the hash workers of each file to their own
+    /// cores.
+    pub pin_threads: bool,
+    /// Hint sequential access and drop each file from the page cache after
+    /// hashing it (Linux only).
+    pub fadvise: bool,
+    /// Archive formats whose members `compute_hashes` hashes individually,
+    /// in addition to the archive itself.
+    pub archives: Vec<ArchiveFormat>,
+    /// Also hash the decompressed content of .gz, .xz and .zst files.
+    pub decompress: bool,
+    /// Compare the content of s3:// objects with their ETag.
+    pub s3_check_etag: bool,
+    /// Stops reading and hashing once cancelled.
+    pub cancel: CancellationToken,
+    /// Adds up the time spent in each algorithm.
+    pub timings: AlgorithmTimings,
+    /// How many times a file is read again from the start after a transient
+    /// read error, and how long to wait before each attempt.
+    pub retries: u32,
+    pub retry_delay: Duration,
+    /// Limits the rate at which files are read.
+    pub throttle: Option<Throttle>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            channel_size: 10,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            adaptive_chunk_size: false,
+            mmap_threshold: None,
+            io_backend: IoBackend::Read,
+            direct_io: false,
+            fadvise: false,
+            pin_threads: false,
+            archives: Vec::new(),
+            decompress: false,
+            s3_check_etag: false,
+            cancel: CancellationToken::new(),
+            timings: AlgorithmTimings::default(),
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            throttle: None,
+        }
+    }
+}
+
+impl ReadOptions {
+    /// Wraps the `on_bytes` of a read loop so that it waits as needed to
+    /// keep within the `throttle`.
+    pub(crate) fn throttled<'a>(
+        &'a self,
+        on_bytes: &'a mut dyn FnMut(u64) -> Result<()>,
+    ) -> impl FnMut(u64) -> Result<()> + 'a {
+        move |bytes| {
+            if let Some(throttle) = &self.throttle {
+                throttle.consume(bytes);
+            }
+            on_bytes(bytes)
+        }
+    }
+}
+
+/// What to do with FIFOs, sockets and device nodes found in directories.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SpecialFiles {
+    /// Leave them out
+    #[default]
+    Skip,
+    /// List them without hashing
+    Report,
+    /// Hash whatever can be read from them
+    Hash,
+}
+
+/// How symbolic links are handled, both given as input paths and found in
+/// directories.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Symlinks {
+    /// Hash the files they point to and descend into the directories they
+    /// point to
+    #[default]
+    Follow,
+    /// Leave them out
+    Skip,
+    /// List them without hashing
+    Report,
+    /// Hash the target path stored in the link instead of what it points to
+    HashLinkText,
+}
+
+/// In which order the files found are handed to the file workers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FileOrder {
+    /// As they are found
+    #[default]
+    Discovery,
+    /// Largest first, once the whole walk is done
+    LargestFirst,
+    /// Smallest first, once the whole walk is done
+    SmallestFirst,
+}
+
+/// Settings for walking the input paths.
+#[derive(Clone, Debug)]
+pub struct TraversalOptions {
+    /// How symbolic links are handled.
+    pub symlinks: Symlinks,
+    /// Skip files matched by `.gitignore` and `.ignore` rules, along with
+    /// `.git` directories.
+    pub respect_gitignore: bool,
+    /// How deep to descend into directories given as input paths, where 1
+    /// only includes the files directly inside them.
+    pub max_depth: Option<usize>,
+    /// Skip hidden files and directories: names starting with a dot, and on
+    /// Windows entries with the hidden attribute.
+    pub skip_hidden: bool,
+    /// Also hash the named data streams of each file (NTFS only).
+    pub alternate_streams: bool,
+    /// How special files found in directories are handled.
+    pub special_files: SpecialFiles,
+    /// Skip files smaller than this many bytes.
+    pub min_size: Option<u64>,
+    /// Skip files larger than this many bytes.
+    pub max_size: Option<u64>,
+    /// Skip files last modified before this time.
+    pub newer_than: Option<SystemTime>,
+    /// Skip files last modified after this time.
+    pub older_than: Option<SystemTime>,
+    /// In which order files are hashed. Results are still written in
+    /// traversal order unless the output is unordered.
+    pub order: FileOrder,
+    /// Walk the entries of each directory sorted by the bytes of their names
+    /// instead of in the order the filesystem lists them.
+    pub sort: bool,
+}
+
+impl Default for TraversalOptions {
+    fn default() -> Self {
+        Self {
+            symlinks: Symlinks::Follow,
+            respect_gitignore: false,
+            max_depth: None,
+            skip_hidden: false,
+            alternate_streams: false,
+            special_files: SpecialFiles::Skip,
+            min_size: None,
+            max_size: None,
+            newer_than: None,
+            older_than: None,
+            order: FileOrder::Discovery,
+            sort: false,
+        }
+    }
+}
+
+impl TraversalOptions {
+    /// Whether the file at `path` passes the file filters. Files whose
+    /// metadata cannot be read are kept, so that the error gets reported.
+    fn includes(&self, path: &Path) -> bool {
+        let filtered = self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.newer_than.is_some()
+            || self.older_than.is_some();
+        if !filtered || path.as_os_str() == STDIN_PATH {
+            return true;
+        }
+        let Ok(metadata) = path.metadata() else {
+            return true;
+        };
+        let len = metadata.len();
+        if !(self.min_size.is_none_or(|min| len >= min)
+            && self.max_size.is_none_or(|max| len <= max))
+        {
+            return false;
+        }
+        if self.newer_than.is_none() && self.older_than.is_none() {
+            return true;
+        }
+        let Ok(modified) = metadata.modified() else {
+            return true;
+        };
+        self.newer_than.is_none_or(|time| modified >= time)
+            && self.older_than.is_none_or(|time| modified <= time)
+    }
+}
+
+/// Events sent from the traversal and file workers to the thread that
+/// collects the results.
+pub(crate) enum FileEvent {
+    /// The scan has found more items to report, with the given total size.
+    Scanned {
+        files: usize,
+        bytes: u64,
+    },
+    /// The scan is complete.
+    ScanDone,
+    /// A file worker has started hashing this file.
+    Started(PathBuf),
+    Bytes(u64),
+    /// The result of a file, followed by those of its archive members.
+    Results {
+        index: usize,
+        results: Vec<FileHashResult>,
+    },
+    /// An item that could not be hashed, for a reason other than not being
+    /// found.
+    Failed {
+        index: usize,
+        path: PathBuf,
+        error: anyhow::Error,
+    },
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn compute_hashes(
     paths: &[PathBuf],
     algorithms: &[HashAlgorithm],
     show_headers: bool,
     continue_on_error: bool,
-    follow_symlinks: bool,
-    channel_size: usize,
-    chunk_size: usize,
+    traversal: &TraversalOptions,
+    jobs: usize,
+    per_device_jobs: Option<PerDeviceJobs>,
+    dedup_hard_links: bool,
+    write_xattr: bool,
+    ordered: bool,
+    skip_paths: &HashSet<PathBuf>,
+    cache: Option<&HashCache>,
+    stdin_name: &str,
+    read_options: &ReadOptions,
     output_manager: &mut OutputManager,
+    mut progress: Option<&mut dyn ProgressSink>,
 ) -> Result<()> {
     if show_headers {
-        let header = format!(
-            "{}  {}",
-            algorithms
-                .iter()
-                .map(|algo| algo.to_string())
-                .collect::<Vec<_>>()
-                .join("  "),
-            "path"
-        );
-        output_manager.write_result(&header)?;
+        output_manager.write_header(algorithms)?;
     }
 
-    for path in paths {
-        if let Err(e) = process_path(
-            path,
-            algorithms,
-            continue_on_error,
-            follow_symlinks,
-            channel_size,
-            chunk_size,
-            output_manager,
-        ) {
-            eprintln!("Error processing path {}: {}", path.display(), e);
-            if !continue_on_error {
-                return Err(e);
+    run_pipeline(
+        paths,
+        algorithms,
+        traversal,
+        jobs,
+        per_device_jobs,
+        dedup_hard_links,
+        write_xattr,
+        skip_paths,
+        cache,
+        stdin_name,
+        read_options,
+        &mut |event| {
+            match event {
+                FileEvent::Scanned { files, bytes } => {
+                    notify(output_manager, &mut progress, |sink| {
+                        sink.on_discovered(files, bytes)
+                    })
+                }
+                FileEvent::ScanDone => notify(output_manager, &mut progress, |sink| {
+                    sink.on_discovery_finished()
+                }),
+                FileEvent::Started(path) => notify(output_manager, &mut progress, |sink| {
+                    sink.on_file_started(&path)
+                }),
+                FileEvent::Bytes(bytes) => {
+                    notify(output_manager, &mut progress, |sink| sink.on_bytes(bytes))
+                }
+                FileEvent::Results { index, mut results } => {
+                    if let Some(result) = results.first() {
+                        notify(output_manager, &mut progress, |sink| {
+                            sink.on_file_finished(&result.path)
+                        });
+                    }
+                    for result in &mut results {
+                        result.path = output_manager.path_rewrite().apply(&result.path);
+                    }
+                    if ordered {
+                        output_manager.write_entries_at(index, results, algorithms)?;
+                    } else {
+                        output_manager.write_entries(&results, algorithms)?;
+                    }
+                }
+                FileEvent::Failed { index, path, error } => {
+                    notify(output_manager, &mut progress, |sink| {
+                        sink.on_file_finished(&path)
+                    });
+                    if is_cancelled(&error) {
+                        // Only the files done before the cancellation are
+                        // written
+                        if ordered {
+                            output_manager.write_entries_at(index, Vec::new(), algorithms)?;
+                        }
+                        return Ok(());
+                    }
+                    if !output_manager.log_error(&path, &*error)? {
+                        eprintln!("{:#}", error);
+                    }
+                    output_manager.count_error();
+                    if !continue_on_error {
+                        return Err(error);
+                    }
+                    if ordered {
+                        output_manager.write_entries_at(index, Vec::new(), algorithms)?;
+                    }
+                }
             }
-        }
-    }
+            Ok(())
+        },
+    )?;
 
     output_manager.finish()?;
+    read_options.cancel.check()?;
     Ok(())
 }
 
-fn process_path(
-    path: &Path,
-    algorithms: &[HashAlgorithm],
-    continue_on_error: bool,
-    follow_symlinks: bool,
-    channel_size: usize,
-    chunk_size: usize,
+/// Whether a file failed because the run was cancelled.
+pub(crate) fn is_cancelled(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref(), Some(HashError::Cancelled))
+}
+
+/// Reports progress to the terminal progress of `output_manager`, and to
+/// `progress` when the caller passed its own.
+fn notify(
     output_manager: &mut OutputManager,
+    progress: &mut Option<&mut dyn ProgressSink>,
+    report: impl Fn(&mut dyn ProgressSink),
+) {
+    report(output_manager);
+    if let Some(progress) = progress {
+        report(*progress);
+    }
+}
+
+/// Walks `paths` and hashes the files found, passing the events of the
+/// traversal and of the file workers to `on_event` on the calling thread.
+/// Stops early if `on_event` fails.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_pipeline(
+    paths: &[PathBuf],
+    algorithms: &[HashAlgorithm],
+    traversal: &TraversalOptions,
+    jobs: usize,
+    per_device_jobs: Option<PerDeviceJobs>,
+    dedup_hard_links: bool,
+    write_xattr: bool,
+    skip_paths: &HashSet<PathBuf>,
+    cache: Option<&HashCache>,
+    stdin_name: &str,
+    read_options: &ReadOptions,
+    on_event: &mut dyn FnMut(FileEvent) -> Result<()>,
 ) -> Result<()> {
-    if path.is_symlink() && !follow_symlinks {
-        let result = format!(
-            "{}  {} (symlink)",
-            vec!["N/A"; algorithms.len()].join("  "),
-            path.display()
-        );
-        output_manager.write_result(&result)?;
-        return Ok(());
-    }
-
-    if path.is_dir() {
-        for entry in WalkDir::new(path).follow_links(follow_symlinks) {
-            match entry {
-                Ok(entry) => {
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Err(e) =
-                            process_file(path, algorithms, channel_size, chunk_size, output_manager)
-                        {
-                            eprintln!("Error processing file {}: {}", path.display(), e);
-                            if !continue_on_error {
-                                return Err(anyhow!("Failed to process file: {}", path.display()));
+    let device_limiter = per_device_jobs.map(DeviceLimiter::new);
+    let hard_links = dedup_hard_links.then(HardLinks::new);
+
+    // One thread walks the paths, `jobs` threads hash files concurrently, and
+    // this thread collects their events. Another walk runs ahead of the first
+    // to total the sizes for the progress line. Returning early drops the
+    // receivers, which makes the other threads stop at their next send.
+    thread::scope(|scope| -> Result<()> {
+        let (work_sender, work_receiver) = bounded::<(usize, PathBuf)>(jobs.max(1) * 2);
+        let (event_sender, event_receiver) =
+            bounded::<FileEvent>(read_options.channel_size.max(1) * jobs.max(1));
+
+        let scan_events = event_sender.clone();
+        let cancel = &read_options.cancel;
+        scope.spawn(move || scan_sizes(paths, traversal, skip_paths, cancel, scan_events));
+
+        let traversal_events = event_sender.clone();
+        scope.spawn(move || {
+            enumerate_files(
+                paths,
+                algorithms,
+                traversal,
+                skip_paths,
+                cancel,
+                work_sender,
+                traversal_events,
+            )
+        });
+
+        for worker in 0..jobs.max(1) {
+            let work_receiver = work_receiver.clone();
+            let event_sender = event_sender.clone();
+            let device_limiter = device_limiter.as_ref();
+            let hard_links = hard_links.as_ref();
+            scope.spawn(move || {
+                if read_options.pin_threads {
+                    // Leave room for this file's hash workers on the
+                    // following cores
+                    affinity::pin_current_thread(worker * (algorithms.len() + 1));
+                }
+                file_worker(
+                    algorithms,
+                    read_options,
+                    device_limiter,
+                    hard_links,
+                    write_xattr,
+                    cache,
+                    stdin_name,
+                    work_receiver,
+                    event_sender,
+                )
+            });
+        }
+        drop(work_receiver);
+        drop(event_sender);
+
+        for event in event_receiver {
+            on_event(event)?;
+        }
+        Ok(())
+    })
+}
+
+/// An item found while walking the input paths.
+enum WalkItem {
+    File(PathBuf),
+    /// An item listed without hashing, with a note on what it is: a symlink
+    /// given as an input path while symlinks are not followed, a special
+    /// file found in a directory while they are reported, or a symlink
+    /// leading back to one of its parent directories.
+    Listed(PathBuf, &'static str),
+    /// A symlink whose target path is hashed.
+    LinkText(PathBuf),
+    Error(ignore::Error),
+}
+
+/// Walks `paths` in order, descending into directories, and yields the files
+/// to hash along with the items that are reported without hashing. Items in
+/// `skip_paths` are left out.
+fn walk<'a>(
+    paths: &'a [PathBuf],
+    traversal: &'a TraversalOptions,
+    skip_paths: &'a HashSet<PathBuf>,
+) -> impl Iterator<Item = WalkItem> + 'a {
+    paths
+        .iter()
+        .flat_map(move |path| -> Box<dyn Iterator<Item = WalkItem>> {
+            if path.as_os_str() == STDIN_PATH || remote::is_url(path) {
+                Box::new(iter::once(WalkItem::File(path.clone())))
+            } else if path.is_symlink() && traversal.symlinks != Symlinks::Follow {
+                Box::new(symlink_item(path.clone(), traversal.symlinks).into_iter())
+            } else if path.is_dir() {
+                Box::new(directory_walker(path, traversal).build().filter_map(
+                    |entry| match entry {
+                        Ok(entry) => {
+                            if entry.path_is_symlink() && traversal.symlinks != Symlinks::Follow {
+                                return symlink_item(entry.into_path(), traversal.symlinks);
+                            }
+                            let metadata = entry.path().metadata().ok()?;
+                            if metadata.is_file() {
+                                return Some(WalkItem::File(entry.into_path()));
+                            }
+                            let kind = special_file_kind(&metadata.file_type())?;
+                            match traversal.special_files {
+                                SpecialFiles::Skip => None,
+                                SpecialFiles::Report => {
+                                    Some(WalkItem::Listed(entry.into_path(), kind))
+                                }
+                                SpecialFiles::Hash => Some(WalkItem::File(entry.into_path())),
                             }
                         }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error accessing entry: {}", e);
-                    if !continue_on_error {
-                        return Err(anyhow!("Failed to access entry"));
-                    }
+                        Err(e) => Some(match loop_link(&e) {
+                            Some(link) => WalkItem::Listed(link.to_path_buf(), "symlink loop"),
+                            None => WalkItem::Error(e),
+                        }),
+                    },
+                ))
+            } else {
+                Box::new(iter::once(WalkItem::File(path.clone())))
+            }
+        })
+        .flat_map(move |item| -> Box<dyn Iterator<Item = WalkItem>> {
+            match item {
+                WalkItem::File(path)
+                    if traversal.alternate_streams && path.as_os_str() != STDIN_PATH =>
+                {
+                    let streams = alternate_streams::stream_paths(&path);
+                    Box::new(
+                        iter::once(WalkItem::File(path))
+                            .chain(streams.into_iter().map(WalkItem::File)),
+                    )
                 }
+                item => Box::new(iter::once(item)),
             }
-        }
-        Ok(())
+        })
+        .filter(move |item| match item {
+            WalkItem::File(path) => !skip_paths.contains(path) && traversal.includes(path),
+            WalkItem::Listed(path, _) | WalkItem::LinkText(path) => !skip_paths.contains(path),
+            WalkItem::Error(_) => true,
+        })
+}
+
+/// Names the kind of a FIFO, socket or device node.
+#[cfg(unix)]
+fn special_file_kind(file_type: &FileType) -> Option<&'static str> {
+    use std::os::unix::fs::FileTypeExt;
+    if file_type.is_fifo() {
+        Some("fifo")
+    } else if file_type.is_socket() {
+        Some("socket")
+    } else if file_type.is_block_device() {
+        Some("block device")
+    } else if file_type.is_char_device() {
+        Some("character device")
     } else {
-        process_file(path, algorithms, channel_size, chunk_size, output_manager)
+        None
     }
 }
 
-fn process_file(
-    path: &Path,
+#[cfg(not(unix))]
+fn special_file_kind(_file_type: &FileType) -> Option<&'static str> {
+    None
+}
+
+/// Returns what a symlink turns into under a policy other than following it.
+fn symlink_item(path: PathBuf, symlinks: Symlinks) -> Option<WalkItem> {
+    match symlinks {
+        Symlinks::Follow => Some(WalkItem::File(path)),
+        Symlinks::Skip => None,
+        Symlinks::Report => Some(WalkItem::Listed(path, "symlink")),
+        Symlinks::HashLinkText => Some(WalkItem::LinkText(path)),
+    }
+}
+
+/// Returns the symlink that caused `error` if it is a directory loop.
+fn loop_link(error: &ignore::Error) -> Option<&Path> {
+    match error {
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => loop_link(err),
+        _ => None,
+    }
+}
+
+/// Returns the path an error happened on, if it has one.
+fn error_path(error: &ignore::Error) -> Option<&Path> {
+    match error {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        _ => None,
+    }
+}
+
+/// Configures the walk of a directory given as an input path.
+fn directory_walker(path: &Path, traversal: &TraversalOptions) -> WalkBuilder {
+    let gitignore = traversal.respect_gitignore;
+    let mut builder = WalkBuilder::new(path);
+    builder
+        .standard_filters(false)
+        .follow_links(traversal.symlinks == Symlinks::Follow)
+        .max_depth(traversal.max_depth)
+        .hidden(traversal.skip_hidden)
+        .git_ignore(gitignore)
+        .git_exclude(gitignore)
+        .ignore(gitignore)
+        .parents(gitignore)
+        .require_git(false);
+    if gitignore {
+        builder.filter_entry(|entry| entry.file_name() != ".git");
+    }
+    if traversal.sort {
+        // Byte order rather than the locale's, so that every machine agrees
+        builder.sort_by_file_name(|a, b| a.as_encoded_bytes().cmp(b.as_encoded_bytes()));
+    }
+    builder
+}
+
+/// Queues every file found by `walk` to hash and reports the items that need
+/// no hashing (unfollowed symlinks, reported special files, symlink loops,
+/// traversal errors) directly. Each item is numbered with its traversal position so that the
+/// output can be put back in order. Unless files are hashed in discovery
+/// order, they are only queued once the whole walk is done, sorted by size.
+fn enumerate_files(
+    paths: &[PathBuf],
     algorithms: &[HashAlgorithm],
-    channel_size: usize,
-    chunk_size: usize,
-    output_manager: &mut OutputManager,
-) -> Result<()> {
-    match compute_file_hashes(path, algorithms, channel_size, chunk_size, output_manager) {
-        Ok(hashes) => {
-            let result = format!("{}  {}", hashes.join("  "), path.display());
-            output_manager.write_result(&result)?;
-            Ok(())
+    traversal: &TraversalOptions,
+    skip_paths: &HashSet<PathBuf>,
+    cancel: &CancellationToken,
+    work_sender: Sender<(usize, PathBuf)>,
+    event_sender: Sender<FileEvent>,
+) {
+    let mut sorted_files = Vec::new();
+    for (index, item) in walk(paths, traversal, skip_paths).enumerate() {
+        if cancel.is_cancelled() {
+            return;
         }
-        Err(HashError::FileNotFound(e)) => {
-            let result = format!(
-                "{}  {}  (File not found: {})",
-                vec!["N/A"; algorithms.len()].join("  "),
-                path.display(),
-                e
-            );
-            output_manager.write_result(&result)?;
-            Ok(())
+        let sent = match item {
+            WalkItem::File(path) if traversal.order != FileOrder::Discovery => {
+                sorted_files.push((input_size(&path), index, path));
+                true
+            }
+            WalkItem::File(path) => work_sender.send((index, path)).is_ok(),
+            WalkItem::Listed(path, note) => event_sender
+                .send(FileEvent::Results {
+                    index,
+                    results: vec![FileHashResult::listed(path, note)],
+                })
+                .is_ok(),
+            // Link targets are short, so they are hashed right away
+            WalkItem::LinkText(path) => match hash_link_text(&path, algorithms) {
+                Ok((hashes, size)) => event_sender
+                    .send(FileEvent::Results {
+                        index,
+                        results: vec![FileHashResult::new(path, algorithms, hashes, size)],
+                    })
+                    .is_ok(),
+                Err(error) => event_sender
+                    .send(FileEvent::Failed { index, path, error })
+                    .is_ok(),
+            },
+            WalkItem::Error(e) => event_sender
+                .send(FileEvent::Failed {
+                    index,
+                    path: error_path(&e).map(Path::to_path_buf).unwrap_or_default(),
+                    error: anyhow!("Error accessing entry: {}", e),
+                })
+                .is_ok(),
+        };
+
+        if !sent {
+            // The collecting side has stopped
+            return;
+        }
+    }
+
+    match traversal.order {
+        FileOrder::Discovery => return,
+        FileOrder::LargestFirst => sorted_files.sort_by_key(|file| Reverse(file.0)),
+        FileOrder::SmallestFirst => sorted_files.sort_by_key(|file| file.0),
+    }
+    for (_, index, path) in sorted_files {
+        if cancel.is_cancelled() || work_sender.send((index, path)).is_err() {
+            return;
+        }
+    }
+}
+
+/// An item found by `walk_files`.
+#[cfg(feature = "async")]
+pub(crate) enum Walked {
+    /// A file for the caller to read.
+    File(PathBuf),
+    /// The result of an item that needs no reading.
+    Done(FileHashResult),
+}
+
+/// Walks `paths` like `compute_hashes` does, for callers that read the files
+/// themselves. Listed items, symlinks whose target path is hashed and entries
+/// that cannot be accessed come with their result.
+#[cfg(feature = "async")]
+pub(crate) fn walk_files<'a>(
+    paths: &'a [PathBuf],
+    algorithms: &'a [HashAlgorithm],
+    traversal: &'a TraversalOptions,
+    skip_paths: &'a HashSet<PathBuf>,
+) -> impl Iterator<Item = Walked> + 'a {
+    walk(paths, traversal, skip_paths).map(move |item| match item {
+        WalkItem::File(path) => Walked::File(path),
+        WalkItem::Listed(path, note) => Walked::Done(FileHashResult::listed(path, note)),
+        WalkItem::LinkText(path) => Walked::Done(match hash_link_text(&path, algorithms) {
+            Ok((hashes, size)) => FileHashResult::new(path, algorithms, hashes, size),
+            Err(error) => FileHashResult::failed(path, HashError::Other(error)),
+        }),
+        WalkItem::Error(e) => Walked::Done(FileHashResult::failed(
+            error_path(&e).map(Path::to_path_buf).unwrap_or_default(),
+            HashError::Other(anyhow!("Error accessing entry: {}", e)),
+        )),
+    })
+}
+
+/// Walks `paths` like `compute_hashes` does and writes the size and path of
+/// each file it would hash to `writer`, without reading any of them. Returns
+/// the number of files and their total size.
+pub(crate) fn list_files(
+    paths: &[PathBuf],
+    traversal: &TraversalOptions,
+    skip_paths: &HashSet<PathBuf>,
+    writer: &mut dyn Write,
+) -> Result<(usize, u64)> {
+    let mut files = 0;
+    let mut bytes = 0;
+    for item in walk(paths, traversal, skip_paths) {
+        let (path, size) = match item {
+            WalkItem::File(path) => {
+                let size = input_size(&path);
+                (path, size)
+            }
+            WalkItem::LinkText(path) => {
+                let size = std::fs::read_link(&path).map_or(0, |target| {
+                    target.as_os_str().as_encoded_bytes().len() as u64
+                });
+                (path, size)
+            }
+            WalkItem::Listed(..) => continue,
+            WalkItem::Error(e) => {
+                eprintln!("Error accessing entry: {}", e);
+                continue;
+            }
+        };
+        writeln!(writer, "{}", result_line(&size.to_string(), &path))
+            .map_err(HashError::OutputError)?;
+        files += 1;
+        bytes += size;
+    }
+    Ok((files, bytes))
+}
+
+/// Number of files counted between two reports of the scan.
+const SCAN_BATCH: usize = 1000;
+
+/// Walks `paths` like `enumerate_files`, but only counts the items it would
+/// report and sums the sizes of the files, so the progress line can show how
+/// much work is left.
+fn scan_sizes(
+    paths: &[PathBuf],
+    traversal: &TraversalOptions,
+    skip_paths: &HashSet<PathBuf>,
+    cancel: &CancellationToken,
+    event_sender: Sender<FileEvent>,
+) {
+    let mut files = 0;
+    let mut bytes = 0;
+    for item in walk(paths, traversal, skip_paths) {
+        if cancel.is_cancelled() {
+            return;
+        }
+        files += 1;
+        if let WalkItem::File(path) = item {
+            bytes += input_size(&path);
+        }
+        if files == SCAN_BATCH {
+            if event_sender
+                .send(FileEvent::Scanned { files, bytes })
+                .is_err()
+            {
+                return;
+            }
+            (files, bytes) = (0, 0);
+        }
+    }
+    let _ = event_sender.send(FileEvent::Scanned { files, bytes });
+    let _ = event_sender.send(FileEvent::ScanDone);
+}
+
+/// Returns the number of bytes that will be read from the file at `path`.
+/// Block devices have no length in their metadata, so their size is found by
+/// seeking to their end.
+fn input_size(path: &Path) -> u64 {
+    let Ok(metadata) = path.metadata() else {
+        return 0;
+    };
+    #[cfg(unix)]
+    if std::os::unix::fs::FileTypeExt::is_block_device(&metadata.file_type()) {
+        return File::open(path)
+            .and_then(|mut file| file.seek(SeekFrom::End(0)))
+            .unwrap_or(0);
+    }
+    metadata.len()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn file_worker(
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    device_limiter: Option<&DeviceLimiter>,
+    hard_links: Option<&HardLinks>,
+    write_xattr: bool,
+    cache: Option<&HashCache>,
+    stdin_name: &str,
+    work_receiver: Receiver<(usize, PathBuf)>,
+    event_sender: Sender<FileEvent>,
+) {
+    for (index, path) in work_receiver {
+        if read_options.cancel.is_cancelled() {
+            return;
+        }
+        let name = if path.as_os_str() == STDIN_PATH {
+            Path::new(stdin_name)
+        } else {
+            path.as_path()
+        };
+        let _permit = device_limiter.map(|limiter| limiter.acquire(&path));
+        // Taken before hashing, so a change made meanwhile is noticed later
+        let metadata = (write_xattr && path.as_os_str() != STDIN_PATH)
+            .then(|| path.metadata().ok())
+            .flatten();
+        if event_sender.send(FileEvent::Started(path.clone())).is_err() {
+            return;
+        }
+        let started = Instant::now();
+        let mut size = 0;
+        let mut report_bytes = |bytes| {
+            // Archives and downloads are read outside of `file_digests`
+            read_options.cancel.check()?;
+            size += bytes;
+            event_sender
+                .send(FileEvent::Bytes(bytes))
+                .map_err(|_| anyhow!("Output has been closed"))
+        };
+        let remote = remote::is_url(&path);
+        let hash = |on_bytes: &mut dyn FnMut(u64) -> Result<()>| match cache {
+            // Standard input and URLs have no metadata to key the cache on
+            Some(cache) if path.as_os_str() != STDIN_PATH && !remote => {
+                cached_file_hashes(&path, algorithms, read_options, cache, on_bytes)
+            }
+            _ => file_digests(&path, algorithms, read_options, on_bytes),
+        };
+        // Downloads are only hashed as a whole
+        let archive = (!remote)
+            .then(|| ArchiveFormat::detect(&path, &read_options.archives))
+            .flatten();
+        let compression = (read_options.decompress && !remote)
+            .then(|| Compression::detect(&path))
+            .flatten();
+        let hashes = match (archive, compression, hard_links) {
+            (Some(format), _, _) => {
+                hash_archive(&path, format, algorithms, read_options, &mut report_bytes)
+            }
+            (None, Some(compression), _) => hash_compressed(
+                &path,
+                compression,
+                algorithms,
+                read_options,
+                &mut report_bytes,
+            ),
+            (None, None, Some(hard_links)) if path.as_os_str() != STDIN_PATH => hard_links
+                .hashes(&path, &mut report_bytes, hash)
+                .map(ArchiveHashes::from),
+            (None, None, _) => hash(&mut report_bytes).map(ArchiveHashes::from),
+        };
+        if let (Ok(hashes), Some(metadata)) = (&hashes, &metadata) {
+            if let Err(e) = xattrs::write(&path, algorithms, &hashes.hashes, metadata) {
+                eprintln!("Warning: {:#}", e);
+            }
+        }
+        let event = match hashes {
+            Ok(hashes) => FileEvent::Results {
+                index,
+                // Archive members and decompressed content follow the file
+                // itself
+                results: iter::once(FileHashResult {
+                    elapsed: Some(started.elapsed()),
+                    ..FileHashResult::new(name.to_path_buf(), algorithms, hashes.hashes, size)
+                })
+                .chain(hashes.members.into_iter().map(|mut member| {
+                    member.path = member_path(name, &member.path);
+                    member
+                }))
+                .collect(),
+            },
+            Err(HashError::NotFound(e)) => FileEvent::Results {
+                index,
+                results: vec![FileHashResult::failed(
+                    name.to_path_buf(),
+                    HashError::NotFound(e),
+                )],
+            },
+            Err(e) => FileEvent::Failed {
+                index,
+                path: name.to_path_buf(),
+                error: anyhow::Error::new(e)
+                    .context(format!("Error processing file {}", name.display())),
+            },
+        };
+        if event_sender.send(event).is_err() {
+            return;
         }
-        Err(HashError::Other(e)) => Err(e),
     }
 }
 
+/// Returns the cached hashes of the file if it has not changed since they
+/// were stored, and hashes it and stores the results otherwise. Cache hits
+/// selected for verification are hashed again and compared.
+fn cached_file_hashes(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    cache: &HashCache,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    let key = path
+        .metadata()
+        .ok()
+        .and_then(|metadata| FileKey::new(path, &metadata));
+    let Some(key) = key else {
+        return file_digests(path, algorithms, read_options, on_bytes);
+    };
+
+    let cached = cache.get(&key)?;
+    if let Some(cached) = &cached {
+        if !cache.should_verify() {
+            // Count the file as done for the progress
+            on_bytes(key.size)?;
+            return Ok(cached.clone());
+        }
+    }
+
+    let hashes = file_digests(path, algorithms, read_options, on_bytes)?;
+    if cached.is_some_and(|cached| cached != hashes) {
+        eprintln!(
+            "Warning: cached hashes of {} do not match its contents, replacing them",
+            path.display()
+        );
+    }
+    cache.put(&key, &hashes)?;
+    Ok(hashes)
+}
+
+/// Hashes the file at `path` with each of `algorithms`, calling `on_bytes`
+/// with the number of bytes read as it goes.
 pub fn compute_file_hashes(
     path: &Path,
     algorithms: &[HashAlgorithm],
-    channel_size: usize,
-    chunk_size: usize,
-    output_manager: &mut OutputManager,
-) -> Result<Vec<String>, HashError> {
-    let file = File::open(path).map_err(|e| {
-        if e.kind() == std::io::ErrorKind::NotFound {
-            HashError::FileNotFound(e)
-        } else {
-            HashError::Other(e.into())
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> FileHashResult {
+    let mut size = 0;
+    let digests = file_digests(path, algorithms, read_options, &mut |bytes| {
+        size += bytes;
+        on_bytes(bytes)
+    });
+    match digests {
+        Ok(digests) => FileHashResult::new(path.to_path_buf(), algorithms, digests, size),
+        Err(error) => FileHashResult::failed(path.to_path_buf(), error),
+    }
+}
+
+/// Returns the digest of each of `algorithms` for the file at `path`.
+pub(crate) fn file_digests(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    let on_bytes = &mut read_options.throttled(on_bytes);
+    if remote::is_url(path) {
+        return remote::hash_url(path, algorithms, read_options, on_bytes);
+    }
+    // Standard input cannot be read again
+    let retries = if path.as_os_str() == STDIN_PATH {
+        0
+    } else {
+        read_options.retries
+    };
+    // Bytes read again after a retry were already reported
+    let mut reported = 0;
+    let mut attempt = 0;
+    loop {
+        let mut read = 0;
+        let digests = local_file_digests(path, algorithms, read_options, &mut |bytes| {
+            read += bytes;
+            if read <= reported {
+                return Ok(());
+            }
+            let new_bytes = read - reported;
+            reported = read;
+            on_bytes(new_bytes)
+        });
+        match digests {
+            Err(e) if attempt < retries && e.is_transient() => {
+                attempt += 1;
+                eprintln!(
+                    "Warning: {:#}; retrying ({} of {})",
+                    anyhow::Error::new(e),
+                    attempt,
+                    retries
+                );
+                thread::sleep(read_options.retry_delay);
+            }
+            digests => return digests,
         }
-    })?;
+    }
+}
+
+/// Reads the file at `path` once and returns the digest of each of
+/// `algorithms`.
+fn local_file_digests(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    let file = open_input(path)?;
+    let _cache_advice = CacheAdvice::new(&file, read_options.fadvise);
+    let reader_core = pin_reader(read_options);
+    // A chunk size of 0 from a library caller would never advance
+    let chunk_size = read_options.chunk_size.max(1);
+    let mapping = map_if_large(&file, path, read_options)?;
 
-    let mut reader = BufReader::with_capacity(chunk_size * 2, file);
-    let mut buffer = vec![0; chunk_size];
+    // With a single algorithm there is nothing to parallelize, so skip the
+    // channels and threads
+    if let [algorithm] = algorithms {
+        let digest = hash_inline(path, algorithm, file, mapping, read_options, on_bytes)?;
+        return Ok(vec![digest]);
+    }
+
+    // Spawning a thread per algorithm costs more than hashing a small file, so
+    // read it whole and hash it on this thread
+    let metadata = file.metadata()?;
+    if mapping.is_none() && metadata.is_file() && metadata.len() <= chunk_size as u64 {
+        return hash_small_file(path, algorithms, file, read_options, on_bytes);
+    }
 
-    let (senders, receivers): (Vec<Sender<FileChunk>>, Vec<Receiver<FileChunk>>) =
-        algorithms.iter().map(|_| bounded(channel_size)).unzip();
+    let (senders, receivers): (Vec<Sender<FileChunk>>, Vec<Receiver<FileChunk>>) = algorithms
+        .iter()
+        .map(|_| bounded(read_options.channel_size))
+        .unzip();
 
     let results = Arc::new(Mutex::new(Vec::new()));
 
@@ -161,59 +1077,287 @@ pub fn compute_file_hashes(
         .map(|(i, (algo, receiver))| {
             let algo = algo.clone();
             let results = Arc::clone(&results);
-            thread::spawn(move || hash_worker(i, algo, receiver, results))
+            let cancel = read_options.cancel.clone();
+            let timings = read_options.timings.clone();
+            thread::spawn(move || {
+                if let Some(core) = reader_core {
+                    affinity::pin_current_thread(core + 1 + i);
+                }
+                hash_worker(i, algo, receiver, results, cancel, timings)
+            })
         })
         .collect();
 
-    let mut is_empty = true;
-    loop {
-        let bytes_read = reader
-            .read(&mut buffer)
-            .with_context(|| format!("Failed to read from file: {}", path.display()))?;
-        if bytes_read == 0 {
-            break;
-        }
-        is_empty = false;
-        let is_last = bytes_read < chunk_size;
-        let chunk = FileChunk {
-            data: buffer[..bytes_read].to_vec(),
-            is_last,
-        };
-
+    let send = |chunk: FileChunk| -> Result<()> {
         for sender in &senders {
-            sender.send(chunk.clone()).context("Failed to send chunk")?;
+            if let Err(e) = sender.send(chunk.clone()) {
+                // Workers stop early when the run is cancelled
+                read_options.cancel.check()?;
+                return Err(e).context("Failed to send chunk");
+            }
         }
+        Ok(())
+    };
 
-        output_manager.update_bytes(bytes_read as u64)?;
-
-        if is_last {
-            break;
+    if let Some(mapping) = mapping {
+        let mapping = Arc::new(mapping);
+        for start in (0..mapping.len()).step_by(chunk_size) {
+            read_options.cancel.check()?;
+            let end = (start + chunk_size).min(mapping.len());
+            send(FileChunk {
+                data: ChunkData::Mapped(Arc::clone(&mapping), start..end),
+                is_last: false,
+            })?;
+            on_bytes((end - start) as u64)?;
         }
-    }
-
-    if is_empty {
-        // Handle empty file
-        let empty_chunk = FileChunk {
-            data: Vec::new(),
+        send(FileChunk {
+            data: ChunkData::Mapped(mapping, 0..0),
             is_last: true,
-        };
-        for sender in &senders {
-            sender
-                .send(empty_chunk.clone())
-                .context("Failed to send empty chunk")?;
+        })?;
+    } else {
+        let mut reader = FileReader::new(file, read_options)?;
+        let pool = BufferPool::new(chunk_size);
+        let mut offset = 0;
+
+        // Only a read of zero bytes marks the end of the file: short reads are
+        // normal for pipes and network filesystems, and a file whose size is a
+        // multiple of the chunk size must still produce a final chunk
+        loop {
+            read_options.cancel.check()?;
+            let (buffer, range) = reader
+                .next_chunk(&pool)
+                .map_err(|e| read_error(path, offset, e))?;
+            let bytes_read = range.len();
+            offset += bytes_read as u64;
+            let is_last = bytes_read == 0;
+            send(FileChunk {
+                data: ChunkData::Buffer(Arc::new(pool.wrap(buffer, range))),
+                is_last,
+            })?;
+
+            if is_last {
+                break;
+            }
+            on_bytes(bytes_read as u64)?;
         }
     }
 
     for handle in handles {
         handle
             .join()
-            .map_err(|e| anyhow!("Hash worker thread panicked: {:?}", e))??;
+            .map_err(|_| HashError::WorkerPanic(path.to_path_buf()))??;
     }
 
-    let results = results
+    let mut results = results
         .lock()
         .map_err(|e| anyhow!("Failed to lock results: {:?}", e))?;
-    Ok(results.iter().map(|r| hex::encode(r)).collect())
+    Ok(std::mem::take(&mut *results))
+}
+
+/// Path that stands for standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// Opens the file at `path`, or a handle to standard input for `-`.
+pub(crate) fn open_input(path: &Path) -> Result<File, HashError> {
+    let file = if path.as_os_str() != STDIN_PATH {
+        File::open(path)
+    } else {
+        stdin_file()
+    };
+    file.map_err(HashError::from)
+}
+
+/// Error for a read that failed after the first `offset` bytes of `path`.
+pub(crate) fn read_error(path: &Path, offset: u64, source: std::io::Error) -> HashError {
+    HashError::ReadError {
+        path: path.to_path_buf(),
+        offset,
+        source,
+    }
+}
+
+fn stdin_file() -> std::io::Result<File> {
+    #[cfg(unix)]
+    let handle = std::os::fd::AsFd::as_fd(&std::io::stdin()).try_clone_to_owned()?;
+    #[cfg(windows)]
+    let handle =
+        std::os::windows::io::AsHandle::as_handle(&std::io::stdin()).try_clone_to_owned()?;
+    Ok(File::from(handle))
+}
+
+/// Returns the core the reading thread runs on when pinning is enabled,
+/// pinning it to the first core if it has not been pinned yet.
+fn pin_reader(read_options: &ReadOptions) -> Option<usize> {
+    if !read_options.pin_threads {
+        return None;
+    }
+    Some(affinity::pinned_core().unwrap_or_else(|| {
+        affinity::pin_current_thread(0);
+        0
+    }))
+}
+
+/// Memory-maps the file when mapping is enabled and the file reaches the
+/// configured size threshold.
+fn map_if_large(file: &File, path: &Path, read_options: &ReadOptions) -> Result<Option<Mmap>> {
+    let Some(threshold) = read_options.mmap_threshold else {
+        return Ok(None);
+    };
+    let metadata = file.metadata()?;
+    if !metadata.is_file() || metadata.len() < threshold.max(1) {
+        return Ok(None);
+    }
+    // SAFETY: the mapping is only read. If another process truncates the file
+    // while it is being hashed, reading may fault; this is the documented
+    // trade-off of --mmap.
+    let mapping = unsafe { Mmap::map(file) }
+        .with_context(|| format!("Failed to memory-map file: {}", path.display()))?;
+    Ok(Some(mapping))
+}
+
+/// Hashes the target path stored in the symlink at `path`, returning the
+/// digests and the length of the target.
+fn hash_link_text(path: &Path, algorithms: &[HashAlgorithm]) -> Result<(Vec<Vec<u8>>, u64)> {
+    let target = std::fs::read_link(path)
+        .with_context(|| format!("Failed to read symlink: {}", path.display()))?;
+    let target = target.as_os_str().as_encoded_bytes();
+    let digests = algorithms
+        .iter()
+        .map(|algorithm| {
+            let mut algo = algorithm.clone();
+            algo.update(target);
+            algo.finalize_reset()
+        })
+        .collect();
+    Ok((digests, target.len() as u64))
+}
+
+fn hash_small_file(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    file: File,
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    let mut reader = FileReader::new(file, read_options)?;
+    let pool = BufferPool::new(read_options.chunk_size.max(1));
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    loop {
+        read_options.cancel.check()?;
+        let (buffer, range) = reader
+            .next_chunk(&pool)
+            .map_err(|e| read_error(path, offset, e))?;
+        if range.is_empty() {
+            break;
+        }
+        offset += range.len() as u64;
+        on_bytes(range.len() as u64)?;
+        chunks.push((buffer, range));
+    }
+    Ok(algorithms
+        .iter()
+        .map(|algorithm| {
+            let started = Instant::now();
+            let mut algo = algorithm.clone();
+            for (buffer, range) in &chunks {
+                algo.update(&buffer[range.clone()]);
+            }
+            let digest = algo.finalize_reset();
+            read_options.timings.add(algorithm, started.elapsed());
+            digest
+        })
+        .collect())
+}
+
+fn hash_inline(
+    path: &Path,
+    algorithm: &HashAlgorithm,
+    file: File,
+    mapping: Option<Mmap>,
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<u8>, HashError> {
+    let chunk_size = read_options.chunk_size.max(1);
+    let mut algo = algorithm.clone();
+    let busy = Cell::new(Duration::ZERO);
+    let mut update = |data: &[u8]| {
+        let started = Instant::now();
+        algo.update(data);
+        busy.set(busy.get() + started.elapsed());
+    };
+    if let Some(mapping) = mapping {
+        for chunk in mapping.chunks(chunk_size) {
+            read_options.cancel.check()?;
+            update(chunk);
+            on_bytes(chunk.len() as u64)?;
+        }
+    } else {
+        // Pipes and devices have no length, and are often the largest streams
+        let metadata = file.metadata()?;
+        let fits_in_one_chunk = metadata.is_file() && metadata.len() <= chunk_size as u64;
+        let mut reader = FileReader::new(file, read_options)?;
+        let pool = BufferPool::new(chunk_size);
+        let offset = Cell::new(0);
+        let mut hash_chunk = |(buffer, range): (Vec<u8>, Range<usize>)| -> Result<bool> {
+            let bytes_read = range.len();
+            if bytes_read == 0 {
+                return Ok(false);
+            }
+            read_options.cancel.check()?;
+            update(&buffer[range]);
+            pool.put(buffer);
+            offset.set(offset.get() + bytes_read as u64);
+            on_bytes(bytes_read as u64)?;
+            Ok(true)
+        };
+        let read_error = |e| read_error(path, offset.get(), e);
+
+        if fits_in_one_chunk {
+            // Not worth a thread
+            while hash_chunk(reader.next_chunk(&pool).map_err(read_error)?)? {}
+        } else {
+            // Read the next chunk on another thread while this one hashes the
+            // current chunk, so reading and hashing overlap
+            thread::scope(|scope| -> Result<(), HashError> {
+                let (sender, receiver) = bounded(1);
+                scope.spawn(|| {
+                    if let Some(core) = affinity::pinned_core() {
+                        affinity::pin_current_thread(core + 1);
+                    }
+                    read_ahead(&mut reader, &pool, sender)
+                });
+                for chunk in receiver {
+                    if !hash_chunk(chunk.map_err(read_error)?)? {
+                        break;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+    }
+    let started = Instant::now();
+    let digest = algo.finalize_reset();
+    read_options
+        .timings
+        .add(algorithm, busy.get() + started.elapsed());
+    Ok(digest)
+}
+
+/// Sends the chunks of `reader` through `sender` until the end of the file,
+/// a read error, or the receiving side going away.
+fn read_ahead(
+    reader: &mut FileReader,
+    pool: &BufferPool,
+    sender: Sender<std::io::Result<(Vec<u8>, Range<usize>)>>,
+) {
+    loop {
+        let chunk = reader.next_chunk(pool);
+        let done = !matches!(&chunk, Ok((_, range)) if !range.is_empty());
+        if sender.send(chunk).is_err() || done {
+            return;
+        }
+    }
 }
 
 fn hash_worker(
@@ -221,27 +1365,28 @@ fn hash_worker(
     mut algo: HashAlgorithm,
     receiver: Receiver<FileChunk>,
     results: Arc<Mutex<Vec<Vec<u8>>>>,
+    cancel: CancellationToken,
+    timings: AlgorithmTimings,
 ) -> Result<()> {
-    loop {
-        match receiver.recv() {
-            Ok(chunk) => {
-                algo.update(&chunk.data);
-                if chunk.is_last {
-                    let hash = algo.finalize_reset();
-                    let mut results = results
-                        .lock()
-                        .map_err(|e| anyhow!("Failed to lock results: {:?}", e))?;
-                    if results.len() <= index {
-                        results.resize(index + 1, Vec::new());
-                    }
-                    results[index] = hash;
-                    break;
-                }
-            }
-            Err(RecvError) => {
-                // Channel is disconnected, exit the loop
-                break;
+    let mut busy = Duration::ZERO;
+    // The loop ends when the last chunk arrives or the channel is disconnected
+    while let Ok(chunk) = receiver.recv() {
+        cancel.check()?;
+        let started = Instant::now();
+        algo.update(&chunk.data);
+        busy += started.elapsed();
+        if chunk.is_last {
+            let started = Instant::now();
+            let hash = algo.finalize_reset();
+            timings.add(&algo, busy + started.elapsed());
+            let mut results = results
+                .lock()
+                .map_err(|e| anyhow!("Failed to lock results: {:?}", e))?;
+            if results.len() <= index {
+                results.resize(index + 1, Vec::new());
             }
+            results[index] = hash;
+            break;
         }
     }
     Ok(())
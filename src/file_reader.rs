@@ -0,0 +1,151 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::ops::Range;
+
+use crate::buffer_pool::BufferPool;
+use crate::chunk_tuner::ChunkTuner;
+#[cfg(target_os = "linux")]
+use crate::direct_reader::DirectReader;
+use crate::file_processing::ReadOptions;
+#[cfg(target_os = "linux")]
+use crate::uring_reader::UringReader;
+
+/// How file contents are read from disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum IoBackend {
+    /// Sequential buffered reads
+    #[default]
+    Read,
+    /// Overlapping reads submitted through io_uring (Linux only)
+    Uring,
+}
+
+/// Reads a file as a sequence of chunks taken from a `BufferPool`.
+pub enum FileReader {
+    /// Reads through the page cache, with the chunk size optionally picked by
+    /// a `ChunkTuner`.
+    Buffered(BufReader<File>, Option<ChunkTuner>),
+    #[cfg(target_os = "linux")]
+    Direct(DirectReader),
+    #[cfg(target_os = "linux")]
+    Uring(Box<UringReader>),
+}
+
+impl FileReader {
+    pub fn new(file: File, read_options: &ReadOptions) -> Result<Self> {
+        let chunk_size = read_options.chunk_size;
+
+        #[cfg(target_os = "linux")]
+        if read_options.direct_io && file.metadata()?.is_file() {
+            // Not every filesystem supports O_DIRECT (tmpfs, some network
+            // filesystems); those are read through the page cache instead
+            if let Ok(reader) = DirectReader::new(&file, chunk_size) {
+                return Ok(FileReader::Direct(reader));
+            }
+        }
+
+        match read_options.io_backend {
+            #[cfg(target_os = "linux")]
+            // io_uring reads at explicit offsets, which only makes sense for
+            // regular files
+            IoBackend::Uring if file.metadata()?.is_file() => Ok(FileReader::Uring(Box::new(
+                UringReader::new(file, chunk_size)?,
+            ))),
+            #[cfg(not(target_os = "linux"))]
+            IoBackend::Uring => Err(anyhow::anyhow!(
+                "The io_uring backend is only available on Linux"
+            )),
+            _ => {
+                let tuner = if read_options.adaptive_chunk_size {
+                    let metadata = file.metadata()?;
+                    Some(ChunkTuner::new(
+                        metadata.is_file().then_some(metadata.len()),
+                    ))
+                } else {
+                    None
+                };
+                // Reads larger than the capacity bypass the BufReader's buffer,
+                // so a tuner growing the chunk size is not limited by it
+                let capacity = match &tuner {
+                    Some(tuner) => tuner.size(),
+                    None => chunk_size * 2,
+                };
+                Ok(FileReader::Buffered(
+                    BufReader::with_capacity(capacity, file),
+                    tuner,
+                ))
+            }
+        }
+    }
+
+    /// Returns a buffer from `pool` holding the next chunk of the file and the
+    /// range of the buffer that was read into. An empty range means the end of
+    /// the file.
+    pub fn next_chunk(&mut self, pool: &BufferPool) -> io::Result<(Vec<u8>, Range<usize>)> {
+        match self {
+            FileReader::Buffered(reader, tuner) => {
+                if let Some(tuner) = tuner {
+                    pool.set_buffer_size(tuner.next_size());
+                }
+                let mut buffer = pool.get();
+                let bytes_read = reader.read(&mut buffer)?;
+                if let Some(tuner) = tuner {
+                    tuner.record(bytes_read);
+                }
+                Ok((buffer, 0..bytes_read))
+            }
+            #[cfg(target_os = "linux")]
+            FileReader::Direct(reader) => reader.next_chunk(pool),
+            #[cfg(target_os = "linux")]
+            FileReader::Uring(reader) => reader.next_chunk(pool),
+        }
+    }
+}
+
+/// Tells the kernel that a file is about to be read sequentially, and drops
+/// its pages from the page cache once hashing is done, so that large sweeps
+/// do not evict the rest of the system's cached data. Does nothing on
+/// platforms without `posix_fadvise`.
+pub struct CacheAdvice {
+    #[cfg(target_os = "linux")]
+    file: Option<File>,
+}
+
+impl CacheAdvice {
+    pub fn new(file: &File, enabled: bool) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            // The hints are best effort, so failures are ignored
+            let file = enabled.then(|| file.try_clone().ok()).flatten();
+            if let Some(file) = &file {
+                fadvise(file, libc::POSIX_FADV_SEQUENTIAL);
+            }
+            Self { file }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (file, enabled);
+            Self {}
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for CacheAdvice {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            fadvise(file, libc::POSIX_FADV_DONTNEED);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn fadvise(file: &File, advice: libc::c_int) {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: advisory call on a file descriptor we own, covering the whole file
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice);
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Result};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::checksum_verification::{parse_checksum_file, CheckFormat};
+use crate::file_processing::{compute_file_hashes, ReadOptions};
+use crate::hash_algorithms::{AlgorithmOptions, HashAlgorithm};
+use crate::output::format_digests;
+use crate::path_escape::{escape_path, ESCAPE_MARKER};
+use crate::OutputManager;
+
+/// Compares the ssdeep signatures of the given files against the SSDEEP
+/// column of a manifest and reports every pair with a non-zero similarity
+/// score, best matches first.
+#[allow(clippy::too_many_arguments)]
+pub fn fuzzy_match(
+    manifest: &Path,
+    paths: &[PathBuf],
+    options: &AlgorithmOptions,
+    show_headers: bool,
+    follow_symlinks: bool,
+    read_options: &ReadOptions,
+    output_manager: &mut OutputManager,
+) -> Result<()> {
+    let (entries, detected_algorithms) =
+        parse_checksum_file(manifest, CheckFormat::Native, &[], options)?;
+    let column = detected_algorithms
+        .iter()
+        .position(|algo| matches!(algo, HashAlgorithm::Ssdeep(_)))
+        .ok_or_else(|| {
+            anyhow!(
+                "Manifest {} has no SSDEEP column in its header",
+                manifest.display()
+            )
+        })?;
+    let algorithms = [HashAlgorithm::with_options("ssdeep", options)?];
+
+    if show_headers {
+        output_manager.write_result_header("Score  path  match")?;
+    }
+
+    for path in paths {
+        for entry in WalkDir::new(path).follow_links(follow_symlinks) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!("Error accessing entry: {}", e);
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+
+            output_manager.start_file(path);
+            let signature =
+                match compute_file_hashes(path, &algorithms, read_options, &mut |bytes| {
+                    output_manager.update_bytes(bytes)
+                })
+                .into_result()
+                {
+                    Ok(result) => format_digests(&result.digests).remove(0),
+                    Err(e) => {
+                        eprintln!("Error processing file {}: {}", path.display(), e);
+                        continue;
+                    }
+                };
+
+            let mut matches: Vec<(u32, &Path)> = entries
+                .iter()
+                .filter(|candidate| candidate.algorithm.is_none())
+                .filter_map(|candidate| {
+                    let score = ssdeep::compare(&signature, &candidate.hashes[column]).ok()?;
+                    (score > 0).then_some((score, candidate.path.as_path()))
+                })
+                .collect();
+            matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+            for (score, matched) in matches {
+                let (path, matched) = (escape_path(path), escape_path(matched));
+                let marker = if matches!(path, Cow::Owned(_)) || matches!(matched, Cow::Owned(_)) {
+                    ESCAPE_MARKER.to_string()
+                } else {
+                    String::new()
+                };
+                output_manager
+                    .write_result(&format!("{}{}  {}  {}", marker, score, path, matched))?;
+            }
+        }
+    }
+
+    output_manager.finish()?;
+    Ok(())
+}
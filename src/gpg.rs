@@ -0,0 +1,89 @@
+use anyhow::{bail, Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// First line of a clearsigned message.
+const CLEARSIGN_HEADER: &[u8] = b"-----BEGIN PGP SIGNED MESSAGE-----";
+
+/// Where the signature of `manifest` is written and looked for: the same
+/// path with `.asc` appended.
+pub fn signature_path(manifest: &Path) -> PathBuf {
+    let mut path = OsString::from(manifest);
+    path.push(".asc");
+    PathBuf::from(path)
+}
+
+/// Signs `manifest` with gpg, using the key `key_id` or gpg's default key,
+/// and writes the ASCII-armored signature to `signature_path(manifest)`:
+/// a detached signature, or with `clearsign` a copy of the manifest wrapped
+/// in its signature. gpg asks for the passphrase itself if it needs one.
+pub fn sign(manifest: &Path, key_id: Option<&str>, clearsign: bool) -> Result<PathBuf> {
+    let signature = signature_path(manifest);
+    let mut command = Command::new("gpg");
+    command
+        .args(["--yes", "--armor", "--output"])
+        .arg(&signature);
+    if let Some(key_id) = key_id {
+        command.args(["--local-user", key_id]);
+    }
+    command
+        .arg(if clearsign {
+            "--clearsign"
+        } else {
+            "--detach-sign"
+        })
+        .arg(manifest);
+    let status = command.status().context("Failed to run gpg")?;
+    if !status.success() {
+        bail!("gpg failed to sign {} ({})", manifest.display(), status);
+    }
+    Ok(signature)
+}
+
+/// Checks the signature of `manifest` at `signature_path(manifest)` with gpg,
+/// which prints who signed it. A clearsigned copy must hold exactly the
+/// contents of `manifest`.
+pub fn verify(manifest: &Path) -> Result<()> {
+    let signature = signature_path(manifest);
+    let signed = fs::read(&signature)
+        .with_context(|| format!("Failed to read signature: {}", signature.display()))?;
+    let mut command = Command::new("gpg");
+    command.arg("--batch");
+    if signed.starts_with(CLEARSIGN_HEADER) {
+        let output = command
+            .arg("--decrypt")
+            .arg(&signature)
+            .stderr(Stdio::inherit())
+            .output()
+            .context("Failed to run gpg")?;
+        if !output.status.success() {
+            bail!(
+                "Bad signature for {} ({})",
+                manifest.display(),
+                output.status
+            );
+        }
+        let contents = fs::read(manifest)
+            .with_context(|| format!("Failed to read manifest: {}", manifest.display()))?;
+        if output.stdout != contents {
+            bail!(
+                "{} does not match the manifest signed in {}",
+                manifest.display(),
+                signature.display()
+            );
+        }
+    } else {
+        let status = command
+            .arg("--verify")
+            .arg(&signature)
+            .arg(manifest)
+            .status()
+            .context("Failed to run gpg")?;
+        if !status.success() {
+            bail!("Bad signature for {} ({})", manifest.display(), status);
+        }
+    }
+    Ok(())
+}
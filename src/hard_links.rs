@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::utils::HashError;
+
+/// Digests of one inode, set by whichever of its links is hashed first, or
+/// `None` if hashing that link failed.
+type SharedHashes = Arc<OnceLock<Option<Vec<Vec<u8>>>>>;
+
+/// Remembers the hashes of files with several hard links, so that the
+/// content of each inode is only read once.
+#[derive(Default)]
+pub struct HardLinks {
+    /// Shared hashes and number of links not seen yet, per device and inode
+    inodes: Mutex<HashMap<(u64, u64), (SharedHashes, u64)>>,
+}
+
+impl HardLinks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the hashes of the file at `path`, calling `hash` unless
+    /// another link to the same inode has already been hashed. A link found
+    /// while another one is being hashed waits for its result. If hashing a
+    /// link fails, the next link is hashed on its own.
+    pub fn hashes(
+        &self,
+        path: &Path,
+        on_bytes: &mut dyn FnMut(u64) -> anyhow::Result<()>,
+        hash: impl FnOnce(&mut dyn FnMut(u64) -> anyhow::Result<()>) -> Result<Vec<Vec<u8>>, HashError>,
+    ) -> Result<Vec<Vec<u8>>, HashError> {
+        let Some((shared, size)) = self.inode(path) else {
+            return hash(on_bytes);
+        };
+
+        let mut hash = Some(hash);
+        let mut result = None;
+        let hashes = shared.get_or_init(|| {
+            let hashes = (hash.take().unwrap())(on_bytes);
+            let shared = hashes.as_ref().ok().cloned();
+            result = Some(hashes);
+            shared
+        });
+        match (result, hashes, hash) {
+            (Some(result), _, _) => result,
+            (None, Some(hashes), _) => {
+                // Count the link as done for the progress
+                on_bytes(size)?;
+                Ok(hashes.clone())
+            }
+            (None, None, Some(hash)) => hash(on_bytes),
+            (None, None, None) => unreachable!("the hash closure runs at most once"),
+        }
+    }
+
+    /// Returns the shared hashes of the inode behind `path` and its size, or
+    /// `None` if the file has a single link.
+    #[cfg(unix)]
+    fn inode(&self, path: &Path) -> Option<(SharedHashes, u64)> {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = path.metadata().ok()?;
+        if metadata.nlink() < 2 {
+            return None;
+        }
+        let key = (metadata.dev(), metadata.ino());
+        let mut inodes = self.inodes.lock().unwrap_or_else(|e| e.into_inner());
+        let (shared, remaining) = inodes
+            .entry(key)
+            .or_insert_with(|| (SharedHashes::default(), metadata.nlink()));
+        let shared = shared.clone();
+        *remaining -= 1;
+        if *remaining == 0 {
+            // Every link has been seen, so the entry will not be needed again
+            inodes.remove(&key);
+        }
+        Some((shared, metadata.len()))
+    }
+
+    #[cfg(not(unix))]
+    fn inode(&self, _path: &Path) -> Option<(SharedHashes, u64)> {
+        None
+    }
+}
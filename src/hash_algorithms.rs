@@ -1,12 +1,30 @@
+use adler::Adler32;
 use anyhow::{anyhow, Result};
 use blake3::Hasher as Blake3;
+use crc32fast::Hasher as Crc32;
 use digest::Digest;
+use highway::{HighwayHash, HighwayHasher, Key as HighwayKey};
+use hmac::{Hmac, Mac};
 use md5::Md5;
+#[cfg(feature = "native")]
+use memmap2::Mmap;
 use sha1::Sha1;
 use sha2::{Sha256, Sha384, Sha512};
 use sha3::{Sha3_256, Sha3_384, Sha3_512};
+use ssdeep::Generator as Ssdeep;
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "native")]
+use std::ops::{Deref, Range};
+#[cfg(feature = "native")]
+use std::sync::Arc;
+use std::sync::{LazyLock, RwLock};
+
+#[cfg(feature = "native")]
+use crate::buffer_pool::PooledBuffer;
 
 #[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum HashAlgorithm {
     Md5(Md5),
     Sha1(Sha1),
@@ -16,12 +34,241 @@ pub enum HashAlgorithm {
     Sha3_256(Sha3_256),
     Sha3_384(Sha3_384),
     Sha3_512(Sha3_512),
-    Blake3(Blake3),
+    Blake3(Blake3, Blake3Threads),
+    Blake3Keyed(Blake3, Blake3Threads),
+    Blake3DeriveKey(Blake3, Blake3Threads),
+    Adler32(Adler32),
+    Crc32(Crc32),
+    Highway64(HighwayHasher, HighwayKey),
+    Highway128(HighwayHasher, HighwayKey),
+    Highway256(HighwayHasher, HighwayKey),
+    HmacMd5(Hmac<Md5>),
+    HmacSha1(Hmac<Sha1>),
+    HmacSha256(Hmac<Sha256>),
+    HmacSha384(Hmac<Sha384>),
+    HmacSha512(Hmac<Sha512>),
+    HmacSha3_256(Hmac<Sha3_256>),
+    HmacSha3_384(Hmac<Sha3_384>),
+    HmacSha3_512(Hmac<Sha3_512>),
+    Ssdeep(Ssdeep),
+    /// Shannon entropy of the byte distribution, in bits per byte.
+    Entropy(Box<[u64; 256]>),
+    /// An algorithm added with `register_algorithm`.
+    Custom(Box<dyn StreamingHasher>),
+}
+
+/// A digest computed outside this crate, made available by name with
+/// `register_algorithm`. Implementations also need `Clone`, which is used
+/// to get a fresh hasher for each file.
+pub trait StreamingHasher: CloneHasher + Send + Sync {
+    /// Name of the algorithm, as given to `-a` and written in headers.
+    fn name(&self) -> &str;
+
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the digest of the data so far and resets the hasher for the
+    /// next file. Digests are written as hex.
+    fn finalize(&mut self) -> Vec<u8>;
+}
+
+/// Clones boxed `StreamingHasher`s. Implemented for every hasher that is
+/// `Clone`.
+pub trait CloneHasher {
+    fn clone_hasher(&self) -> Box<dyn StreamingHasher>;
+}
+
+impl<T: StreamingHasher + Clone + 'static> CloneHasher for T {
+    fn clone_hasher(&self) -> Box<dyn StreamingHasher> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn StreamingHasher> {
+    fn clone(&self) -> Self {
+        self.clone_hasher()
+    }
+}
+
+impl fmt::Debug for dyn StreamingHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StreamingHasher")
+            .field(&self.name())
+            .finish()
+    }
+}
+
+/// Algorithms added with `register_algorithm`, by lowercase name.
+static REGISTRY: LazyLock<RwLock<HashMap<String, Box<dyn StreamingHasher>>>> =
+    LazyLock::new(Default::default);
+
+/// Makes `hasher` available by its name wherever an algorithm is named,
+/// such as in `validate_algorithms`, `HashAlgorithm::new` and checksum file
+/// headers. Names are matched case-insensitively, the built-in algorithms
+/// keep theirs, and registering a name again replaces the previous hasher.
+pub fn register_algorithm(hasher: impl StreamingHasher + Clone + 'static) {
+    let name = hasher.name().to_lowercase();
+    REGISTRY
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name, Box::new(hasher));
+}
+
+fn registered_algorithm(name: &str) -> Option<HashAlgorithm> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    let hasher = registry.get(name)?;
+    Some(HashAlgorithm::Custom(hasher.clone()))
+}
+
+/// Names of the built-in algorithms that need no key, as accepted by
+/// `HashAlgorithm::new`.
+pub const BUILTIN_ALGORITHMS: &[&str] = &[
+    "md5",
+    "sha1",
+    "sha256",
+    "sha384",
+    "sha512",
+    "sha3-256",
+    "sha3-384",
+    "sha3-512",
+    "blake3",
+    "adler32",
+    "crc32",
+    "highway64",
+    "highway128",
+    "highway256",
+    "ssdeep",
+];
+
+/// Algorithms that an HMAC key can wrap, as listed in errors.
+pub(crate) const HMAC_ALGORITHMS: &str =
+    "md5, sha1, sha2-256, sha2-384, sha2-512, sha3-256, sha3-384 and sha3-512";
+
+/// Whether `algo` can be selected along with an HMAC key: it is one
+/// `new_hmac` wraps, already names an HMAC, or is entropy, which stays
+/// unkeyed.
+pub(crate) fn supports_hmac(algo: &str) -> bool {
+    let name = algo.to_lowercase();
+    matches!(
+        name.strip_prefix("hmac-").unwrap_or(&name),
+        "md5"
+            | "sha1"
+            | "sha256"
+            | "sha2-256"
+            | "sha384"
+            | "sha2-384"
+            | "sha512"
+            | "sha2-512"
+            | "sha3-256"
+            | "sha3-384"
+            | "sha3-512"
+            | "entropy"
+    )
+}
+
+/// Parameters needed to construct keyed algorithms.
+#[derive(Clone, Debug, Default)]
+pub struct AlgorithmOptions {
+    /// 256-bit HighwayHash key. An all-zero key is used when not set.
+    pub highway_key: Option<[u8; 32]>,
+    /// When set, every selected digest is wrapped in HMAC with this key.
+    pub hmac_key: Option<Vec<u8>>,
+    /// 256-bit key for BLAKE3's native keyed mode.
+    pub blake3_key: Option<[u8; 32]>,
+    /// Context string for BLAKE3's derive_key mode.
+    pub blake3_context: Option<String>,
+    /// Threads used by BLAKE3's internal parallelism.
+    pub blake3_threads: Blake3Threads,
+}
+
+/// Where BLAKE3 runs the parallel hashing of large chunks.
+#[derive(Clone, Debug, Default)]
+pub enum Blake3Threads {
+    /// Rayon's global pool, with one thread per CPU. Without the `native`
+    /// feature, the calling thread only.
+    #[default]
+    Global,
+    /// The hash worker's own thread only.
+    Single,
+    /// A dedicated pool shared by all BLAKE3 hashers.
+    #[cfg(feature = "native")]
+    Pool(Arc<rayon::ThreadPool>),
+}
+
+impl Blake3Threads {
+    /// Bounds BLAKE3 to `threads` threads, or leaves it on the global pool.
+    pub fn new(threads: Option<usize>) -> Result<Self> {
+        match threads {
+            None => Ok(Blake3Threads::Global),
+            Some(0) => Err(anyhow!("The number of BLAKE3 threads must be at least 1")),
+            Some(1) => Ok(Blake3Threads::Single),
+            #[cfg(not(feature = "native"))]
+            Some(_) => Err(anyhow!("BLAKE3 threads need the native feature")),
+            #[cfg(feature = "native")]
+            Some(threads) => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|i| format!("blake3-{}", i))
+                    .build()?;
+                Ok(Blake3Threads::Pool(Arc::new(pool)))
+            }
+        }
+    }
+
+    fn update(&self, hasher: &mut Blake3, data: &[u8]) {
+        match self {
+            #[cfg(feature = "native")]
+            Blake3Threads::Global => {
+                hasher.update_rayon(data);
+            }
+            #[cfg(not(feature = "native"))]
+            Blake3Threads::Global => {
+                hasher.update(data);
+            }
+            Blake3Threads::Single => {
+                hasher.update(data);
+            }
+            #[cfg(feature = "native")]
+            Blake3Threads::Pool(pool) => {
+                pool.install(|| hasher.update_rayon(data));
+            }
+        }
+    }
+}
+
+impl AlgorithmOptions {
+    fn highway_key(&self) -> HighwayKey {
+        let mut key = [0u64; 4];
+        if let Some(bytes) = &self.highway_key {
+            for (word, chunk) in key.iter_mut().zip(bytes.chunks_exact(8)) {
+                *word = u64::from_le_bytes(chunk.try_into().expect("chunk of 8 bytes"));
+            }
+        }
+        HighwayKey(key)
+    }
 }
 
 impl HashAlgorithm {
     pub fn new(algo: &str) -> Result<Self> {
-        match algo.to_lowercase().as_str() {
+        Self::with_options(algo, &AlgorithmOptions::default())
+    }
+
+    pub fn with_options(algo: &str, options: &AlgorithmOptions) -> Result<Self> {
+        let name = algo.to_lowercase();
+        // Checksum file headers name HMAC columns explicitly, e.g. "HMAC-SHA2-256"
+        if let Some(inner) = name.strip_prefix("hmac-") {
+            let key = options
+                .hmac_key
+                .as_deref()
+                .ok_or_else(|| anyhow!("Algorithm {} requires an HMAC key", algo))?;
+            return Self::new_hmac(inner, key);
+        }
+        // Entropy is a measurement of the contents, not a digest to key
+        if let Some(key) = options.hmac_key.as_ref().filter(|_| name != "entropy") {
+            return Self::new_hmac(&name, key);
+        }
+
+        let threads = options.blake3_threads.clone();
+        match name.as_str() {
             "md5" => Ok(HashAlgorithm::Md5(Md5::new())),
             "sha1" => Ok(HashAlgorithm::Sha1(Sha1::new())),
             "sha256" | "sha2-256" => Ok(HashAlgorithm::Sha256(Sha256::new())),
@@ -30,8 +277,84 @@ impl HashAlgorithm {
             "sha3-256" => Ok(HashAlgorithm::Sha3_256(Sha3_256::new())),
             "sha3-384" => Ok(HashAlgorithm::Sha3_384(Sha3_384::new())),
             "sha3-512" => Ok(HashAlgorithm::Sha3_512(Sha3_512::new())),
-            "blake3" => Ok(HashAlgorithm::Blake3(Blake3::new())),
-            _ => Err(anyhow!("Unsupported algorithm: {}", algo)),
+            "blake3" => match (&options.blake3_key, &options.blake3_context) {
+                (Some(key), _) => Ok(HashAlgorithm::Blake3Keyed(Blake3::new_keyed(key), threads)),
+                (None, Some(context)) => Ok(HashAlgorithm::Blake3DeriveKey(
+                    Blake3::new_derive_key(context),
+                    threads,
+                )),
+                (None, None) => Ok(HashAlgorithm::Blake3(Blake3::new(), threads)),
+            },
+            "blake3-keyed" => {
+                let key = options
+                    .blake3_key
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Algorithm {} requires a BLAKE3 key", algo))?;
+                Ok(HashAlgorithm::Blake3Keyed(Blake3::new_keyed(key), threads))
+            }
+            "blake3-derive-key" => {
+                let context = options
+                    .blake3_context
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("Algorithm {} requires a BLAKE3 context", algo))?;
+                Ok(HashAlgorithm::Blake3DeriveKey(
+                    Blake3::new_derive_key(context),
+                    threads,
+                ))
+            }
+            "adler32" | "adler-32" => Ok(HashAlgorithm::Adler32(Adler32::new())),
+            "crc32" => Ok(HashAlgorithm::Crc32(Crc32::new())),
+            "ssdeep" => Ok(HashAlgorithm::Ssdeep(Ssdeep::new())),
+            "entropy" => Ok(HashAlgorithm::Entropy(Box::new([0; 256]))),
+            "highway64" => {
+                let key = options.highway_key();
+                Ok(HashAlgorithm::Highway64(HighwayHasher::new(key), key))
+            }
+            "highway128" => {
+                let key = options.highway_key();
+                Ok(HashAlgorithm::Highway128(HighwayHasher::new(key), key))
+            }
+            "highway256" => {
+                let key = options.highway_key();
+                Ok(HashAlgorithm::Highway256(HighwayHasher::new(key), key))
+            }
+            _ => registered_algorithm(&name)
+                .ok_or_else(|| anyhow!("Unsupported algorithm: {}", algo)),
+        }
+    }
+
+    fn new_hmac(algo: &str, key: &[u8]) -> Result<Self> {
+        let invalid_key = |e| anyhow!("Invalid HMAC key: {}", e);
+        match algo {
+            "md5" => Ok(HashAlgorithm::HmacMd5(
+                Hmac::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            "sha1" => Ok(HashAlgorithm::HmacSha1(
+                Hmac::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            "sha256" | "sha2-256" => Ok(HashAlgorithm::HmacSha256(
+                Hmac::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            "sha384" | "sha2-384" => Ok(HashAlgorithm::HmacSha384(
+                Hmac::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            "sha512" | "sha2-512" => Ok(HashAlgorithm::HmacSha512(
+                Hmac::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            "sha3-256" => Ok(HashAlgorithm::HmacSha3_256(
+                Hmac::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            "sha3-384" => Ok(HashAlgorithm::HmacSha3_384(
+                Hmac::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            "sha3-512" => Ok(HashAlgorithm::HmacSha3_512(
+                Hmac::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            _ => Err(anyhow!(
+                "HMAC is not supported for algorithm: {} (supported: {})",
+                algo,
+                HMAC_ALGORITHMS
+            )),
         }
     }
 
@@ -45,9 +368,31 @@ impl HashAlgorithm {
             HashAlgorithm::Sha3_256(h) => h.update(data),
             HashAlgorithm::Sha3_384(h) => h.update(data),
             HashAlgorithm::Sha3_512(h) => h.update(data),
-            HashAlgorithm::Blake3(h) => {
-                h.update_rayon(data);
+            HashAlgorithm::Blake3(h, threads)
+            | HashAlgorithm::Blake3Keyed(h, threads)
+            | HashAlgorithm::Blake3DeriveKey(h, threads) => threads.update(h, data),
+            HashAlgorithm::Adler32(h) => h.write_slice(data),
+            HashAlgorithm::Crc32(h) => h.update(data),
+            HashAlgorithm::Highway64(h, _)
+            | HashAlgorithm::Highway128(h, _)
+            | HashAlgorithm::Highway256(h, _) => h.append(data),
+            HashAlgorithm::HmacMd5(h) => Mac::update(h, data),
+            HashAlgorithm::HmacSha1(h) => Mac::update(h, data),
+            HashAlgorithm::HmacSha256(h) => Mac::update(h, data),
+            HashAlgorithm::HmacSha384(h) => Mac::update(h, data),
+            HashAlgorithm::HmacSha512(h) => Mac::update(h, data),
+            HashAlgorithm::HmacSha3_256(h) => Mac::update(h, data),
+            HashAlgorithm::HmacSha3_384(h) => Mac::update(h, data),
+            HashAlgorithm::HmacSha3_512(h) => Mac::update(h, data),
+            HashAlgorithm::Ssdeep(h) => {
+                h.update(data);
+            }
+            HashAlgorithm::Entropy(counts) => {
+                for &byte in data {
+                    counts[byte as usize] += 1;
+                }
             }
+            HashAlgorithm::Custom(h) => h.update(data),
         }
     }
 
@@ -61,31 +406,167 @@ impl HashAlgorithm {
             HashAlgorithm::Sha3_256(h) => h.finalize_reset().to_vec(),
             HashAlgorithm::Sha3_384(h) => h.finalize_reset().to_vec(),
             HashAlgorithm::Sha3_512(h) => h.finalize_reset().to_vec(),
-            HashAlgorithm::Blake3(h) => {
+            HashAlgorithm::Blake3(h, _)
+            | HashAlgorithm::Blake3Keyed(h, _)
+            | HashAlgorithm::Blake3DeriveKey(h, _) => {
                 let result = h.finalize().as_bytes().to_vec();
-                *h = Blake3::new();
+                // reset() keeps the key or context, unlike replacing the hasher
+                h.reset();
                 result
             }
+            HashAlgorithm::Adler32(h) => {
+                // Big-endian, matching the trailer of a zlib stream
+                let result = h.checksum().to_be_bytes().to_vec();
+                *h = Adler32::new();
+                result
+            }
+            HashAlgorithm::Crc32(h) => {
+                // Big-endian, as written in SFV files
+                let result = h.clone().finalize().to_be_bytes().to_vec();
+                h.reset();
+                result
+            }
+            HashAlgorithm::Highway64(h, key) => {
+                let hasher = std::mem::replace(h, HighwayHasher::new(*key));
+                hasher.finalize64().to_be_bytes().to_vec()
+            }
+            HashAlgorithm::Highway128(h, key) => {
+                let hasher = std::mem::replace(h, HighwayHasher::new(*key));
+                highway_words_to_bytes(&hasher.finalize128())
+            }
+            HashAlgorithm::Highway256(h, key) => {
+                let hasher = std::mem::replace(h, HighwayHasher::new(*key));
+                highway_words_to_bytes(&hasher.finalize256())
+            }
+            HashAlgorithm::HmacMd5(h) => h.finalize_reset().into_bytes().to_vec(),
+            HashAlgorithm::HmacSha1(h) => h.finalize_reset().into_bytes().to_vec(),
+            HashAlgorithm::HmacSha256(h) => h.finalize_reset().into_bytes().to_vec(),
+            HashAlgorithm::HmacSha384(h) => h.finalize_reset().into_bytes().to_vec(),
+            HashAlgorithm::HmacSha512(h) => h.finalize_reset().into_bytes().to_vec(),
+            HashAlgorithm::HmacSha3_256(h) => h.finalize_reset().into_bytes().to_vec(),
+            HashAlgorithm::HmacSha3_384(h) => h.finalize_reset().into_bytes().to_vec(),
+            HashAlgorithm::HmacSha3_512(h) => h.finalize_reset().into_bytes().to_vec(),
+            HashAlgorithm::Ssdeep(h) => {
+                // The digest is the textual signature; inputs beyond the
+                // supported size (192 GiB) yield an empty one
+                let result = h
+                    .finalize()
+                    .map(|hash| hash.to_string().into_bytes())
+                    .unwrap_or_default();
+                h.reset();
+                result
+            }
+            HashAlgorithm::Entropy(counts) => {
+                let total: u64 = counts.iter().sum();
+                let entropy = if total == 0 {
+                    0.0
+                } else {
+                    counts
+                        .iter()
+                        .filter(|&&count| count > 0)
+                        .map(|&count| {
+                            let p = count as f64 / total as f64;
+                            -p * p.log2()
+                        })
+                        .sum()
+                };
+                counts.fill(0);
+                entropy.to_be_bytes().to_vec()
+            }
+            HashAlgorithm::Custom(h) => h.finalize(),
         }
     }
 
-    pub fn to_string(&self) -> String {
+    /// Formats a digest returned by `finalize_reset` for output.
+    pub fn encode_digest(&self, digest: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Ssdeep(_) => String::from_utf8_lossy(digest).into_owned(),
+            HashAlgorithm::Entropy(_) => match <[u8; 8]>::try_from(digest) {
+                Ok(bytes) => format!("{:.6}", f64::from_be_bytes(bytes)),
+                Err(_) => "N/A".to_string(),
+            },
+            _ => hex::encode(digest),
+        }
+    }
+}
+
+/// Finalizes each of `hashers` in turn, resetting them for the next input.
+pub(crate) fn digests(hashers: &mut [HashAlgorithm]) -> Vec<Vec<u8>> {
+    hashers
+        .iter_mut()
+        .map(|hasher| hasher.finalize_reset())
+        .collect()
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HashAlgorithm::Md5(_) => "MD5",
+            HashAlgorithm::Sha1(_) => "SHA1",
+            HashAlgorithm::Sha256(_) => "SHA2-256",
+            HashAlgorithm::Sha384(_) => "SHA2-384",
+            HashAlgorithm::Sha512(_) => "SHA2-512",
+            HashAlgorithm::Sha3_256(_) => "SHA3-256",
+            HashAlgorithm::Sha3_384(_) => "SHA3-384",
+            HashAlgorithm::Sha3_512(_) => "SHA3-512",
+            HashAlgorithm::Blake3(..) => "BLAKE3",
+            HashAlgorithm::Blake3Keyed(..) => "BLAKE3-KEYED",
+            HashAlgorithm::Blake3DeriveKey(..) => "BLAKE3-DERIVE-KEY",
+            HashAlgorithm::Adler32(_) => "ADLER32",
+            HashAlgorithm::Crc32(_) => "CRC32",
+            HashAlgorithm::Highway64(..) => "HIGHWAY64",
+            HashAlgorithm::Highway128(..) => "HIGHWAY128",
+            HashAlgorithm::Highway256(..) => "HIGHWAY256",
+            HashAlgorithm::HmacMd5(_) => "HMAC-MD5",
+            HashAlgorithm::HmacSha1(_) => "HMAC-SHA1",
+            HashAlgorithm::HmacSha256(_) => "HMAC-SHA2-256",
+            HashAlgorithm::HmacSha384(_) => "HMAC-SHA2-384",
+            HashAlgorithm::HmacSha512(_) => "HMAC-SHA2-512",
+            HashAlgorithm::HmacSha3_256(_) => "HMAC-SHA3-256",
+            HashAlgorithm::HmacSha3_384(_) => "HMAC-SHA3-384",
+            HashAlgorithm::HmacSha3_512(_) => "HMAC-SHA3-512",
+            HashAlgorithm::Ssdeep(_) => "SSDEEP",
+            HashAlgorithm::Entropy(_) => "ENTROPY",
+            HashAlgorithm::Custom(h) => h.name(),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Serializes HighwayHash output words in order, each one big-endian, so the
+/// hex form reads the same as printing the words with `{:016x}`.
+fn highway_words_to_bytes(words: &[u64]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_be_bytes()).collect()
+}
+
+/// File data shared by all hash workers. Cloning only bumps a reference
+/// count, so the data is read once regardless of how many algorithms are
+/// selected.
+#[cfg(feature = "native")]
+#[derive(Clone, Debug)]
+pub enum ChunkData {
+    /// A read buffer, which goes back to its pool once every worker is done
+    /// with it.
+    Buffer(Arc<PooledBuffer>),
+    /// A range of a memory-mapped file.
+    Mapped(Arc<Mmap>, Range<usize>),
+}
+
+#[cfg(feature = "native")]
+impl Deref for ChunkData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
         match self {
-            HashAlgorithm::Md5(_) => "MD5".to_string(),
-            HashAlgorithm::Sha1(_) => "SHA1".to_string(),
-            HashAlgorithm::Sha256(_) => "SHA2-256".to_string(),
-            HashAlgorithm::Sha384(_) => "SHA2-384".to_string(),
-            HashAlgorithm::Sha512(_) => "SHA2-512".to_string(),
-            HashAlgorithm::Sha3_256(_) => "SHA3-256".to_string(),
-            HashAlgorithm::Sha3_384(_) => "SHA3-384".to_string(),
-            HashAlgorithm::Sha3_512(_) => "SHA3-512".to_string(),
-            HashAlgorithm::Blake3(_) => "BLAKE3".to_string(),
+            ChunkData::Buffer(buffer) => buffer,
+            ChunkData::Mapped(mapping, range) => &mapping[range.clone()],
         }
     }
 }
 
+#[cfg(feature = "native")]
 #[derive(Clone, Debug)]
 pub struct FileChunk {
-    pub data: Vec<u8>,
+    pub data: ChunkData,
     pub is_last: bool,
 }
@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs::Metadata;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::hash_algorithms::HashAlgorithm;
+
+/// Input hashed to tell apart algorithms that share a name but not a key.
+const KEY_PROBE: &[u8] = b"parallelhash cache key probe";
+
+/// Identifies a file's contents without reading them: if none of these
+/// change, the file is assumed to be unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileKey {
+    /// Device and inode on Unix, the canonical path elsewhere.
+    file: String,
+    pub size: u64,
+    mtime_ns: i64,
+}
+
+impl FileKey {
+    pub fn new(path: &Path, metadata: &Metadata) -> Option<Self> {
+        #[cfg(unix)]
+        let file = {
+            use std::os::unix::fs::MetadataExt;
+            format!("{}:{}", metadata.dev(), metadata.ino())
+        };
+        #[cfg(not(unix))]
+        let file = path.canonicalize().ok()?.to_string_lossy().into_owned();
+        #[cfg(unix)]
+        let _ = path;
+
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            file,
+            size: metadata.len(),
+            mtime_ns: i64::try_from(mtime.as_nanos()).ok()?,
+        })
+    }
+}
+
+/// Results of previous runs, stored in an SQLite database so unchanged files
+/// do not have to be hashed again.
+pub struct HashCache {
+    connection: Mutex<Connection>,
+    /// One identifier per selected algorithm, in column order.
+    algorithm_ids: Vec<String>,
+    verify_percent: u64,
+    hits: AtomicU64,
+}
+
+impl HashCache {
+    /// Opens or creates the cache at `path`. `verify_percent` of the cache hits
+    /// are hashed again to check that the cached results still hold.
+    pub fn open(path: &Path, algorithms: &[HashAlgorithm], verify_percent: u8) -> Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("Failed to open cache: {}", path.display()))?;
+        connection
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA synchronous = NORMAL;
+                 CREATE TABLE IF NOT EXISTS hashes (
+                     file TEXT NOT NULL,
+                     size INTEGER NOT NULL,
+                     mtime_ns INTEGER NOT NULL,
+                     algorithm TEXT NOT NULL,
+                     digest TEXT NOT NULL,
+                     PRIMARY KEY (file, size, mtime_ns, algorithm)
+                 );",
+            )
+            .with_context(|| format!("Failed to initialize cache: {}", path.display()))?;
+
+        // Keyed algorithms are only interchangeable with the same key, so the
+        // identifier includes the digest of a fixed input
+        let algorithm_ids = algorithms
+            .iter()
+            .map(|algorithm| {
+                let mut algo = algorithm.clone();
+                algo.update(KEY_PROBE);
+                let digest = algo.finalize_reset();
+                format!("{}:{}", algo, algo.encode_digest(&digest))
+            })
+            .collect();
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            algorithm_ids,
+            verify_percent: verify_percent.min(100).into(),
+            hits: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns the cached digests of every selected algorithm, or `None` if
+    /// any of them is missing. Digests are stored as hex.
+    pub fn get(&self, key: &FileKey) -> Result<Option<Vec<Vec<u8>>>> {
+        let connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let mut statement = connection.prepare_cached(
+            "SELECT digest FROM hashes
+             WHERE file = ?1 AND size = ?2 AND mtime_ns = ?3 AND algorithm = ?4",
+        )?;
+        let mut digests = Vec::with_capacity(self.algorithm_ids.len());
+        for algorithm in &self.algorithm_ids {
+            let digest: Option<String> = statement
+                .query_row(
+                    params![key.file, key.size as i64, key.mtime_ns, algorithm],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match digest.and_then(|digest| hex::decode(digest).ok()) {
+                Some(digest) => digests.push(digest),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(digests))
+    }
+
+    pub fn put(&self, key: &FileKey, digests: &[Vec<u8>]) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let transaction = connection.transaction()?;
+        // Entries of earlier versions of the file can no longer be hit
+        transaction
+            .prepare_cached(
+                "DELETE FROM hashes WHERE file = ?1 AND (size != ?2 OR mtime_ns != ?3)",
+            )?
+            .execute(params![key.file, key.size as i64, key.mtime_ns])?;
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT OR REPLACE INTO hashes (file, size, mtime_ns, algorithm, digest)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for (algorithm, digest) in self.algorithm_ids.iter().zip(digests) {
+                statement.execute(params![
+                    key.file,
+                    key.size as i64,
+                    key.mtime_ns,
+                    algorithm,
+                    hex::encode(digest)
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Counts a cache hit and tells whether it should be hashed again to
+    /// verify it. Verified hits are spread evenly over the run.
+    pub fn should_verify(&self) -> bool {
+        let hits = self.hits.fetch_add(1, Ordering::Relaxed) + 1;
+        hits * self.verify_percent / 100 != (hits - 1) * self.verify_percent / 100
+    }
+}
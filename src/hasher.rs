@@ -0,0 +1,325 @@
+use anyhow::{anyhow, Result};
+use crossbeam::channel::bounded;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+use crate::cancel::CancellationToken;
+use crate::device_limiter::PerDeviceJobs;
+use crate::file_processing::{
+    compute_hashes, is_cancelled, list_files, run_pipeline, FileEvent, ReadOptions, Symlinks,
+    TraversalOptions, STDIN_PATH,
+};
+#[cfg(feature = "async")]
+use crate::file_processing::{walk_files, Walked};
+use crate::hash_algorithms::HashAlgorithm;
+use crate::hash_cache::HashCache;
+use crate::output::OutputManager;
+use crate::progress::ProgressSink;
+use crate::results::FileHashResult;
+use crate::utils::HashError;
+
+/// Hashes files and directories with a fixed set of options, writing one
+/// result line per file to an `OutputManager`, or returning the results for
+/// the caller to handle. Built with `Hasher::builder()`,
+/// e.g. `Hasher::builder().algorithms(algorithms).chunk_size(1 << 20)
+/// .follow_symlinks(false).build()`.
+pub struct Hasher {
+    algorithms: Vec<HashAlgorithm>,
+    show_headers: bool,
+    continue_on_error: bool,
+    traversal: TraversalOptions,
+    jobs: usize,
+    per_device_jobs: Option<PerDeviceJobs>,
+    dedup_hard_links: bool,
+    write_xattr: bool,
+    ordered: bool,
+    skip_paths: HashSet<PathBuf>,
+    cache: Option<HashCache>,
+    stdin_name: String,
+    read_options: ReadOptions,
+}
+
+impl Hasher {
+    pub fn builder() -> HasherBuilder {
+        HasherBuilder::default()
+    }
+
+    pub fn algorithms(&self) -> &[HashAlgorithm] {
+        &self.algorithms
+    }
+
+    /// Hashes the files at `paths`, descending into directories, and writes
+    /// the results to `output_manager`.
+    pub fn hash(&self, paths: &[PathBuf], output_manager: &mut OutputManager) -> Result<()> {
+        self.hash_inner(paths, output_manager, None)
+    }
+
+    /// Like `hash`, also reporting the progress to `progress`, e.g. for a
+    /// GUI. The progress of `output_manager` is still updated, and can be
+    /// turned off with `ProgressMode::Off`.
+    pub fn hash_with_progress(
+        &self,
+        paths: &[PathBuf],
+        output_manager: &mut OutputManager,
+        progress: &mut dyn ProgressSink,
+    ) -> Result<()> {
+        self.hash_inner(paths, output_manager, Some(progress))
+    }
+
+    fn hash_inner(
+        &self,
+        paths: &[PathBuf],
+        output_manager: &mut OutputManager,
+        progress: Option<&mut dyn ProgressSink>,
+    ) -> Result<()> {
+        compute_hashes(
+            paths,
+            &self.algorithms,
+            self.show_headers,
+            self.continue_on_error,
+            &self.traversal,
+            self.jobs,
+            self.per_device_jobs,
+            self.dedup_hard_links,
+            self.write_xattr,
+            self.ordered,
+            &self.skip_paths,
+            self.cache.as_ref(),
+            &self.stdin_name,
+            &self.read_options,
+            output_manager,
+            progress,
+        )
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn read_options(&self) -> &ReadOptions {
+        &self.read_options
+    }
+
+    /// Number of files hashed at once.
+    #[cfg(feature = "async")]
+    pub(crate) fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Walks `paths` with the traversal settings of this hasher, for callers
+    /// that read the files themselves.
+    #[cfg(feature = "async")]
+    pub(crate) fn walk_files<'a>(
+        &'a self,
+        paths: &'a [PathBuf],
+    ) -> impl Iterator<Item = Walked> + 'a {
+        walk_files(paths, &self.algorithms, &self.traversal, &self.skip_paths)
+    }
+
+    /// Writes the size and path of each file that `hash` would hash to
+    /// `writer`, one per line, without reading them, e.g. to check the
+    /// traversal and filtering options before a long run. Returns the number
+    /// of files and their total size.
+    pub fn dry_run(&self, paths: &[PathBuf], writer: &mut dyn Write) -> Result<(usize, u64)> {
+        list_files(paths, &self.traversal, &self.skip_paths, writer)
+    }
+
+    /// Hashes the files at `paths` on background threads and returns their
+    /// results as they come, in input order unless the hasher is unordered.
+    /// Archive members follow their archive. Files that cannot be hashed
+    /// yield a result with an error and do not stop the others.
+    pub fn results(self: Arc<Self>, paths: Vec<PathBuf>) -> impl Iterator<Item = FileHashResult> {
+        let (sender, receiver) = bounded(self.read_options.channel_size.max(1));
+        thread::spawn(move || {
+            let mut pending = BTreeMap::new();
+            let mut next_index = 0;
+            let send = |results: Vec<FileHashResult>| -> Result<()> {
+                for result in results {
+                    sender
+                        .send(result)
+                        .map_err(|_| anyhow!("Results are no longer read"))?;
+                }
+                Ok(())
+            };
+            // Stops when the iterator is dropped
+            let _ = run_pipeline(
+                &paths,
+                &self.algorithms,
+                &self.traversal,
+                self.jobs,
+                self.per_device_jobs,
+                self.dedup_hard_links,
+                self.write_xattr,
+                &self.skip_paths,
+                self.cache.as_ref(),
+                &self.stdin_name,
+                &self.read_options,
+                &mut |event| {
+                    let (index, results) = match event {
+                        FileEvent::Results { index, results } => (index, results),
+                        // Files cut short by a cancellation are left out
+                        FileEvent::Failed { index, error, .. } if is_cancelled(&error) => {
+                            (index, Vec::new())
+                        }
+                        FileEvent::Failed { index, path, error } => {
+                            let error = error.downcast().unwrap_or_else(HashError::Other);
+                            (index, vec![FileHashResult::failed(path, error)])
+                        }
+                        _ => return Ok(()),
+                    };
+                    if !self.ordered {
+                        return send(results);
+                    }
+                    pending.insert(index, results);
+                    while let Some(results) = pending.remove(&next_index) {
+                        send(results)?;
+                        next_index += 1;
+                    }
+                    Ok(())
+                },
+            );
+        });
+        receiver.into_iter()
+    }
+}
+
+/// Number of files hashed at once unless set otherwise: one per CPU.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Sets up a `Hasher`. Every setting has the default of the matching
+/// command-line option.
+pub struct HasherBuilder {
+    hasher: Hasher,
+}
+
+impl Default for HasherBuilder {
+    fn default() -> Self {
+        Self {
+            hasher: Hasher {
+                algorithms: Vec::new(),
+                show_headers: false,
+                continue_on_error: false,
+                traversal: TraversalOptions::default(),
+                jobs: default_jobs(),
+                per_device_jobs: None,
+                dedup_hard_links: true,
+                write_xattr: false,
+                ordered: true,
+                skip_paths: HashSet::new(),
+                cache: None,
+                stdin_name: STDIN_PATH.to_string(),
+                read_options: ReadOptions::default(),
+            },
+        }
+    }
+}
+
+impl HasherBuilder {
+    pub fn algorithms(mut self, algorithms: Vec<HashAlgorithm>) -> Self {
+        self.hasher.algorithms = algorithms;
+        self
+    }
+
+    /// Write a header line naming the algorithms before the results.
+    pub fn show_headers(mut self, show_headers: bool) -> Self {
+        self.hasher.show_headers = show_headers;
+        self
+    }
+
+    /// Report files that fail to hash and go on, instead of stopping at the
+    /// first one.
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.hasher.continue_on_error = continue_on_error;
+        self
+    }
+
+    pub fn traversal(mut self, traversal: TraversalOptions) -> Self {
+        self.hasher.traversal = traversal;
+        self
+    }
+
+    /// Follow symbolic links, or list them without hashing.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.hasher.traversal.symlinks = if follow {
+            Symlinks::Follow
+        } else {
+            Symlinks::Report
+        };
+        self
+    }
+
+    /// Number of files hashed at once.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.hasher.jobs = jobs;
+        self
+    }
+
+    pub fn per_device_jobs(mut self, per_device_jobs: Option<PerDeviceJobs>) -> Self {
+        self.hasher.per_device_jobs = per_device_jobs;
+        self
+    }
+
+    /// Read files with several hard links only once.
+    pub fn dedup_hard_links(mut self, dedup_hard_links: bool) -> Self {
+        self.hasher.dedup_hard_links = dedup_hard_links;
+        self
+    }
+
+    /// Store the hashes of each file in its extended attributes (Unix only).
+    pub fn write_xattr(mut self, write_xattr: bool) -> Self {
+        self.hasher.write_xattr = write_xattr;
+        self
+    }
+
+    /// Write the results in input order rather than as files finish.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.hasher.ordered = ordered;
+        self
+    }
+
+    /// Paths left out, such as those already in a resumed output file.
+    pub fn skip_paths(mut self, skip_paths: HashSet<PathBuf>) -> Self {
+        self.hasher.skip_paths = skip_paths;
+        self
+    }
+
+    /// Reuse and store results in `cache`, which must have been opened with
+    /// the same algorithms.
+    pub fn cache(mut self, cache: Option<HashCache>) -> Self {
+        self.hasher.cache = cache;
+        self
+    }
+
+    /// Path printed for data read from standard input.
+    pub fn stdin_name(mut self, stdin_name: impl Into<String>) -> Self {
+        self.hasher.stdin_name = stdin_name.into();
+        self
+    }
+
+    pub fn read_options(mut self, read_options: ReadOptions) -> Self {
+        self.hasher.read_options = read_options;
+        self
+    }
+
+    /// Stop hashing once `cancel` is cancelled, e.g. from another thread.
+    /// `hash` then returns `HashError::Cancelled` after writing the results
+    /// of the files already done, and `results` ends after them.
+    pub fn cancellation_token(mut self, cancel: CancellationToken) -> Self {
+        self.hasher.read_options.cancel = cancel;
+        self
+    }
+
+    /// Number of bytes read and hashed at a time, at least 1.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.hasher.read_options.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn build(self) -> Hasher {
+        self.hasher
+    }
+}
@@ -1,11 +1,88 @@
+#[cfg(feature = "native")]
+pub mod affinity;
+#[cfg(feature = "native")]
+pub mod alternate_streams;
+#[cfg(feature = "native")]
+pub mod archives;
+#[cfg(feature = "async")]
+pub mod async_hashing;
+#[cfg(feature = "native")]
+pub mod bench;
+#[cfg(feature = "native")]
+pub mod buffer_pool;
+pub mod cancel;
+#[cfg(feature = "native")]
 pub mod checksum_verification;
+#[cfg(feature = "native")]
+pub mod chunk_tuner;
+#[cfg(feature = "native")]
 pub mod cli;
+#[cfg(feature = "serve")]
+pub mod daemon;
+#[cfg(feature = "native")]
+pub mod device_limiter;
+#[cfg(all(feature = "native", target_os = "linux"))]
+pub mod direct_reader;
+#[cfg(feature = "native")]
 pub mod file_processing;
+#[cfg(feature = "native")]
+pub mod file_reader;
+#[cfg(feature = "native")]
+pub mod fuzzy_matching;
+#[cfg(feature = "native")]
+pub mod gpg;
+#[cfg(feature = "native")]
+pub mod hard_links;
 pub mod hash_algorithms;
+#[cfg(feature = "native")]
+pub mod hash_cache;
+#[cfg(feature = "native")]
+pub mod hasher;
+#[cfg(feature = "native")]
+pub mod minisign;
+#[cfg(feature = "native")]
 pub mod output;
+#[cfg(feature = "native")]
+pub mod output_format;
+#[cfg(feature = "native")]
+pub mod par2;
+#[cfg(feature = "native")]
+pub mod path_escape;
+#[cfg(feature = "native")]
+pub mod path_rewrite;
+#[cfg(feature = "native")]
+pub mod priority;
+#[cfg(feature = "native")]
+pub mod progress;
+#[cfg(feature = "native")]
+pub mod remote;
+pub mod results;
+#[cfg(feature = "native")]
+pub mod resume;
+#[cfg(feature = "s3")]
+pub mod s3;
+#[cfg(feature = "native")]
+pub mod self_test;
+pub mod sequential;
+#[cfg(feature = "native")]
+pub mod stats;
+#[cfg(feature = "native")]
+pub mod throttle;
+#[cfg(feature = "native")]
+pub mod torrent;
+#[cfg(all(feature = "native", target_os = "linux"))]
+pub mod uring_reader;
 pub mod utils;
+#[cfg(feature = "native")]
+pub mod xattr_verification;
+#[cfg(feature = "native")]
+pub mod xattrs;
 
+#[cfg(feature = "native")]
 pub use cli::Args;
-pub use file_processing::compute_hashes;
+#[cfg(feature = "native")]
+pub use hasher::{Hasher, HasherBuilder};
+#[cfg(feature = "native")]
 pub use output::OutputManager;
+pub use results::FileHashResult;
 pub use utils::validate_algorithms;
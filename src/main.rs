@@ -1,39 +1,378 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
+use parallelhash::bench::run_bench;
+use parallelhash::cancel::CancellationToken;
+use parallelhash::checksum_verification::{glob_filter, CheckOptions};
+use parallelhash::chunk_tuner::ChunkSize;
+use parallelhash::file_processing::{
+    ReadOptions, Symlinks, TraversalOptions, DEFAULT_CHUNK_SIZE, STDIN_PATH,
+};
+use parallelhash::fuzzy_matching::fuzzy_match;
+use parallelhash::hash_algorithms::{AlgorithmOptions, Blake3Threads, HashAlgorithm};
+use parallelhash::hash_cache::HashCache;
+use parallelhash::hasher::default_jobs;
+use parallelhash::output::{format_bytes, ProgressMode, ProgressOptions};
+use parallelhash::output_format::{digest_name, run_metadata, ResultFormat};
+use parallelhash::par2::verify_par2;
+use parallelhash::path_rewrite::PathRewrite;
+use parallelhash::priority;
+use parallelhash::resume;
+use parallelhash::self_test::run_self_test;
+use parallelhash::stats::{AlgorithmTimings, StatsRecorder};
+use parallelhash::throttle::Throttle;
+use parallelhash::torrent::verify_torrent;
+use parallelhash::utils::{parse_hex_key, read_path_list, HashError};
+use parallelhash::xattr_verification::verify_xattrs;
 use parallelhash::{
-    checksum_verification, compute_hashes, validate_algorithms, Args, OutputManager,
+    checksum_verification, gpg, minisign, validate_algorithms, Args, Hasher, OutputManager,
 };
+use std::fs;
+use std::io::Write;
+use std::process::ExitCode;
+use std::time::Duration;
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let algorithms = validate_algorithms(&args.algorithms)?;
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {:?}", error);
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
 
-    let mut output_manager = OutputManager::new(args.output.as_deref())?;
+/// Exit status for an error, by the kind of failure that stopped the run.
+/// Invalid arguments exit with 2 from the argument parser.
+fn exit_code(error: &anyhow::Error) -> u8 {
+    match error.downcast_ref::<HashError>() {
+        Some(HashError::NotFound(_)) => 3,
+        Some(HashError::PermissionDenied(_)) => 4,
+        Some(HashError::ReadError { .. }) => 5,
+        Some(HashError::OutputError(_)) => 6,
+        Some(HashError::WorkerPanic(_)) => 7,
+        Some(HashError::Cancelled | HashError::Other(_)) | None => 1,
+    }
+}
 
-    if let Some(check_file) = args.check {
-        if !args.algorithms.is_empty() {
-            eprintln!("Warning: Algorithms specified with -a option will take precedence over the header in the checksum file.");
+fn run() -> Result<()> {
+    let mut args = Args::parse();
+    if args.background {
+        // Before any thread is spawned, as they inherit the priority
+        if let Err(e) = priority::enter_background() {
+            eprintln!("Warning: failed to lower the process priority: {}", e);
+        }
+    }
+    if let Some(list) = &args.files_from {
+        args.paths.extend(read_path_list(list, args.null)?);
+    }
+    if args.stdin {
+        args.paths.push(STDIN_PATH.into());
+    }
+    if args.entropy {
+        args.algorithms.push("entropy".to_string());
+    }
+    let hmac_key = match (&args.hmac_key, &args.hmac_key_hex) {
+        (Some(path), _) => Some(
+            fs::read(path)
+                .with_context(|| format!("Failed to read HMAC key file: {}", path.display()))?,
+        ),
+        (None, Some(hex_key)) => Some(hex::decode(hex_key.trim()).context("Invalid hex HMAC key")?),
+        (None, None) => None,
+    };
+    let options = AlgorithmOptions {
+        highway_key: args.highway_key.as_deref().map(parse_hex_key).transpose()?,
+        hmac_key,
+        blake3_key: args.blake3_key.as_deref().map(parse_hex_key).transpose()?,
+        blake3_context: args.blake3_derive_key.clone(),
+        blake3_threads: Blake3Threads::new(args.blake3_threads)?,
+    };
+    let algorithms = validate_algorithms(&args.algorithms, &options)?;
+    let (chunk_size, adaptive_chunk_size) = match args.chunk_size {
+        ChunkSize::Fixed(size) => (size, false),
+        ChunkSize::Auto => (DEFAULT_CHUNK_SIZE, true),
+    };
+    let read_options = ReadOptions {
+        channel_size: args.channel_size,
+        chunk_size,
+        adaptive_chunk_size,
+        mmap_threshold: args.mmap.then_some(args.mmap_threshold),
+        io_backend: args.io_backend,
+        direct_io: args.direct_io,
+        fadvise: args.fadvise,
+        pin_threads: args.pin_threads,
+        archives: args.archive.clone(),
+        decompress: args.decompress,
+        s3_check_etag: args.s3_check_etag,
+        cancel: CancellationToken::new(),
+        timings: AlgorithmTimings::default(),
+        retries: args.retries,
+        retry_delay: Duration::from_millis(args.retry_delay),
+        throttle: args.max_throughput.map(Throttle::new),
+    };
+    let traversal = TraversalOptions {
+        symlinks: if args.no_follow_symlinks {
+            Symlinks::Report
+        } else {
+            args.symlinks
+        },
+        respect_gitignore: args.respect_gitignore,
+        max_depth: args.max_depth,
+        skip_hidden: args.skip_hidden,
+        alternate_streams: args.ads,
+        special_files: args.special_files,
+        min_size: args.min_size,
+        max_size: args.max_size,
+        newer_than: args.newer_than,
+        older_than: args.older_than,
+        order: args.order,
+        sort: args.sort,
+    };
+    if args.ads && !cfg!(windows) {
+        anyhow::bail!("--ads is only available on Windows");
+    }
+    if (args.write_xattr || args.check_xattr.is_some()) && !cfg!(unix) {
+        anyhow::bail!("Extended attributes are only available on Unix");
+    }
+    let jobs = args.jobs.unwrap_or_else(default_jobs);
+
+    let progress = ProgressOptions {
+        mode: if args.no_progress || args.silent {
+            ProgressMode::Off
+        } else {
+            args.progress
+        },
+        format: args.progress_format,
+        writer: args.progress_fd.map(progress_writer).transpose()?,
+        show_summary: !args.silent,
+    };
+    let cache = args
+        .cache
+        .as_deref()
+        .map(|path| HashCache::open(path, &algorithms, args.cache_verify.unwrap_or(0)))
+        .transpose()?;
+    let path_rewrite = PathRewrite {
+        relative_to: args.relative_to.clone(),
+        strip_prefix: args.strip_prefix.clone(),
+        add_prefix: args.add_prefix.clone(),
+        normalize_separators: args.normalize_paths,
+    };
+    // The manifest lists the paths as rewritten
+    let completed = match (&args.output, args.resume) {
+        (Some(output), true) => resume::completed_paths(output, &algorithms)?.map(|paths| {
+            paths
+                .iter()
+                .map(|path| path_rewrite.reverse(path))
+                .collect()
+        }),
+        _ => None,
+    };
+    if args.dry_run {
+        let (files, bytes) = Hasher::builder()
+            .traversal(traversal)
+            .skip_paths(completed.unwrap_or_default())
+            .build()
+            .dry_run(&args.paths, &mut std::io::stdout().lock())?;
+        if !args.silent {
+            eprintln!(
+                "Dry run: {} files would be hashed, total size: {}",
+                files,
+                format_bytes(bytes)
+            );
+        }
+        return Ok(());
+    }
+    let mut output_manager = OutputManager::new(
+        args.output.as_deref(),
+        completed.is_some(),
+        args.color,
+        progress,
+    )?;
+    output_manager.set_format(args.format.formatter());
+    output_manager.set_path_rewrite(path_rewrite);
+    if let Some(path) = args.stats {
+        output_manager.set_stats(StatsRecorder::new(path, args.stats_format));
+    }
+    if let Some(path) = &args.errors_to {
+        output_manager.set_error_log(path)?;
+    }
+    if let Some(path) = args.summary_json {
+        output_manager.set_summary_json(path, read_options.timings.clone());
+    }
+    #[cfg(unix)]
+    output_manager.snapshot_on_sigusr1()?;
+
+    if let Some(address) = args.serve {
+        serve(&address, &algorithms, &options, &read_options, jobs)?;
+    } else if args.bench {
+        run_bench(
+            &algorithms,
+            &args.paths,
+            &read_options,
+            &mut std::io::stdout().lock(),
+        )?;
+    } else if args.self_test {
+        run_self_test(&read_options, &mut std::io::stdout().lock())?;
+    } else if let Some(check_file) = args.check {
+        if args.verify_signature {
+            gpg::verify(&check_file)?;
+        }
+        if let Some(public_key) = &args.verify_minisign {
+            let trusted_comment = minisign::verify(&check_file, public_key)?;
+            if !args.silent {
+                eprintln!("Signature verified");
+                if let Some(comment) = trusted_comment {
+                    eprintln!("Trusted comment: {}", comment);
+                }
+            }
         }
         checksum_verification::verify_checksums(
             &check_file,
             &algorithms,
+            &options,
+            &CheckOptions {
+                format: args.check_format,
+                verify_only: args.verify_only.clone(),
+                ignore_hash_case: args.ignore_hash_case,
+                ignore_path_case: args.ignore_path_case,
+                base_dir: args.check_dir.clone(),
+                failed_only: args.failed_only,
+                recheck_failures: args.recheck_failures,
+                filter: if args.check_filter.is_empty() {
+                    None
+                } else {
+                    Some(glob_filter(&args.check_filter)?)
+                },
+            },
             args.show_headers,
-            args.channel_size,
-            args.chunk_size,
+            &read_options,
             &mut output_manager,
         )?;
-    } else {
-        compute_hashes(
+    } else if let Some(dir) = args.check_xattr {
+        verify_xattrs(
+            &dir,
+            &options,
+            args.show_headers,
+            traversal.symlinks == Symlinks::Follow,
+            &read_options,
+            &mut output_manager,
+        )?;
+    } else if let Some([torrent, dir]) = args.check_torrent.as_deref() {
+        verify_torrent(torrent, dir, args.show_headers, &mut output_manager)?;
+    } else if let Some(par2) = args.check_par2 {
+        verify_par2(&par2, args.show_headers, &mut output_manager)?;
+    } else if let Some(manifest) = args.fuzzy_match {
+        fuzzy_match(
+            &manifest,
             &args.paths,
-            &algorithms,
+            &options,
             args.show_headers,
-            args.continue_on_error,
-            !args.no_follow_symlinks,
-            args.channel_size,
-            args.chunk_size,
+            traversal.symlinks == Symlinks::Follow,
+            &read_options,
             &mut output_manager,
         )?;
+    } else {
+        if args.format == ResultFormat::Digest {
+            let (named, unnamed): (Vec<_>, Vec<_>) = algorithms
+                .iter()
+                .partition(|algo| digest_name(algo).is_some());
+            if named.is_empty() {
+                anyhow::bail!(
+                    "The digest format needs MD5, SHA1, SHA2-256 or SHA2-512 among the algorithms"
+                );
+            }
+            if !unnamed.is_empty() {
+                let names = unnamed
+                    .iter()
+                    .map(|algo| algo.to_string())
+                    .collect::<Vec<_>>();
+                eprintln!(
+                    "Warning: {} left out of the digest format, which has no name for them",
+                    names.join(", ")
+                );
+            }
+        }
+        // A resumed manifest already has its header
+        if args.metadata && completed.is_none() {
+            for line in run_metadata(&args.paths, &algorithms) {
+                output_manager.write_comment(&line)?;
+            }
+        }
+        if completed.is_none() {
+            for comment in &args.comment {
+                output_manager.write_comment(comment)?;
+            }
+        }
+        Hasher::builder()
+            .algorithms(algorithms)
+            // A resumed manifest already has its header
+            .show_headers(args.show_headers && completed.is_none())
+            .continue_on_error(args.continue_on_error)
+            .traversal(traversal)
+            .jobs(jobs)
+            .per_device_jobs(args.per_device_jobs)
+            .dedup_hard_links(!args.no_dedup_hardlinks)
+            .write_xattr(args.write_xattr)
+            .ordered(!args.unordered)
+            .skip_paths(completed.unwrap_or_default())
+            .cache(cache)
+            .stdin_name(args.stdin_name)
+            .read_options(read_options)
+            .build()
+            .hash(&args.paths, &mut output_manager)?;
     }
 
+    drop(output_manager);
+    if let (Some(key_id), Some(output)) = (&args.sign, &args.output) {
+        let signature = gpg::sign(output, key_id.as_deref(), args.clearsign)?;
+        if !args.silent {
+            eprintln!("Signed: {}", signature.display());
+        }
+    }
+    if let (Some(secret_key), Some(output)) = (&args.minisign, &args.output) {
+        let signature = minisign::sign(output, secret_key)?;
+        if !args.silent {
+            eprintln!("Signed: {}", signature.display());
+        }
+    }
     Ok(())
 }
+
+/// Runs the daemon of --serve until the process is stopped.
+#[cfg(feature = "serve")]
+fn serve(
+    address: &str,
+    algorithms: &[HashAlgorithm],
+    options: &AlgorithmOptions,
+    read_options: &ReadOptions,
+    jobs: usize,
+) -> Result<()> {
+    parallelhash::daemon::serve(address, algorithms, options, read_options, jobs)
+}
+
+#[cfg(not(feature = "serve"))]
+fn serve(
+    _address: &str,
+    _algorithms: &[HashAlgorithm],
+    _options: &AlgorithmOptions,
+    _read_options: &ReadOptions,
+    _jobs: usize,
+) -> Result<()> {
+    anyhow::bail!("--serve is unavailable: parallelhash was built without the serve feature")
+}
+
+/// Opens an inherited file descriptor for progress reports.
+#[cfg(unix)]
+fn progress_writer(fd: i32) -> Result<Box<dyn Write>> {
+    use std::os::fd::BorrowedFd;
+    // SAFETY: the descriptor is only duplicated, which fails cleanly if it is
+    // not open
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) }
+        .try_clone_to_owned()
+        .with_context(|| format!("Invalid progress file descriptor: {}", fd))?;
+    Ok(Box::new(fs::File::from(fd)))
+}
+
+#[cfg(not(unix))]
+fn progress_writer(_fd: i32) -> Result<Box<dyn Write>> {
+    anyhow::bail!("--progress-fd is only available on Unix")
+}
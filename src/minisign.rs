@@ -0,0 +1,323 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512};
+use ring::signature::{Ed25519KeyPair, UnparsedPublicKey, ED25519};
+use sha2::{Digest, Sha512};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Signature algorithm of keys, and of signatures over the data itself.
+const ED25519_ALGORITHM: &[u8] = b"Ed";
+/// Signature algorithm of minisign signatures over the BLAKE2b-512 hash of
+/// the data.
+const PREHASHED_ALGORITHM: &[u8] = b"ED";
+const UNTRUSTED_COMMENT: &str = "untrusted comment: ";
+const TRUSTED_COMMENT: &str = "trusted comment: ";
+
+/// The tool whose key and signature files are read and written: both use
+/// Ed25519 keys, but store secret keys and signatures differently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyFormat {
+    Minisign,
+    Signify,
+}
+
+impl KeyFormat {
+    /// Where the signature of `manifest` is written and looked for: the same
+    /// path with `.minisig` or `.sig` appended.
+    pub fn signature_path(self, manifest: &Path) -> PathBuf {
+        let mut path = OsString::from(manifest);
+        path.push(match self {
+            KeyFormat::Minisign => ".minisig",
+            KeyFormat::Signify => ".sig",
+        });
+        PathBuf::from(path)
+    }
+}
+
+struct SecretKey {
+    format: KeyFormat,
+    key_id: [u8; 8],
+    key_pair: Ed25519KeyPair,
+}
+
+struct PublicKey {
+    key_id: [u8; 8],
+    key: [u8; 32],
+}
+
+/// Signs `manifest` with the minisign or signify secret key in the file
+/// `secret_key`, asking for its password on the terminal if it is
+/// encrypted, and writes the signature next to it in the format of the key.
+/// Returns the path of the signature.
+pub fn sign(manifest: &Path, secret_key: &Path) -> Result<PathBuf> {
+    let key = read_secret_key(secret_key)?;
+    let data = fs::read(manifest)
+        .with_context(|| format!("Failed to read manifest: {}", manifest.display()))?;
+    let signature_path = key.format.signature_path(manifest);
+    let contents = match key.format {
+        KeyFormat::Minisign => {
+            let signature = key.key_pair.sign(&Blake2b512::digest(&data));
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs());
+            let file_name = manifest.file_name().unwrap_or_default().to_string_lossy();
+            let trusted_comment = format!("timestamp:{}\tfile:{}\thashed", timestamp, file_name);
+            let global_signature = key
+                .key_pair
+                .sign(&[signature.as_ref(), trusted_comment.as_bytes()].concat());
+            format!(
+                "{}signature from parallelhash secret key\n{}\n{}{}\n{}\n",
+                UNTRUSTED_COMMENT,
+                encode_signature(PREHASHED_ALGORITHM, &key.key_id, signature.as_ref()),
+                TRUSTED_COMMENT,
+                trusted_comment,
+                BASE64.encode(global_signature)
+            )
+        }
+        KeyFormat::Signify => {
+            let signature = key.key_pair.sign(&data);
+            let public_key = secret_key.with_extension("pub");
+            format!(
+                "{}verify with {}\n{}\n",
+                UNTRUSTED_COMMENT,
+                public_key.file_name().unwrap_or_default().to_string_lossy(),
+                encode_signature(ED25519_ALGORITHM, &key.key_id, signature.as_ref())
+            )
+        }
+    };
+    fs::write(&signature_path, contents)
+        .with_context(|| format!("Failed to write signature: {}", signature_path.display()))?;
+    Ok(signature_path)
+}
+
+/// Checks the minisign or signify signature of `manifest`, found next to it
+/// with `.minisig` or `.sig` appended, with `public_key`: a key file, or the
+/// key itself in base64 as printed by minisign. Returns the trusted comment
+/// of minisign signatures, which is signed too.
+pub fn verify(manifest: &Path, public_key: &str) -> Result<Option<String>> {
+    let key = read_public_key(public_key)?;
+    let signature_path = [KeyFormat::Minisign, KeyFormat::Signify]
+        .map(|format| format.signature_path(manifest))
+        .into_iter()
+        .find(|path| path.exists())
+        .ok_or_else(|| {
+            anyhow!(
+                "No signature found for {}: expected {}",
+                manifest.display(),
+                KeyFormat::Minisign.signature_path(manifest).display()
+            )
+        })?;
+    let text = fs::read_to_string(&signature_path)
+        .with_context(|| format!("Failed to read signature: {}", signature_path.display()))?;
+    let mut lines = text
+        .lines()
+        .filter(|line| !line.starts_with(UNTRUSTED_COMMENT));
+    let encoded = lines.next().unwrap_or_default();
+    let decoded = BASE64
+        .decode(encoded.trim())
+        .ok()
+        .filter(|decoded| decoded.len() == 74)
+        .ok_or_else(|| anyhow!("Invalid signature file: {}", signature_path.display()))?;
+    let (algorithm, rest) = decoded.split_at(2);
+    let (key_id, signature) = rest.split_at(8);
+    if key_id != key.key_id {
+        bail!(
+            "{} was signed with another key than {}",
+            manifest.display(),
+            public_key
+        );
+    }
+
+    let data = fs::read(manifest)
+        .with_context(|| format!("Failed to read manifest: {}", manifest.display()))?;
+    let message = match algorithm {
+        PREHASHED_ALGORITHM => Blake2b512::digest(&data).to_vec(),
+        ED25519_ALGORITHM => data,
+        _ => bail!(
+            "Unsupported signature algorithm in {}",
+            signature_path.display()
+        ),
+    };
+    let verifier = UnparsedPublicKey::new(&ED25519, key.key);
+    verifier
+        .verify(&message, signature)
+        .map_err(|_| anyhow!("Bad signature for {}", manifest.display()))?;
+
+    let Some(trusted_comment) = lines.next() else {
+        return Ok(None);
+    };
+    let trusted_comment = trusted_comment
+        .strip_prefix(TRUSTED_COMMENT)
+        .ok_or_else(|| anyhow!("Invalid signature file: {}", signature_path.display()))?;
+    let global_signature = lines
+        .next()
+        .and_then(|line| BASE64.decode(line.trim()).ok())
+        .ok_or_else(|| anyhow!("Invalid signature file: {}", signature_path.display()))?;
+    verifier
+        .verify(
+            &[signature, trusted_comment.as_bytes()].concat(),
+            &global_signature,
+        )
+        .map_err(|_| {
+            anyhow!(
+                "Bad signature of the trusted comment for {}",
+                manifest.display()
+            )
+        })?;
+    Ok(Some(trusted_comment.to_string()))
+}
+
+fn read_public_key(public_key: &str) -> Result<PublicKey> {
+    let path = Path::new(public_key);
+    let text = if path.exists() {
+        fs::read_to_string(path)
+            .with_context(|| format!("Failed to read public key: {}", path.display()))?
+    } else {
+        public_key.to_string()
+    };
+    let decoded = decode_key_text(&text)
+        .filter(|decoded| decoded.len() == 42 && decoded.starts_with(ED25519_ALGORITHM))
+        .ok_or_else(|| anyhow!("Invalid public key: {}", public_key))?;
+    Ok(PublicKey {
+        key_id: decoded[2..10].try_into()?,
+        key: decoded[10..42].try_into()?,
+    })
+}
+
+fn read_secret_key(path: &Path) -> Result<SecretKey> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read secret key: {}", path.display()))?;
+    let invalid = || anyhow!("Invalid secret key: {}", path.display());
+    let decoded = decode_key_text(&text).ok_or_else(invalid)?;
+    if !decoded.starts_with(ED25519_ALGORITHM) {
+        return Err(invalid());
+    }
+    match (decoded.len(), &decoded[2..4]) {
+        // Ed, KDF (Sc for scrypt or none), checksum B2, KDF salt and limits,
+        // then the key id, the secret key and its checksum, encrypted
+        (158, b"Sc" | b"\0\0") if &decoded[4..6] == b"B2" => {
+            let mut key = decoded[54..158].to_vec();
+            if &decoded[2..4] == b"Sc" {
+                let password = read_password(&format!("Password for {}: ", path.display()))?;
+                let (log_n, r, p) = scrypt_params(
+                    u64::from_le_bytes(decoded[38..46].try_into()?),
+                    u64::from_le_bytes(decoded[46..54].try_into()?),
+                );
+                // The length in the parameters only applies to password hashes
+                let params = scrypt::Params::new(log_n, r, p, scrypt::Params::RECOMMENDED_LEN)
+                    .map_err(|_| invalid())?;
+                let mut stream = vec![0; key.len()];
+                scrypt::scrypt(password.as_bytes(), &decoded[6..38], &params, &mut stream)
+                    .map_err(|_| invalid())?;
+                for (byte, mask) in key.iter_mut().zip(stream) {
+                    *byte ^= mask;
+                }
+            }
+            let (key_id, rest) = key.split_at(8);
+            let (secret, checksum) = rest.split_at(64);
+            let expected = Blake2b::<U32>::digest([ED25519_ALGORITHM, key_id, secret].concat());
+            if checksum != expected.as_slice() {
+                bail!("Wrong password for secret key: {}", path.display());
+            }
+            key_pair(KeyFormat::Minisign, key_id, secret, path)
+        }
+        // Ed, KDF BK (bcrypt) with its rounds and salt, checksum, key id
+        // and secret key
+        (104, b"BK") => {
+            if decoded[4..8] != [0; 4] {
+                bail!(
+                    "Encrypted signify keys are not supported, create one with signify -n: {}",
+                    path.display()
+                );
+            }
+            let (checksum, key_id, secret) = (&decoded[24..32], &decoded[32..40], &decoded[40..]);
+            if Sha512::digest(secret)[..8] != *checksum {
+                return Err(invalid());
+            }
+            key_pair(KeyFormat::Signify, key_id, secret, path)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Builds the key pair of a secret key stored as its seed followed by its
+/// public key.
+fn key_pair(format: KeyFormat, key_id: &[u8], secret: &[u8], path: &Path) -> Result<SecretKey> {
+    let key_pair = Ed25519KeyPair::from_seed_and_public_key(&secret[..32], &secret[32..])
+        .map_err(|_| anyhow!("Invalid secret key: {}", path.display()))?;
+    Ok(SecretKey {
+        format,
+        key_id: key_id.try_into()?,
+        key_pair,
+    })
+}
+
+/// Decodes the base64 line of a key file, after its untrusted comment, or
+/// of a key given by itself.
+fn decode_key_text(text: &str) -> Option<Vec<u8>> {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with(UNTRUSTED_COMMENT))?;
+    BASE64.decode(line).ok()
+}
+
+fn encode_signature(algorithm: &[u8], key_id: &[u8], signature: &[u8]) -> String {
+    BASE64.encode([algorithm, key_id, signature].concat())
+}
+
+/// Reads a password from the terminal without echoing it, or from stdin
+/// when there is no terminal.
+fn read_password(prompt: &str) -> Result<String> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    let mut password = String::new();
+    #[cfg(unix)]
+    if let Ok(tty) = fs::File::options().read(true).write(true).open("/dev/tty") {
+        use std::os::unix::io::AsRawFd;
+        let fd = tty.as_raw_fd();
+        // SAFETY: termios is plain data filled in by tcgetattr before use,
+        // on a descriptor that stays open until the end of this block
+        unsafe {
+            let mut termios = std::mem::zeroed::<libc::termios>();
+            let saved = (libc::tcgetattr(fd, &mut termios) == 0).then_some(termios);
+            if let Some(mut quiet) = saved {
+                quiet.c_lflag &= !libc::ECHO;
+                libc::tcsetattr(fd, libc::TCSANOW, &quiet);
+            }
+            let read = io::BufReader::new(&tty).read_line(&mut password);
+            if let Some(saved) = saved {
+                libc::tcsetattr(fd, libc::TCSANOW, &saved);
+            }
+            eprintln!();
+            read.context("Failed to read the password")?;
+        }
+        return Ok(password.trim_end_matches(['\r', '\n']).to_string());
+    }
+    io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read the password")?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// The scrypt cost parameters (log2 of N, r and p) libsodium derives from the
+/// operations and memory limits stored in minisign keys.
+fn scrypt_params(ops_limit: u64, mem_limit: u64) -> (u8, u32, u32) {
+    let ops_limit = ops_limit.max(32768);
+    let r = 8;
+    let log_n = |max_n: u64| (1..63).find(|&log_n| 1 << log_n > max_n / 2).unwrap_or(63);
+    if ops_limit < mem_limit / 32 {
+        (log_n(ops_limit / (r as u64 * 4)), r, 1)
+    } else {
+        let log_n = log_n(mem_limit / (r as u64 * 128));
+        let max_rp = ((ops_limit / 4) >> log_n).min(0x3fff_ffff);
+        (log_n, r, max_rp as u32 / r)
+    }
+}
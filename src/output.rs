@@ -1,109 +1,613 @@
-use anyhow::Result;
-use std::collections::VecDeque;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
 use std::f64;
 use std::fs::File;
-use std::io::{self, Write};
-use std::path::Path;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::{Duration, Instant};
 
+use crate::hash_algorithms::HashAlgorithm;
+use crate::output_format::{OutputFormat, Summary, TextFormat};
+use crate::path_escape::result_line;
+use crate::path_rewrite::PathRewrite;
+use crate::results::FileHashResult;
+use crate::stats::{write_summary_json, AlgorithmTimings, StatsRecorder};
+use crate::utils::HashError;
+
 const FKIB: f64 = (1024 * 1024) as f64;
-const UPDATE_INTERVAL: Duration = Duration::from_millis(200);
-const THROUGHPUT_WINDOW: Duration = Duration::from_secs(1);
+const UPDATES_PER_SECOND: u8 = 5;
+
+const LINE_INTERVAL: Duration = Duration::from_secs(10);
+const JSON_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Formats each digest for output, as hex for most algorithms.
+pub fn format_digests(digests: &[(HashAlgorithm, Vec<u8>)]) -> Vec<String> {
+    digests
+        .iter()
+        .map(|(algorithm, digest)| algorithm.encode_digest(digest))
+        .collect()
+}
+
+/// Formats a result as an output line, or `None` for a file that failed with
+/// an error other than not being found, which is reported on stderr instead.
+/// Items that were not hashed get `N/A` columns, followed by why.
+pub fn format_result(result: &FileHashResult, algorithms: &[HashAlgorithm]) -> Option<String> {
+    let not_hashed = || result_line(&vec!["N/A"; algorithms.len()].join("  "), &result.path);
+    match (&result.error, result.note) {
+        (Some(HashError::NotFound(e)), _) => {
+            Some(format!("{}  (File not found: {})", not_hashed(), e))
+        }
+        (Some(_), _) => None,
+        (None, Some(note)) => Some(format!("{} ({})", not_hashed(), note)),
+        (None, None) => Some(result_line(
+            &format_digests(&result.digests).join("  "),
+            &result.path,
+        )),
+    }
+}
+
+/// How progress is reported on stderr while hashing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    /// A bar when stderr is a terminal, plain lines otherwise
+    #[default]
+    Auto,
+    /// A progress bar redrawn in place
+    Bar,
+    /// A plain line every ten seconds, suitable for log files
+    Lines,
+    /// No progress reports
+    Off,
+}
+
+/// How periodic progress reports are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProgressFormat {
+    /// Human-readable bar or lines
+    #[default]
+    Text,
+    /// One JSON object per line, every second
+    Json,
+}
+
+/// Whether results are colored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Color when results go to a terminal and NO_COLOR is not set
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// Highlights applied to parts of the results when coloring is enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Highlight {
+    Success,
+    Failure,
+}
+
+/// Settings for the progress reports and summary written while hashing.
+pub struct ProgressOptions {
+    pub mode: ProgressMode,
+    pub format: ProgressFormat,
+    /// Destination of progress lines and JSON events; stderr when not set.
+    /// The progress bar is always drawn on stderr.
+    pub writer: Option<Box<dyn Write>>,
+    /// Print the summary line when hashing finishes.
+    pub show_summary: bool,
+}
+
+impl Default for ProgressOptions {
+    fn default() -> Self {
+        Self {
+            mode: ProgressMode::Auto,
+            format: ProgressFormat::Text,
+            writer: None,
+            show_summary: true,
+        }
+    }
+}
 
 pub struct OutputManager {
     writer: Box<dyn Write>,
+    /// Whether results go to a terminal, where they would mix with the
+    /// progress bar unless it is cleared while they are written.
+    writer_is_terminal: bool,
+    color: bool,
+    format: Box<dyn OutputFormat>,
+    progress: ProgressBar,
+    progress_format: ProgressFormat,
+    progress_writer: Box<dyn Write>,
+    /// When the next progress line is due and the interval between lines,
+    /// unless progress is shown as a bar or not at all.
+    next_line: Option<(Instant, Duration)>,
+    show_summary: bool,
+    current_path: Option<PathBuf>,
+    /// What the SIGUSR1 thread prints a snapshot of, once it is started.
+    snapshot: Option<Arc<Mutex<Snapshot>>>,
     start_time: Instant,
-    next_report: Instant,
+    counts: Counts,
+    pending_results: BTreeMap<usize, Vec<FileHashResult>>,
+    next_result_index: usize,
+    stats: Option<StatsRecorder>,
+    errors: usize,
+    /// Where to write the JSON summary, and the algorithm timings it reports.
+    summary_json: Option<(PathBuf, AlgorithmTimings)>,
+    /// Where per-file errors are written instead of stderr.
+    error_log: Option<File>,
+    path_rewrite: PathRewrite,
+}
+
+/// The counts the progress reports show.
+#[derive(Clone, Default)]
+struct Counts {
     processed_files: usize,
+    discovered_files: usize,
+    discovered_bytes: u64,
+    discovery_finished: bool,
     processed_bytes: u64,
-    recent_updates: VecDeque<(Instant, u64)>,
+}
+
+impl Counts {
+    fn progress_text(&self, start_time: Instant) -> String {
+        let elapsed = start_time.elapsed();
+        let speed = self.processed_bytes as f64 / elapsed.as_secs_f64() / FKIB;
+        let mut bytes = format_bytes(self.processed_bytes);
+        if self.discovery_finished {
+            let total = self.discovered_bytes.max(self.processed_bytes);
+            let percent = if total > 0 {
+                self.processed_bytes as f64 * 100.0 / total as f64
+            } else {
+                100.0
+            };
+            bytes = format!("{}/{} ({:.1}%)", bytes, format_bytes(total), percent);
+        }
+        format!(
+            "Progress: {}, {}, {:.2} MiB/s, elapsed: {}",
+            bytes,
+            self.file_count(),
+            speed,
+            format_duration(elapsed)
+        )
+    }
+
+    /// Processed files, out of the discovered ones when they are known. A
+    /// trailing `+` means the scan is still finding more.
+    fn file_count(&self) -> String {
+        if self.discovered_files == 0 {
+            return format!("{} files", self.processed_files);
+        }
+        format!(
+            "{}/{}{} files",
+            self.processed_files,
+            self.discovered_files,
+            if self.discovery_finished { "" } else { "+" }
+        )
+    }
+}
+
+/// The state a progress snapshot is printed from, shared with the thread
+/// that waits for SIGUSR1.
+struct Snapshot {
+    counts: Counts,
+    current_path: Option<PathBuf>,
 }
 
 impl OutputManager {
-    pub fn new(output_path: Option<&Path>) -> Result<Self> {
-        let writer: Box<dyn Write> = if let Some(path) = output_path {
-            Box::new(File::create(path)?)
+    /// Writes results to `output_path`, or stdout when not given, and reports
+    /// progress as set in `progress`. With `append`, results are added to the
+    /// end of an existing output file instead of replacing it.
+    pub fn new(
+        output_path: Option<&Path>,
+        append: bool,
+        color: ColorMode,
+        progress: ProgressOptions,
+    ) -> Result<Self> {
+        let (writer, writer_is_terminal): (Box<dyn Write>, bool) = if let Some(path) = output_path {
+            let file = if append {
+                File::options().create(true).append(true).open(path)?
+            } else {
+                File::create(path)?
+            };
+            (Box::new(file), false)
         } else {
-            Box::new(io::stdout())
+            (Box::new(io::stdout()), io::stdout().is_terminal())
+        };
+
+        let color = match color {
+            ColorMode::Auto => writer_is_terminal && env::var_os("NO_COLOR").is_none(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
         };
 
+        let mode = match (progress.mode, progress.format) {
+            (ProgressMode::Off, _) => ProgressMode::Off,
+            (_, ProgressFormat::Json) => ProgressMode::Lines,
+            // Carriage-return redraws turn into noise in redirected output
+            (ProgressMode::Auto, _) if io::stderr().is_terminal() => ProgressMode::Bar,
+            (ProgressMode::Auto, _) => ProgressMode::Lines,
+            (mode, _) => mode,
+        };
+        let interval = match progress.format {
+            ProgressFormat::Text => LINE_INTERVAL,
+            ProgressFormat::Json => JSON_INTERVAL,
+        };
+        let next_line =
+            (mode == ProgressMode::Lines).then(|| (Instant::now() + interval, interval));
+        let bar = match mode {
+            ProgressMode::Bar => ProgressBar::with_draw_target(
+                None,
+                ProgressDrawTarget::stderr_with_hz(UPDATES_PER_SECOND),
+            ),
+            _ => ProgressBar::hidden(),
+        };
+        bar.set_style(unknown_total_style());
+
         Ok(Self {
             writer,
+            writer_is_terminal,
+            color,
+            format: Box::new(TextFormat),
+            progress: bar,
+            progress_format: progress.format,
+            progress_writer: progress.writer.unwrap_or_else(|| Box::new(io::stderr())),
+            next_line,
+            show_summary: progress.show_summary,
+            current_path: None,
+            snapshot: None,
             start_time: Instant::now(),
-            next_report: Instant::now(),
-            processed_files: 0,
-            processed_bytes: 0,
-            recent_updates: VecDeque::new(),
+            counts: Counts::default(),
+            pending_results: BTreeMap::new(),
+            next_result_index: 0,
+            stats: None,
+            errors: 0,
+            summary_json: None,
+            error_log: None,
+            path_rewrite: PathRewrite::default(),
         })
     }
 
+    /// Writes the header line of a verification, which is not counted as a
+    /// file.
+    pub fn write_result_header(&mut self, header: &str) -> Result<()> {
+        self.write_raw(format!("{}\n", header).as_bytes())
+    }
+
     pub fn write_result(&mut self, result: &str) -> Result<()> {
-        writeln!(self.writer, "{}", result)?;
-        self.processed_files += 1;
-        self.update_progress()?;
+        self.write_raw(format!("{}\n", result).as_bytes())?;
+        self.counts.processed_files += 1;
+        self.update_progress();
         Ok(())
     }
 
-    pub fn update_bytes(&mut self, bytes: u64) -> Result<()> {
-        self.processed_bytes += bytes;
-        let now = Instant::now();
+    /// Returns `text` wrapped in the terminal color for `highlight` when
+    /// coloring is enabled.
+    pub fn highlight(&self, text: &str, highlight: Highlight) -> String {
+        if !self.color {
+            return text.to_string();
+        }
+        let code = match highlight {
+            Highlight::Success => "32",
+            Highlight::Failure => "31",
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
 
-        self.recent_updates.push_back((now, self.processed_bytes));
+    /// Writes the hashing results in `format` instead of the default text
+    /// format.
+    pub fn set_format(&mut self, format: Box<dyn OutputFormat>) {
+        self.format = format;
+    }
 
-        // Remove outdated entries
-        while let Some((time, _)) = self.recent_updates.front() {
-            if now.duration_since(*time) > THROUGHPUT_WINDOW {
-                self.recent_updates.pop_front();
-            } else {
-                break;
-            }
+    /// Records the time taken by each file written from now on, and writes
+    /// them out when the run finishes.
+    pub fn set_stats(&mut self, stats: StatsRecorder) {
+        self.stats = Some(stats);
+    }
+
+    /// Writes a JSON summary of the run to `path`, or to stderr for `-`, when
+    /// it finishes, with the time spent in each algorithm from `timings`.
+    pub fn set_summary_json(&mut self, path: PathBuf, timings: AlgorithmTimings) {
+        self.summary_json = Some((path, timings));
+    }
+
+    /// Writes the errors of the files that fail from now on to a new file at
+    /// `path`, one JSON object per line, instead of reporting them on stderr.
+    pub fn set_error_log(&mut self, path: &Path) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create error file: {}", path.display()))?;
+        self.error_log = Some(file);
+        Ok(())
+    }
+
+    /// Writes why `path` failed to the error file set with `set_error_log`,
+    /// as an object with its path, the kind of failure, the byte it failed
+    /// at for read errors, and the message. Returns `false` when there is no
+    /// error file, for the caller to report it on stderr instead.
+    pub fn log_error(&mut self, path: &Path, error: &(dyn Error + 'static)) -> Result<bool> {
+        let Some(log) = &mut self.error_log else {
+            return Ok(false);
+        };
+        let causes = std::iter::successors(Some(error), |&e| e.source());
+        let hash_error = causes.clone().find_map(|e| e.downcast_ref::<HashError>());
+        let mut entry = serde_json::json!({
+            "path": path.to_string_lossy(),
+            "kind": hash_error.map_or("other", HashError::kind),
+        });
+        if let Some(HashError::ReadError { offset, .. }) = hash_error {
+            entry["offset"] = (*offset).into();
         }
+        entry["error"] = causes
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join(": ")
+            .into();
+        writeln!(log, "{}", entry).map_err(HashError::OutputError)?;
+        Ok(true)
+    }
 
-        self.update_progress()?;
+    /// Rewrites the paths of the results written from now on with
+    /// `path_rewrite`, e.g. to make them relative to a base directory.
+    pub fn set_path_rewrite(&mut self, path_rewrite: PathRewrite) {
+        self.path_rewrite = path_rewrite;
+    }
+
+    /// Returns how result paths are rewritten.
+    pub fn path_rewrite(&self) -> &PathRewrite {
+        &self.path_rewrite
+    }
+
+    /// Counts a file whose result is left out of the output.
+    pub fn count_file(&mut self) {
+        self.counts.processed_files += 1;
+        self.update_progress();
+    }
+
+    /// Counts a file that failed without a result to write.
+    pub fn count_error(&mut self) {
+        self.errors += 1;
+    }
+
+    /// Writes a comment line, in formats that have them.
+    pub fn write_comment(&mut self, text: &str) -> Result<()> {
+        let mut buffer = Vec::new();
+        self.format.write_comment(&mut buffer, text)?;
+        self.write_raw(&buffer)
+    }
+
+    /// Writes the header of the hashing results, naming the algorithms.
+    pub fn write_header(&mut self, algorithms: &[HashAlgorithm]) -> Result<()> {
+        let mut buffer = Vec::new();
+        self.format.write_header(&mut buffer, algorithms)?;
+        self.write_raw(&buffer)
+    }
+
+    /// Writes the results of one file, followed by those of its archive
+    /// members if any.
+    pub fn write_entries(
+        &mut self,
+        results: &[FileHashResult],
+        algorithms: &[HashAlgorithm],
+    ) -> Result<()> {
+        let mut buffer = Vec::new();
+        for result in results {
+            self.format.write_entry(&mut buffer, result, algorithms)?;
+            if let Some(stats) = &mut self.stats {
+                stats.record(result);
+            }
+            if let Some(error) = &result.error {
+                self.errors += 1;
+                // Only missing files have a result line, see format_result
+                if !self.log_error(&result.path, error)? && !matches!(error, HashError::NotFound(_))
+                {
+                    let causes = std::iter::successors(Some(error as &dyn Error), |&e| e.source());
+                    let causes = causes.map(|e| e.to_string()).collect::<Vec<_>>();
+                    eprintln!(
+                        "Error processing file {}: {}",
+                        result.path.display(),
+                        causes.join(": ")
+                    );
+                }
+            }
+        }
+        self.write_raw(&buffer)?;
+        self.counts.processed_files += 1;
+        self.update_progress();
         Ok(())
     }
 
-    fn calculate_current_throughput(&self) -> f64 {
-        if self.recent_updates.len() < 2 {
-            return 0.0;
+    /// Writes the results with the given traversal index once every result
+    /// before it has been written, buffering them until then. No results
+    /// mark an index that produces no output (e.g. a failed file) so the
+    /// results after it are not held back.
+    pub fn write_entries_at(
+        &mut self,
+        index: usize,
+        results: Vec<FileHashResult>,
+        algorithms: &[HashAlgorithm],
+    ) -> Result<()> {
+        self.pending_results.insert(index, results);
+        while let Some(results) = self.pending_results.remove(&self.next_result_index) {
+            if !results.is_empty() {
+                self.write_entries(&results, algorithms)?;
+            }
+            self.next_result_index += 1;
         }
-        let (start_time, start_bytes) = self.recent_updates.front().unwrap();
-        let (end_time, end_bytes) = self.recent_updates.back().unwrap();
-        let duration = end_time.duration_since(*start_time).as_secs_f64();
-        if duration > 0.0 {
-            let bytes_processed = end_bytes - start_bytes;
-            bytes_processed as f64 / duration / FKIB
+        Ok(())
+    }
+
+    /// Writes formatted output, clearing the progress bar meanwhile when
+    /// both share the terminal.
+    fn write_raw(&mut self, output: &[u8]) -> Result<()> {
+        let written = if self.writer_is_terminal {
+            self.progress.suspend(|| self.writer.write_all(output))
         } else {
-            0.0
-        }
-    }
-
-    fn update_progress(&mut self) -> Result<()> {
-        let now = Instant::now();
-        if now >= self.next_report {
-            let elapsed = self.start_time.elapsed();
-            let avg_speed = self.processed_bytes as f64 / elapsed.as_secs_f64() / FKIB;
-            let current_speed = self.calculate_current_throughput();
-            eprint!(
-                "\rProcessed: {} files, {}, Avg: {:.2} MiB/s, Current: {:.2} MiB/s        ",
-                self.processed_files,
-                format_bytes(self.processed_bytes),
-                avg_speed,
-                current_speed
-            );
-            io::stderr().flush()?;
-            self.next_report = now + UPDATE_INTERVAL;
+            self.writer.write_all(output)
+        };
+        written.map_err(HashError::OutputError)?;
+        Ok(())
+    }
+
+    /// Prints a progress snapshot to stderr whenever the process receives
+    /// SIGUSR1, even when progress reports are turned off. The snapshot is
+    /// printed by a thread of its own, so it comes right away even while a
+    /// read is stalled, e.g. on a hung network filesystem.
+    #[cfg(unix)]
+    pub fn snapshot_on_sigusr1(&mut self) -> Result<()> {
+        let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGUSR1])?;
+        let snapshot = Arc::new(Mutex::new(Snapshot {
+            counts: self.counts.clone(),
+            current_path: self.current_path.clone(),
+        }));
+        self.snapshot = Some(Arc::clone(&snapshot));
+        let progress = self.progress.clone();
+        let start_time = self.start_time;
+        std::thread::spawn(move || {
+            for _ in signals.forever() {
+                let snapshot = snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+                let mut text = snapshot.counts.progress_text(start_time);
+                if let Some(path) = &snapshot.current_path {
+                    text = format!("{}, current file: {}", text, path.display());
+                }
+                progress.suspend(|| eprintln!("{}", text));
+            }
+        });
+        Ok(())
+    }
+
+    /// Records the file that is being hashed, for the progress reports.
+    pub fn start_file(&mut self, path: &Path) {
+        self.current_path = Some(path.to_path_buf());
+        if let Some(snapshot) = &self.snapshot {
+            let mut snapshot = snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+            snapshot.current_path = self.current_path.clone();
         }
+    }
+
+    /// Adds to the number and total size of the files found so far, shown as
+    /// the totals in the progress line.
+    pub fn add_discovered(&mut self, files: usize, bytes: u64) -> Result<()> {
+        self.counts.discovered_files += files;
+        self.counts.discovered_bytes += bytes;
+        self.update_progress();
+        Ok(())
+    }
+
+    /// Marks the discovered totals as final, which switches the progress line
+    /// to a bar with the percentage and the estimated time left.
+    pub fn finish_discovery(&mut self) {
+        self.counts.discovery_finished = true;
+        self.progress.set_style(known_total_style());
+        self.update_progress();
+    }
+
+    pub fn update_bytes(&mut self, bytes: u64) -> Result<()> {
+        self.counts.processed_bytes += bytes;
+        self.update_progress();
         Ok(())
     }
 
+    /// Hands the current counts to the progress bar, which redraws itself at
+    /// most `UPDATES_PER_SECOND` times per second.
+    fn update_progress(&mut self) {
+        if self.counts.discovery_finished {
+            // Files can grow while they are hashed
+            self.progress.set_length(
+                self.counts
+                    .discovered_bytes
+                    .max(self.counts.processed_bytes),
+            );
+        }
+        self.progress.set_position(self.counts.processed_bytes);
+        self.progress.set_message(self.counts.file_count());
+
+        if let Some(snapshot) = &self.snapshot {
+            snapshot
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .counts = self.counts.clone();
+        }
+
+        if let Some((next_line, interval)) = self.next_line {
+            let now = Instant::now();
+            if now >= next_line {
+                self.write_progress_line(false);
+                self.next_line = Some((now + interval, interval));
+            }
+        }
+    }
+
+    /// Writes a progress report in the selected format. Failing to write it
+    /// is not worth interrupting the hashing for.
+    fn write_progress_line(&mut self, done: bool) {
+        let line = match self.progress_format {
+            ProgressFormat::Text => self.counts.progress_text(self.start_time),
+            ProgressFormat::Json => self.progress_json(done),
+        };
+        let _ = writeln!(self.progress_writer, "{}", line);
+        let _ = self.progress_writer.flush();
+    }
+
+    /// A progress event for other programs. Totals are `null` until the scan
+    /// has finished.
+    fn progress_json(&self, done: bool) -> String {
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        let totals_known = self.counts.discovery_finished;
+        serde_json::json!({
+            "files_done": self.counts.processed_files,
+            "files_total": totals_known.then_some(self.counts.discovered_files),
+            "bytes_done": self.counts.processed_bytes,
+            "bytes_total": totals_known.then(|| self.counts.discovered_bytes.max(self.counts.processed_bytes)),
+            "bytes_per_second": if elapsed > 0.0 { self.counts.processed_bytes as f64 / elapsed } else { 0.0 },
+            "elapsed_seconds": elapsed,
+            "current_path": self.current_path.as_ref().map(|path| path.display().to_string()),
+            "done": done,
+        })
+        .to_string()
+    }
+
+    /// Writes the summary and stats and clears the progress line.
     pub fn finish(&mut self) -> Result<()> {
+        self.progress.finish();
+        if !self.progress.is_hidden() {
+            // Keep the final state of the bar on its own line
+            eprintln!();
+        }
+        if self.next_line.is_some() && self.progress_format == ProgressFormat::Json {
+            self.write_progress_line(true);
+        }
         let elapsed = self.start_time.elapsed();
-        let speed = self.processed_bytes as f64 / elapsed.as_secs_f64() / FKIB;
-        let formatted_bytes = format_bytes(self.processed_bytes);
+        let summary = Summary {
+            files: self.counts.processed_files,
+            bytes: self.counts.processed_bytes,
+            errors: self.errors,
+            elapsed,
+        };
+        let mut buffer = Vec::new();
+        self.format.write_summary(&mut buffer, &summary)?;
+        self.write_raw(&buffer)?;
+        if let Some(stats) = &self.stats {
+            stats.write()?;
+        }
+        if let Some((path, timings)) = &self.summary_json {
+            write_summary_json(path, &summary, timings)?;
+        }
+        if !self.show_summary {
+            return Ok(());
+        }
+        let speed = self.counts.processed_bytes as f64 / elapsed.as_secs_f64() / FKIB;
+        let formatted_bytes = format_bytes(self.counts.processed_bytes);
         eprintln!(
-            "\nFinished: {} files processed, {:.2} MiB/s, total time: {}, total bytes: {}",
-            self.processed_files,
+            "Finished: {} files processed, {:.2} MiB/s, total time: {}, total bytes: {}",
+            self.counts.processed_files,
             speed,
             format_duration(elapsed),
             formatted_bytes
@@ -112,7 +616,23 @@ impl OutputManager {
     }
 }
 
-fn format_bytes(bytes: u64) -> String {
+/// Progress line while the total size is not known yet.
+fn unknown_total_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner} {binary_bytes} ({binary_bytes_per_sec}), {msg}")
+        .expect("valid progress template")
+}
+
+/// Progress bar once the total size is known. The bar shrinks to fit narrow
+/// terminals.
+fn known_total_style() -> ProgressStyle {
+    ProgressStyle::with_template(
+        "{percent:>3}% [{wide_bar}] {binary_bytes}/{binary_total_bytes} ({binary_bytes_per_sec}, ETA {eta}), {msg}",
+    )
+    .expect("valid progress template")
+    .progress_chars("=> ")
+}
+
+pub fn format_bytes(bytes: u64) -> String {
     const KIB: u64 = 1024;
     const MIB: u64 = KIB * 1024;
     const GIB: u64 = MIB * 1024;
@@ -0,0 +1,427 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use clap::ValueEnum;
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::hash_algorithms::HashAlgorithm;
+use crate::output::{format_digests, format_result};
+use crate::path_escape::result_line;
+use crate::results::FileHashResult;
+use crate::utils::HashError;
+
+/// Writes hashing results in a given format. `OutputManager` calls it with
+/// the destination of the results, so other crates can add formats by
+/// implementing it and passing it to `OutputManager::set_format`.
+pub trait OutputFormat {
+    /// Writes what comes before the results, when headers are requested.
+    fn write_header(
+        &mut self,
+        writer: &mut dyn Write,
+        algorithms: &[HashAlgorithm],
+    ) -> io::Result<()>;
+
+    /// Writes the result of one file or archive member. `algorithms` are the
+    /// columns of the output, also for results without digests.
+    fn write_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        result: &FileHashResult,
+        algorithms: &[HashAlgorithm],
+    ) -> io::Result<()>;
+
+    /// Writes what comes after the results, once hashing has finished.
+    fn write_summary(&mut self, writer: &mut dyn Write, summary: &Summary) -> io::Result<()>;
+
+    /// Writes a line of `text` that readers of the results skip. Formats
+    /// without comments leave it out.
+    fn write_comment(&mut self, _writer: &mut dyn Write, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Describes a run, for the comments written above the header of a
+/// manifest: the version of parallelhash, the date, the host, the input
+/// paths, the algorithms and the command line.
+pub fn run_metadata(paths: &[PathBuf], algorithms: &[HashAlgorithm]) -> Vec<String> {
+    let mut lines = vec![
+        format!("parallelhash {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "date: {}",
+            humantime::format_rfc3339_seconds(SystemTime::now())
+        ),
+    ];
+    if let Some(host) = hostname() {
+        lines.push(format!("host: {}", host));
+    }
+    lines.extend(paths.iter().map(|path| format!("root: {}", path.display())));
+    let names = algorithms
+        .iter()
+        .map(|algo| algo.to_string())
+        .collect::<Vec<_>>();
+    lines.push(format!("algorithms: {}", names.join(" ")));
+    let command = env::args()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("'{}'", arg)
+            } else {
+                arg
+            }
+        })
+        .collect::<Vec<_>>();
+    lines.push(format!("command: {}", command.join(" ")));
+    lines
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut name = [0u8; 256];
+    // SAFETY: the buffer is valid for its whole length
+    if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } != 0 {
+        return None;
+    }
+    let len = name.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&name[..len]).into_owned())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    env::var("COMPUTERNAME").ok()
+}
+
+/// Totals of a finished run.
+#[derive(Clone, Copy, Debug)]
+pub struct Summary {
+    pub files: usize,
+    pub bytes: u64,
+    /// Files that could not be hashed.
+    pub errors: usize,
+    pub elapsed: Duration,
+}
+
+/// Built-in formats of the hashing results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ResultFormat {
+    /// Digests followed by the path, one file per line
+    #[default]
+    Text,
+    /// One JSON object per file, and a last one with the totals
+    Json,
+    /// Comma-separated values with the path, size and digests
+    Csv,
+    /// HTTP `Digest` and `Content-MD5` header values followed by the path
+    Digest,
+    /// `SHA256(path)= digest` lines, as written by `openssl dgst`
+    Openssl,
+    /// The digest and the path, or the path and its digests, as rhash writes
+    Rhash,
+}
+
+impl ResultFormat {
+    pub fn formatter(self) -> Box<dyn OutputFormat> {
+        match self {
+            ResultFormat::Text => Box::new(TextFormat),
+            ResultFormat::Json => Box::new(JsonFormat),
+            ResultFormat::Csv => Box::new(CsvFormat),
+            ResultFormat::Digest => Box::new(DigestHeaderFormat),
+            ResultFormat::Openssl => Box::new(OpensslFormat),
+            ResultFormat::Rhash => Box::new(RhashFormat),
+        }
+    }
+}
+
+/// The format read back by `--check`, `--resume` and `--fuzzy-match`.
+pub struct TextFormat;
+
+impl OutputFormat for TextFormat {
+    fn write_header(
+        &mut self,
+        writer: &mut dyn Write,
+        algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        let names = algorithms
+            .iter()
+            .map(|algo| algo.to_string())
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}  path", names.join("  "))
+    }
+
+    fn write_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        result: &FileHashResult,
+        algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        match format_result(result, algorithms) {
+            Some(line) => writeln!(writer, "{}", line),
+            None => Ok(()),
+        }
+    }
+
+    /// The summary goes to stderr instead, keeping the output a plain list.
+    fn write_summary(&mut self, _writer: &mut dyn Write, _summary: &Summary) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_comment(&mut self, writer: &mut dyn Write, text: &str) -> io::Result<()> {
+        writeln!(writer, "# {}", text)
+    }
+}
+
+/// JSON Lines: an object per file with its path, size and digests, or why
+/// it was not hashed, then an object with the totals under `summary`.
+pub struct JsonFormat;
+
+impl OutputFormat for JsonFormat {
+    /// Every object names its fields, so there is nothing to add.
+    fn write_header(
+        &mut self,
+        _writer: &mut dyn Write,
+        _algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        result: &FileHashResult,
+        _algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        let mut entry = serde_json::json!({
+            "path": result.path.to_string_lossy(),
+        });
+        match (&result.error, result.note) {
+            (Some(HashError::NotFound(e)), _) => {
+                entry["error"] = format!("File not found: {}", e).into();
+            }
+            (Some(_), _) => return Ok(()),
+            (None, Some(note)) => entry["note"] = note.into(),
+            (None, None) => {
+                entry["size"] = result.size.into();
+                let digests = result
+                    .digests
+                    .iter()
+                    .map(|(algorithm, _)| algorithm.to_string())
+                    .zip(format_digests(&result.digests).into_iter().map(Into::into))
+                    .collect::<serde_json::Map<_, _>>();
+                entry["hashes"] = digests.into();
+            }
+        }
+        writeln!(writer, "{}", entry)
+    }
+
+    fn write_summary(&mut self, writer: &mut dyn Write, summary: &Summary) -> io::Result<()> {
+        let summary = serde_json::json!({
+            "summary": {
+                "files": summary.files,
+                "bytes": summary.bytes,
+                "elapsed_seconds": summary.elapsed.as_secs_f64(),
+            }
+        });
+        writeln!(writer, "{}", summary)
+    }
+}
+
+/// Comma-separated values with a `path,size,<algorithm>...,note` row per
+/// file, quoted where needed. The note says why a file was not hashed.
+pub struct CsvFormat;
+
+impl OutputFormat for CsvFormat {
+    fn write_header(
+        &mut self,
+        writer: &mut dyn Write,
+        algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        let mut row = vec!["path".to_string(), "size".to_string()];
+        row.extend(algorithms.iter().map(|algo| algo.to_string()));
+        row.push("note".to_string());
+        write_csv_row(writer, &row)
+    }
+
+    fn write_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        result: &FileHashResult,
+        algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        let (size, digests, note) = match (&result.error, result.note) {
+            (Some(HashError::NotFound(e)), _) => (
+                String::new(),
+                vec![String::new(); algorithms.len()],
+                format!("File not found: {}", e),
+            ),
+            (Some(_), _) => return Ok(()),
+            (None, Some(note)) => (
+                String::new(),
+                vec![String::new(); algorithms.len()],
+                note.to_string(),
+            ),
+            (None, None) => (
+                result.size.to_string(),
+                format_digests(&result.digests),
+                String::new(),
+            ),
+        };
+        let mut row = vec![result.path.to_string_lossy().into_owned(), size];
+        row.extend(digests);
+        row.push(note);
+        write_csv_row(writer, &row)
+    }
+
+    fn write_summary(&mut self, _writer: &mut dyn Write, _summary: &Summary) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The values of HTTP integrity headers for each file, followed by the path
+/// as in the text format: a `Content-MD5` line (RFC 1864) when MD5 is among
+/// the algorithms, then a `Digest` line (RFC 3230) with the base64 digests of
+/// the algorithms it has names for, e.g.
+/// `Digest: MD5=1B2M2Y8AsgTpgAmY7PhCfg==,SHA-256=47DEQpj8...=  path`.
+/// Other algorithms are left out, and files that were not hashed too.
+pub struct DigestHeaderFormat;
+
+/// The name of `algorithm` in the IANA registry of HTTP `Digest` algorithms,
+/// if it has one.
+pub fn digest_name(algorithm: &HashAlgorithm) -> Option<&'static str> {
+    match algorithm {
+        HashAlgorithm::Md5(_) => Some("MD5"),
+        HashAlgorithm::Sha1(_) => Some("SHA"),
+        HashAlgorithm::Sha256(_) => Some("SHA-256"),
+        HashAlgorithm::Sha512(_) => Some("SHA-512"),
+        _ => None,
+    }
+}
+
+impl OutputFormat for DigestHeaderFormat {
+    /// Every line names its header, so there is nothing to add.
+    fn write_header(
+        &mut self,
+        _writer: &mut dyn Write,
+        _algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        result: &FileHashResult,
+        _algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        if result.error.is_some() || result.note.is_some() {
+            return Ok(());
+        }
+        let mut values = Vec::new();
+        for (algorithm, digest) in &result.digests {
+            if let HashAlgorithm::Md5(_) = algorithm {
+                let header = format!("Content-MD5: {}", BASE64.encode(digest));
+                writeln!(writer, "{}", result_line(&header, &result.path))?;
+            }
+            if let Some(name) = digest_name(algorithm) {
+                values.push(format!("{}={}", name, BASE64.encode(digest)));
+            }
+        }
+        if !values.is_empty() {
+            let header = format!("Digest: {}", values.join(","));
+            writeln!(writer, "{}", result_line(&header, &result.path))?;
+        }
+        Ok(())
+    }
+
+    fn write_summary(&mut self, _writer: &mut dyn Write, _summary: &Summary) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The output of `openssl dgst`: an `ALGORITHM(path)= digest` line for each
+/// algorithm of each file, with the algorithm names of OpenSSL 1.x, e.g.
+/// `SHA256` where OpenSSL 3 writes `SHA2-256`. Files that were not hashed are
+/// left out, as openssl only reports them on stderr.
+pub struct OpensslFormat;
+
+impl OutputFormat for OpensslFormat {
+    /// openssl writes no header.
+    fn write_header(
+        &mut self,
+        _writer: &mut dyn Write,
+        _algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        result: &FileHashResult,
+        _algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        if result.error.is_some() || result.note.is_some() {
+            return Ok(());
+        }
+        let path = result.path.to_string_lossy();
+        for ((algorithm, _), digest) in result.digests.iter().zip(format_digests(&result.digests)) {
+            let name = algorithm.to_string().replace("SHA2-", "SHA");
+            writeln!(writer, "{}({})= {}", name, path, digest)?;
+        }
+        Ok(())
+    }
+
+    fn write_summary(&mut self, _writer: &mut dyn Write, _summary: &Summary) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What rhash writes: `digest  path` lines like md5sum with a single
+/// algorithm, and `path digest...` lines like SFV files with several, without
+/// a header. Files that were not hashed are left out.
+pub struct RhashFormat;
+
+impl OutputFormat for RhashFormat {
+    /// rhash tells the algorithms apart by the length of the digests instead.
+    fn write_header(
+        &mut self,
+        _writer: &mut dyn Write,
+        _algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_entry(
+        &mut self,
+        writer: &mut dyn Write,
+        result: &FileHashResult,
+        _algorithms: &[HashAlgorithm],
+    ) -> io::Result<()> {
+        if result.error.is_some() || result.note.is_some() {
+            return Ok(());
+        }
+        let path = result.path.to_string_lossy();
+        match format_digests(&result.digests).as_slice() {
+            [digest] => writeln!(writer, "{}  {}", digest, path),
+            digests => writeln!(writer, "{} {}", path, digests.join(" ")),
+        }
+    }
+
+    fn write_summary(&mut self, _writer: &mut dyn Write, _summary: &Summary) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes a row, quoting the fields that hold commas, quotes or line breaks.
+pub(crate) fn write_csv_row(writer: &mut dyn Write, fields: &[String]) -> io::Result<()> {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    writeln!(writer, "{}", fields.join(","))
+}
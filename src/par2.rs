@@ -0,0 +1,338 @@
+use anyhow::{anyhow, bail, Context, Result};
+use digest::Digest;
+use md5::Md5;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use crate::output::Highlight;
+use crate::path_escape::result_line;
+use crate::OutputManager;
+
+const MAGIC: &[u8; 8] = b"PAR2\0PKT";
+const MAIN: &[u8; 16] = b"PAR 2.0\0Main\0\0\0\0";
+const FILE_DESCRIPTION: &[u8; 16] = b"PAR 2.0\0FileDesc";
+const SLICE_CHECKSUMS: &[u8; 16] = b"PAR 2.0\0IFSC\0\0\0\0";
+const RECOVERY_SLICE: &[u8; 16] = b"PAR 2.0\0RecvSlic";
+/// Size of the packet header: magic, length, packet hash, set and type.
+const HEADER_SIZE: usize = 64;
+/// Largest packet read, far above what the critical packets of a set with
+/// the most files and slices PAR2 allows take.
+const MAX_PACKET_SIZE: usize = 64 * 1024 * 1024;
+/// Number of bytes read at a time while looking for the next packet.
+const SCAN_SIZE: usize = 64 * 1024;
+/// Largest slice size accepted, as a slice is held in memory while it is
+/// hashed. Larger ones come from damaged or hostile files.
+const MAX_SLICE_SIZE: u64 = 1 << 30;
+/// The beginning of files has its own hash, to find them when renamed.
+const HASH_16K: u64 = 16 * 1024;
+
+/// A file of a PAR2 recovery set, from its description packet.
+struct Par2File {
+    name: String,
+    length: u64,
+    hash: [u8; 16],
+    hash_16k: [u8; 16],
+    /// MD5 of each slice, from the slice checksum packet.
+    slices: Vec<[u8; 16]>,
+}
+
+/// The packets of a PAR2 file needed to verify the data files.
+struct RecoverySet {
+    slice_size: u64,
+    /// Files in the order of the main packet, recoverable ones first.
+    order: Vec<[u8; 16]>,
+    files: BTreeMap<[u8; 16], Par2File>,
+}
+
+/// Reads a PAR2 file packet by packet, holding only the packet being read
+/// in memory.
+struct PacketReader<R> {
+    reader: BufReader<R>,
+    /// Bytes read but not yet consumed, from the start of a packet or of
+    /// the data searched for one.
+    buf: Vec<u8>,
+}
+
+impl<R: Read + Seek> PacketReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Reads until `buf` holds `len` bytes, and tells whether it does, which
+    /// it does not at the end of the file.
+    fn fill(&mut self, len: usize) -> io::Result<bool> {
+        if let Some(missing) = len.checked_sub(self.buf.len()) {
+            (&mut self.reader)
+                .take(missing as u64)
+                .read_to_end(&mut self.buf)?;
+        }
+        Ok(self.buf.len() >= len)
+    }
+
+    /// Drops the data before the next packet magic, and tells whether one
+    /// was found.
+    fn find_magic(&mut self) -> io::Result<bool> {
+        loop {
+            if let Some(start) = self.buf.windows(MAGIC.len()).position(|w| w == MAGIC) {
+                self.buf.drain(..start);
+                return Ok(true);
+            }
+            // The start of a magic may end the data read so far
+            let keep = self.buf.len().min(MAGIC.len() - 1);
+            self.buf.drain(..self.buf.len() - keep);
+            self.fill(keep + SCAN_SIZE)?;
+            if self.buf.len() == keep {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Drops the first `len` bytes of the packet, seeking past those not
+    /// read yet.
+    fn skip(&mut self, len: u64) -> io::Result<()> {
+        let buffered = self
+            .buf
+            .len()
+            .min(usize::try_from(len).unwrap_or(usize::MAX));
+        self.buf.drain(..buffered);
+        let rest = len - buffered as u64;
+        if rest > 0 {
+            let rest = i64::try_from(rest)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Packet too long"))?;
+            self.reader.seek_relative(rest)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the packets of a PAR2 file. Damaged packets, whose hash does not
+/// match, are skipped, as every file of a set repeats the critical ones.
+/// Recovery slices are not needed to verify the data files, so their packets
+/// are seeked past unread.
+fn read_recovery_set(data: impl Read + Seek) -> Result<RecoverySet> {
+    let mut main = None;
+    let mut descriptions = BTreeMap::new();
+    let mut checksums = BTreeMap::new();
+    let mut set_id = None;
+    let mut packets = PacketReader::new(data);
+    while packets.find_magic()? {
+        if !packets.fill(HEADER_SIZE)? {
+            break;
+        }
+        let header = &packets.buf[..HEADER_SIZE];
+        let length = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        if length < HEADER_SIZE as u64 || length % 4 != 0 {
+            packets.skip(MAGIC.len() as u64)?;
+            continue;
+        }
+        if &header[48..64] == RECOVERY_SLICE {
+            packets.skip(length)?;
+            continue;
+        }
+        let length = usize::try_from(length)
+            .ok()
+            .filter(|&length| length <= MAX_PACKET_SIZE);
+        let Some(length) = length else {
+            packets.skip(MAGIC.len() as u64)?;
+            continue;
+        };
+        if !packets.fill(length)?
+            || Md5::digest(&packets.buf[32..length]).as_slice() != &packets.buf[16..32]
+        {
+            packets.skip(MAGIC.len() as u64)?;
+            continue;
+        }
+        let packet: Vec<u8> = packets.buf.drain(..length).collect();
+        let id = <[u8; 16]>::try_from(&packet[32..48]).unwrap();
+        if *set_id.get_or_insert(id) != id {
+            continue;
+        }
+        let body = &packet[HEADER_SIZE..];
+        match &packet[48..64] {
+            kind if kind == MAIN && body.len() >= 12 => {
+                let slice_size = u64::from_le_bytes(body[..8].try_into().unwrap());
+                let ids = body[12..]
+                    .chunks_exact(16)
+                    .map(|id| <[u8; 16]>::try_from(id).unwrap())
+                    .collect::<Vec<_>>();
+                main = Some((slice_size, ids));
+            }
+            kind if kind == FILE_DESCRIPTION && body.len() >= 56 => {
+                let name = &body[56..];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+                descriptions.insert(
+                    <[u8; 16]>::try_from(&body[..16]).unwrap(),
+                    Par2File {
+                        name: String::from_utf8_lossy(name).into_owned(),
+                        length: u64::from_le_bytes(body[48..56].try_into().unwrap()),
+                        hash: body[16..32].try_into().unwrap(),
+                        hash_16k: body[32..48].try_into().unwrap(),
+                        slices: Vec::new(),
+                    },
+                );
+            }
+            kind if kind == SLICE_CHECKSUMS && body.len() >= 16 => {
+                let slices = body[16..]
+                    .chunks_exact(20)
+                    .map(|entry| <[u8; 16]>::try_from(&entry[..16]).unwrap())
+                    .collect::<Vec<_>>();
+                checksums.insert(<[u8; 16]>::try_from(&body[..16]).unwrap(), slices);
+            }
+            _ => {}
+        }
+    }
+
+    let (slice_size, order) = main.ok_or_else(|| anyhow!("No main packet found"))?;
+    if slice_size == 0 || slice_size % 4 != 0 || slice_size > MAX_SLICE_SIZE {
+        bail!("Invalid slice size {}", slice_size);
+    }
+    for (id, slices) in checksums {
+        if let Some(file) = descriptions.get_mut(&id) {
+            file.slices = slices;
+        }
+    }
+    Ok(RecoverySet {
+        slice_size,
+        order,
+        files: descriptions,
+    })
+}
+
+/// Slices of a file, by how they verified.
+#[derive(Default)]
+struct SliceCounts {
+    complete: u64,
+    /// Slices with data missing, from a missing or truncated file.
+    incomplete: u64,
+    corrupt: u64,
+}
+
+/// Verifies the data files of the PAR2 recovery set of `par2`, found next to
+/// it, against the MD5 of each file, of its first 16 KiB and of each slice,
+/// and reports for each one how many of its slices are complete, incomplete
+/// (data missing) or corrupt: OK when the file is whole, INCOMPLETE when it
+/// is missing or too short, and FAILED when its contents differ. Files
+/// without slice checksums, outside the recoverable part of the set, only
+/// have the hash of the whole file to check. Nothing is repaired.
+pub fn verify_par2(
+    par2: &Path,
+    show_headers: bool,
+    output_manager: &mut OutputManager,
+) -> Result<()> {
+    let data = File::open(par2)
+        .with_context(|| format!("Failed to read PAR2 file: {}", par2.display()))?;
+    let set = read_recovery_set(data)
+        .with_context(|| format!("Invalid PAR2 file: {}", par2.display()))?;
+    let dir = par2.parent().unwrap_or(Path::new(""));
+
+    if show_headers {
+        output_manager.write_result_header("Result  complete  incomplete  corrupt  path")?;
+    }
+    for id in &set.order {
+        let file = set
+            .files
+            .get(id)
+            .ok_or_else(|| anyhow!("No description of a file of {}", par2.display()))?;
+        let path = dir.join(safe_path(&file.name)?);
+        output_manager.start_file(&path);
+        let (whole, counts) = verify_file(&path, file, set.slice_size, output_manager)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let status = if whole {
+            output_manager.highlight("OK", Highlight::Success)
+        } else if counts.corrupt > 0 || (counts.incomplete == 0 && path.is_file()) {
+            output_manager.highlight("FAILED", Highlight::Failure)
+        } else {
+            "INCOMPLETE".to_string()
+        };
+        let columns = format!(
+            "{}  {}  {}  {}",
+            status, counts.complete, counts.incomplete, counts.corrupt
+        );
+        output_manager.write_result(&result_line(&columns, &path))?;
+    }
+    output_manager.finish()?;
+    Ok(())
+}
+
+/// Checks that the name of a file of a recovery set stays in its directory.
+fn safe_path(name: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(name);
+    if name.is_empty()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!("Unsafe file name in PAR2 file: {:?}", name);
+    }
+    Ok(path)
+}
+
+/// Hashes a data file slice by slice, returning whether it matches its
+/// length and hashes, and how its slices verified. The last slice is hashed
+/// padded with zeros, as PAR2 does.
+fn verify_file(
+    path: &Path,
+    file: &Par2File,
+    slice_size: u64,
+    output_manager: &mut OutputManager,
+) -> Result<(bool, SliceCounts)> {
+    let mut counts = SliceCounts::default();
+    let slice_count = file.length.div_ceil(slice_size);
+    let mut handle = match File::open(path) {
+        Ok(handle) => handle,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if !file.slices.is_empty() {
+                counts.incomplete = slice_count;
+            }
+            return Ok((false, counts));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut hasher = Md5::new();
+    let mut hasher_16k = Md5::new();
+    let mut buf = vec![0; slice_size as usize];
+    let mut total = 0;
+    for slice in 0..slice_count {
+        let want = slice_size.min(file.length - slice * slice_size) as usize;
+        let mut read = 0;
+        while read < want {
+            match handle.read(&mut buf[read..want]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        output_manager.update_bytes(read as u64)?;
+        hasher.update(&buf[..read]);
+        if total < HASH_16K {
+            hasher_16k.update(&buf[..read.min((HASH_16K - total) as usize)]);
+        }
+        total += read as u64;
+        buf[read..].fill(0);
+        if let Some(expected) = file.slices.get(slice as usize) {
+            if read < want {
+                counts.incomplete += 1;
+            } else if Md5::digest(&buf).as_slice() == expected {
+                counts.complete += 1;
+            } else {
+                counts.corrupt += 1;
+            }
+        }
+        if read < want {
+            counts.incomplete += file.slices.len().saturating_sub(slice as usize + 1) as u64;
+            return Ok((false, counts));
+        }
+    }
+    // A longer file has other contents
+    let longer = handle.read(&mut [0])? > 0;
+    let whole = !longer
+        && hasher.finalize().as_slice() == file.hash
+        && hasher_16k.finalize().as_slice() == file.hash_16k;
+    Ok((whole, counts))
+}
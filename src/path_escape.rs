@@ -0,0 +1,96 @@
+use anyhow::{bail, Result};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use crate::utils::path_from_bytes;
+
+/// Starts a result line whose path is escaped, as in coreutils checksum
+/// files, so that lines without it are read exactly as before.
+pub const ESCAPE_MARKER: char = '\\';
+
+/// Builds a result line from its leading columns and the path. Paths that
+/// are not valid UTF-8 or contain a line break are escaped, and the line then
+/// starts with `ESCAPE_MARKER`.
+pub fn result_line(columns: &str, path: &Path) -> String {
+    match escape_path(path) {
+        Cow::Borrowed(path) => format!("{}  {}", columns, path),
+        Cow::Owned(path) => format!("{}{}  {}", ESCAPE_MARKER, columns, path),
+    }
+}
+
+/// Splits the escape marker off a result line, returning the rest of the line
+/// and whether its path is escaped.
+pub fn strip_marker(line: &str) -> (&str, bool) {
+    match line.strip_prefix(ESCAPE_MARKER) {
+        Some(line) => (line, true),
+        None => (line, false),
+    }
+}
+
+/// Reads back a path written by `result_line`.
+pub fn parse_path(path: &str, escaped: bool) -> Result<PathBuf> {
+    if escaped {
+        unescape_path(path)
+    } else {
+        Ok(PathBuf::from(path))
+    }
+}
+
+/// Returns `path` unchanged if it can be written as is, or escaped: with
+/// backslashes doubled, line breaks written as `\n` and `\r`, and bytes that
+/// are not valid UTF-8 written as `\xHH`. Lines holding an escaped path must
+/// start with `ESCAPE_MARKER`.
+pub fn escape_path(path: &Path) -> Cow<'_, str> {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    if let Ok(path) = std::str::from_utf8(bytes) {
+        if !path.contains(['\n', '\r']) {
+            return Cow::Borrowed(path);
+        }
+    }
+
+    let mut escaped = String::with_capacity(bytes.len() + 8);
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+fn unescape_path(escaped: &str) -> Result<PathBuf> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        let (byte, tail) = match rest {
+            [b'\\', tail @ ..] => (b'\\', tail),
+            [b'n', tail @ ..] => (b'\n', tail),
+            [b'r', tail @ ..] => (b'\r', tail),
+            [b'x', high, low, tail @ ..] => {
+                let hex = std::str::from_utf8(&[*high, *low])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => (byte, tail),
+                    None => bail!("Invalid escape sequence in path: {}", escaped),
+                }
+            }
+            _ => bail!("Invalid escape sequence in path: {}", escaped),
+        };
+        bytes.push(byte);
+        rest = tail;
+    }
+    Ok(path_from_bytes(&bytes))
+}
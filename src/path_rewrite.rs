@@ -0,0 +1,157 @@
+use std::path::{self, Component, Path, PathBuf};
+#[cfg(unix)]
+use std::{ffi::OsString, os::unix::ffi::OsStrExt, os::unix::ffi::OsStringExt};
+
+/// How the paths of results are rewritten when they are written, so that
+/// manifests do not depend on where the files were when they were hashed,
+/// and how the paths of a manifest are rewritten back when checking it.
+#[derive(Clone, Debug, Default)]
+pub struct PathRewrite {
+    /// Write paths relative to this directory.
+    pub relative_to: Option<PathBuf>,
+    /// Remove this leading part of the paths, after making them relative.
+    pub strip_prefix: Option<PathBuf>,
+    /// Then put this in front of them.
+    pub add_prefix: Option<PathBuf>,
+    /// Read the paths of a manifest made on another system with its path
+    /// separators, `\` on Windows and `/` elsewhere.
+    pub normalize_separators: bool,
+}
+
+impl PathRewrite {
+    /// Rewrites the path of a file as it is written to the output.
+    pub fn apply(&self, path: &Path) -> PathBuf {
+        let path = match &self.relative_to {
+            Some(base) => relative(path, base),
+            None => path.to_path_buf(),
+        };
+        replace_prefix(
+            path,
+            self.strip_prefix.as_deref(),
+            self.add_prefix.as_deref(),
+        )
+    }
+
+    /// Rewrites a path read from a manifest into the path of the file, undoing
+    /// `apply`.
+    pub fn reverse(&self, path: &Path) -> PathBuf {
+        let path = if self.normalize_separators {
+            normalize_separators(path)
+        } else {
+            path.to_path_buf()
+        };
+        let path = &replace_prefix(
+            path,
+            self.add_prefix.as_deref(),
+            self.strip_prefix.as_deref(),
+        );
+        match &self.relative_to {
+            Some(base) if path.is_relative() => {
+                let joined = base.join(path);
+                if path.components().any(|c| c == Component::ParentDir) {
+                    clean(&joined)
+                } else {
+                    joined
+                }
+            }
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+/// Converts the `\` of a path to `/`.
+#[cfg(unix)]
+fn normalize_separators(path: &Path) -> PathBuf {
+    let bytes = path
+        .as_os_str()
+        .as_bytes()
+        .iter()
+        .map(|&byte| if byte == b'\\' { b'/' } else { byte })
+        .collect();
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+/// Converts the `/` of a path to the native separator. Windows takes both
+/// already, but `/` would not match the prefixes to strip otherwise.
+#[cfg(not(unix))]
+fn normalize_separators(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// Replaces the leading components `old` of `path` with `new`. Paths that do
+/// not start with `old` are left alone, and without `old`, `new` only goes in
+/// front of relative paths.
+fn replace_prefix(path: PathBuf, old: Option<&Path>, new: Option<&Path>) -> PathBuf {
+    let rest = match old {
+        Some(old) => match path.strip_prefix(old) {
+            Ok(rest) => rest,
+            Err(_) => return path,
+        },
+        None if path.is_absolute() => return path,
+        None => &path,
+    };
+    match new {
+        Some(new) if rest.as_os_str().is_empty() => new.to_path_buf(),
+        Some(new) => new.join(rest),
+        None if rest.as_os_str().is_empty() => PathBuf::from("."),
+        None => rest.to_path_buf(),
+    }
+}
+
+/// Returns the path that leads to `path` from the directory `base`, e.g.
+/// `../b/file` for `/a/b/file` from `/a/c`. Paths on another drive than
+/// `base` are returned whole.
+fn relative(path: &Path, base: &Path) -> PathBuf {
+    let (path, base) = if path.is_absolute() == base.is_absolute() {
+        (clean(path), clean(base))
+    } else {
+        (clean(&absolute(path)), clean(&absolute(base)))
+    };
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let up = base.components().skip(common);
+    if up.clone().any(|c| c == Component::ParentDir) {
+        // Leaving a relative base upwards depends on the current directory
+        return relative(&absolute(&path), &absolute(&base));
+    }
+    if common == 0 && path.is_absolute() {
+        return path;
+    }
+    let relative = up
+        .map(|_| Component::ParentDir)
+        .chain(path.components().skip(common))
+        .collect::<PathBuf>();
+    if relative.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        relative
+    }
+}
+
+/// Resolves `.` and `..` components without following symlinks, so that
+/// paths given in different forms can be compared.
+fn clean(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match cleaned.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    cleaned.pop();
+                }
+                // The parent of the root is the root itself
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => cleaned.push(".."),
+            },
+            component => cleaned.push(component),
+        }
+    }
+    cleaned
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
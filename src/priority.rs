@@ -0,0 +1,50 @@
+use std::io;
+
+/// Lowers the CPU priority of the current process to the lowest and, where
+/// the platform has one, moves its I/O to the idle scheduling class, so that
+/// it only uses the disks and processors when nothing else wants them.
+///
+/// On Linux both priorities belong to each thread and are inherited by the
+/// threads it spawns, so this is called before any worker is started.
+pub fn enter_background() -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        // SAFETY: plain syscalls on the calling thread, with no pointers
+        unsafe {
+            if libc::setpriority(libc::PRIO_PROCESS, 0, 19) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            ) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        // SAFETY: plain syscall on the current process, with no pointers
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentProcess, SetPriorityClass, PROCESS_MODE_BACKGROUND_BEGIN,
+        };
+        // Background mode lowers both the CPU and the I/O priority
+        // SAFETY: the pseudo handle of the current process is always valid
+        if unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use crate::output::OutputManager;
+
+/// Receives the progress of a hashing run, so programs embedding the library
+/// can show it their own way. `OutputManager` implements it with the
+/// progress bar or lines on stderr.
+///
+/// Every method is called on the thread that runs the hashing, and files
+/// hashed concurrently have their events interleaved.
+pub trait ProgressSink {
+    /// A file worker has started hashing the file at `path`.
+    fn on_file_started(&mut self, path: &Path);
+
+    /// `bytes` more bytes have been read and hashed.
+    fn on_bytes(&mut self, bytes: u64);
+
+    /// The file at `path` has been hashed, or has failed to.
+    fn on_file_finished(&mut self, path: &Path);
+
+    /// The scan ahead of the hashing has found `files` more files, totalling
+    /// `bytes` bytes.
+    fn on_discovered(&mut self, _files: usize, _bytes: u64) {}
+
+    /// The scan has found every file, so the totals are final.
+    fn on_discovery_finished(&mut self) {}
+}
+
+impl ProgressSink for OutputManager {
+    fn on_file_started(&mut self, path: &Path) {
+        self.start_file(path);
+    }
+
+    fn on_bytes(&mut self, bytes: u64) {
+        let _ = self.update_bytes(bytes);
+    }
+
+    /// Files are counted as their results are written, so that the count
+    /// matches the output.
+    fn on_file_finished(&mut self, _path: &Path) {}
+
+    fn on_discovered(&mut self, files: usize, bytes: u64) {
+        let _ = self.add_discovered(files, bytes);
+    }
+
+    fn on_discovery_finished(&mut self) {
+        self.finish_discovery();
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Context, Result};
+#[cfg(feature = "http")]
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::file_processing::ReadOptions;
+#[cfg(feature = "http")]
+use crate::hash_algorithms::digests;
+use crate::hash_algorithms::HashAlgorithm;
+#[cfg(feature = "s3")]
+use crate::s3;
+use crate::utils::HashError;
+
+/// Scheme of the paths that name S3 objects, as in `s3://bucket/key`.
+pub const S3_SCHEME: &str = "s3://";
+
+/// Number of times a download is resumed after its connection drops.
+#[cfg(feature = "http")]
+const MAX_RESUMES: u32 = 3;
+
+/// Whether `path` is an http://, https:// or s3:// URL rather than a local
+/// path.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| {
+        let scheme = path.get(..8).unwrap_or(path).to_ascii_lowercase();
+        scheme.starts_with("http://")
+            || scheme.starts_with("https://")
+            || scheme.starts_with(S3_SCHEME)
+    })
+}
+
+/// Hashes the resource at `url` as it is downloaded.
+pub fn hash_url(
+    url: &Path,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    let url = url
+        .to_str()
+        .with_context(|| format!("Invalid URL: {}", url.display()))?;
+    if url.starts_with(S3_SCHEME) {
+        hash_s3(url, algorithms, read_options, on_bytes)
+    } else {
+        hash_http(url, algorithms, read_options, on_bytes)
+    }
+}
+
+#[cfg(feature = "s3")]
+fn hash_s3(
+    url: &str,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    s3::hash_object(url, algorithms, read_options.s3_check_etag, on_bytes)
+}
+
+#[cfg(not(feature = "s3"))]
+fn hash_s3(
+    url: &str,
+    _: &[HashAlgorithm],
+    _: &ReadOptions,
+    _: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    Err(anyhow!(
+        "Cannot read {}: parallelhash was built without the s3 feature",
+        url
+    )
+    .into())
+}
+
+#[cfg(not(feature = "http"))]
+fn hash_http(
+    url: &str,
+    _: &[HashAlgorithm],
+    _: &ReadOptions,
+    _: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    Err(anyhow!(
+        "Cannot download {}: parallelhash was built without the http feature",
+        url
+    )
+    .into())
+}
+
+/// Hashes the body of the response to a GET request for `url`. If the
+/// connection drops, the download resumes where it stopped with a range
+/// request, provided the server supports them and the resource has not
+/// changed in the meantime.
+#[cfg(feature = "http")]
+fn hash_http(
+    url: &str,
+    algorithms: &[HashAlgorithm],
+    read_options: &ReadOptions,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let mut hashers = algorithms.to_vec();
+    let mut buffer = vec![0; read_options.chunk_size];
+    let mut offset = 0u64;
+    // ETag or Last-Modified of the first response, so a resumed download
+    // fails instead of mixing two versions of the resource
+    let mut validator: Option<String> = None;
+    let mut resumes = 0;
+    loop {
+        let mut request = agent.get(url);
+        if offset > 0 {
+            request = request.header("Range", format!("bytes={}-", offset));
+            if let Some(validator) = &validator {
+                request = request.header("If-Range", validator);
+            }
+        }
+        let response = request
+            .call()
+            .with_context(|| format!("Failed to download {}", url))?;
+        let status = response.status();
+        if status == 404 || status == 410 {
+            return Err(HashError::NotFound(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("HTTP {}", status),
+            )));
+        }
+        if offset == 0 {
+            if !status.is_success() {
+                return Err(anyhow!("Failed to download {}: HTTP {}", url, status).into());
+            }
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            // Weak ETags cannot be used in If-Range
+            validator = header("etag")
+                .filter(|etag| !etag.starts_with("W/"))
+                .or_else(|| header("last-modified"));
+        } else if status != 206 {
+            return Err(anyhow!(
+                "Failed to resume download of {} at byte {}: HTTP {}",
+                url,
+                offset,
+                status
+            )
+            .into());
+        }
+
+        let mut body = response.into_body().into_reader();
+        let interrupted = loop {
+            match body.read(&mut buffer) {
+                Ok(0) => break None,
+                Ok(read) => {
+                    for hasher in &mut hashers {
+                        hasher.update(&buffer[..read]);
+                    }
+                    offset += read as u64;
+                    on_bytes(read as u64)?;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Some(e),
+            }
+        };
+        match interrupted {
+            None => return Ok(digests(&mut hashers)),
+            Some(e) if resumes < MAX_RESUMES => {
+                resumes += 1;
+                eprintln!(
+                    "Warning: download of {} interrupted at byte {} ({}), resuming",
+                    url, offset, e
+                );
+            }
+            Some(e) => {
+                return Err(anyhow::Error::from(e)
+                    .context(format!("Failed to download {}", url))
+                    .into())
+            }
+        }
+    }
+}
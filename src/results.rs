@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::hash_algorithms::HashAlgorithm;
+use crate::utils::HashError;
+
+/// Outcome of hashing one file, or one member of an archive. Formatting it
+/// for output is left to the `output` module.
+#[derive(Debug)]
+pub struct FileHashResult {
+    /// Path of the file as it is reported: the `--stdin-name` for standard
+    /// input, and `archive!member` for archive members.
+    pub path: PathBuf,
+    /// Digest of each algorithm as returned by `HashAlgorithm::finalize_reset`,
+    /// in the order the algorithms were given. Empty when the file was not
+    /// hashed.
+    pub digests: Vec<(HashAlgorithm, Vec<u8>)>,
+    /// Number of bytes hashed.
+    pub size: u64,
+    /// Why the file could not be hashed.
+    pub error: Option<HashError>,
+    /// What an item listed without hashing is, e.g. "symlink".
+    pub note: Option<&'static str>,
+    /// Time spent reading and hashing the file, when it was hashed on its
+    /// own rather than as a member of an archive.
+    pub elapsed: Option<Duration>,
+}
+
+impl FileHashResult {
+    /// Pairs `digests` with the algorithms that computed them.
+    pub fn new(
+        path: PathBuf,
+        algorithms: &[HashAlgorithm],
+        digests: Vec<Vec<u8>>,
+        size: u64,
+    ) -> Self {
+        Self {
+            path,
+            digests: algorithms.iter().cloned().zip(digests).collect(),
+            size,
+            error: None,
+            note: None,
+            elapsed: None,
+        }
+    }
+
+    pub fn failed(path: PathBuf, error: HashError) -> Self {
+        Self {
+            path,
+            digests: Vec::new(),
+            size: 0,
+            error: Some(error),
+            note: None,
+            elapsed: None,
+        }
+    }
+
+    /// An item reported without hashing, such as a symlink that is not
+    /// followed.
+    pub fn listed(path: PathBuf, note: &'static str) -> Self {
+        Self {
+            path,
+            digests: Vec::new(),
+            size: 0,
+            error: None,
+            note: Some(note),
+            elapsed: None,
+        }
+    }
+
+    /// Returns the result, or its error if the file could not be hashed.
+    pub fn into_result(mut self) -> Result<Self, HashError> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(self),
+        }
+    }
+}
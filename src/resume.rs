@@ -0,0 +1,100 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::checksum_verification::is_comment;
+use crate::hash_algorithms::HashAlgorithm;
+use crate::path_escape::{parse_path, strip_marker};
+
+/// Notes written after the path of items listed without digests.
+const NOTES: [&str; 6] = [
+    " (symlink)",
+    " (symlink loop)",
+    " (fifo)",
+    " (socket)",
+    " (block device)",
+    " (character device)",
+];
+
+/// Reads the manifest left by an interrupted run and returns the paths it
+/// already lists, or `None` if there is nothing to resume. A last line cut
+/// short by the interruption is removed from the file, so the run can append
+/// to it.
+pub fn completed_paths(
+    manifest: &Path,
+    algorithms: &[HashAlgorithm],
+) -> Result<Option<HashSet<PathBuf>>> {
+    let mut data = match fs::read(manifest) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read manifest: {}", manifest.display()))
+        }
+    };
+
+    let complete = data
+        .iter()
+        .rposition(|&byte| byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    if complete < data.len() {
+        OpenOptions::new()
+            .write(true)
+            .open(manifest)
+            .and_then(|file| file.set_len(complete as u64))
+            .with_context(|| {
+                format!("Failed to truncate partial line of {}", manifest.display())
+            })?;
+        data.truncate(complete);
+    }
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    let names: Vec<String> = algorithms.iter().map(|algo| algo.to_string()).collect();
+    let header = format!("{}  path", names.join("  "));
+    let mut completed = HashSet::new();
+    let lines = String::from_utf8_lossy(&data);
+    let mut lines = lines
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !is_comment(line))
+        .peekable();
+    let first = lines.peek().map(|&(i, _)| i);
+    for (i, line) in lines {
+        if Some(i) == first && line.ends_with("  path") {
+            if line != header {
+                bail!(
+                    "{} was written with different algorithms ({}), cannot resume",
+                    manifest.display(),
+                    line
+                );
+            }
+            continue;
+        }
+        let (line, escaped) = strip_marker(line);
+        let parts: Vec<&str> = line.splitn(names.len() + 1, "  ").collect();
+        if parts.len() != names.len() + 1 {
+            bail!(
+                "Line {} of {} does not match the selected algorithms, cannot resume",
+                i + 1,
+                manifest.display()
+            );
+        }
+        let mut path = parts[names.len()];
+        // Lines without digests carry a note after the path
+        if parts[..names.len()].iter().all(|&hash| hash == "N/A") {
+            path = NOTES
+                .iter()
+                .find_map(|note| path.strip_suffix(note))
+                .unwrap_or(path);
+            path = path.split("  (File not found: ").next().unwrap_or(path);
+        }
+        completed.insert(parse_path(path, escaped).with_context(|| {
+            format!("Invalid path at line {} of {}", i + 1, manifest.display())
+        })?);
+    }
+    Ok(Some(completed))
+}
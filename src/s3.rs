@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Context, Result};
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::Client;
+use digest::Digest;
+use md5::Md5;
+use std::io;
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+
+use crate::hash_algorithms::{digests, HashAlgorithm};
+use crate::remote::S3_SCHEME;
+use crate::utils::HashError;
+
+/// Runtime driving the SDK and the client shared by every download, set up
+/// on first use from the usual AWS environment variables, profiles and
+/// instance metadata.
+fn client() -> Result<&'static (Runtime, Client)> {
+    static CLIENT: OnceLock<(Runtime, Client)> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .context("Failed to start the S3 client runtime")?;
+    let config = runtime.block_on(aws_config::load_from_env());
+    let client = Client::new(&config);
+    Ok(CLIENT.get_or_init(|| (runtime, client)))
+}
+
+/// Hashes the S3 object at `url` as it is downloaded. With `check_etag`, the
+/// MD5 of its content, or of its parts for multipart uploads, is also
+/// compared with the ETag of the object, and a mismatch is an error.
+pub fn hash_object(
+    url: &str,
+    algorithms: &[HashAlgorithm],
+    check_etag: bool,
+    on_bytes: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Vec<Vec<u8>>, HashError> {
+    let (bucket, key) = url
+        .strip_prefix(S3_SCHEME)
+        .and_then(|path| path.split_once('/'))
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .with_context(|| format!("Invalid S3 URL, expected s3://bucket/key: {}", url))?;
+    let (runtime, client) = client()?;
+    runtime.block_on(async {
+        // The ETag and part size come first, and the download is made
+        // conditional on the ETag, so both describe the same version
+        let expected = if check_etag {
+            Some(expected_etag(client, bucket, key).await?)
+        } else {
+            None
+        };
+        let mut request = client.get_object().bucket(bucket).key(key);
+        if let Some((etag, _)) = &expected {
+            request = request.if_match(format!("\"{}\"", etag));
+        }
+        let object = match request.send().await {
+            Ok(object) => object,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Err(no_such_key());
+            }
+            Err(e) => {
+                return Err(
+                    anyhow!("Failed to download {}: {}", url, DisplayErrorContext(e)).into(),
+                )
+            }
+        };
+        let mut etag_hasher = expected
+            .as_ref()
+            .map(|(_, part_size)| EtagHasher::new(*part_size));
+
+        let mut hashers = algorithms.to_vec();
+        let mut body = object.body;
+        while let Some(bytes) = body
+            .try_next()
+            .await
+            .with_context(|| format!("Failed to download {}", url))?
+        {
+            for hasher in &mut hashers {
+                hasher.update(&bytes);
+            }
+            if let Some(etag_hasher) = &mut etag_hasher {
+                etag_hasher.update(&bytes);
+            }
+            on_bytes(bytes.len() as u64)?;
+        }
+
+        if let (Some((etag, _)), Some(etag_hasher)) = (expected, etag_hasher) {
+            let computed = etag_hasher.finalize();
+            if computed != etag {
+                return Err(anyhow!(
+                    "ETag mismatch for {}: the object has {}, its content gives {}",
+                    url,
+                    etag,
+                    computed
+                )
+                .into());
+            }
+        }
+        Ok(digests(&mut hashers))
+    })
+}
+
+fn no_such_key() -> HashError {
+    HashError::NotFound(io::Error::new(io::ErrorKind::NotFound, "no such key"))
+}
+
+/// Returns the ETag of an object, along with the size of its parts if it was
+/// uploaded in several, read from its first part.
+async fn expected_etag(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<(String, Option<u64>), HashError> {
+    let head = match client
+        .head_object()
+        .bucket(bucket)
+        .key(key)
+        .part_number(1)
+        .send()
+        .await
+    {
+        Ok(head) => head,
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => {
+            return Err(no_such_key());
+        }
+        Err(e) => {
+            return Err(anyhow!(
+                "Failed to read the ETag of s3://{}/{}: {}",
+                bucket,
+                key,
+                DisplayErrorContext(e)
+            )
+            .into())
+        }
+    };
+    let etag = head
+        .e_tag()
+        .map(|etag| etag.trim_matches('"').to_string())
+        .with_context(|| format!("No ETag for s3://{}/{}", bucket, key))?;
+    let part_size = if etag.contains('-') {
+        let size = head
+            .content_length()
+            .and_then(|size| u64::try_from(size).ok())
+            .filter(|&size| size > 0)
+            .with_context(|| format!("No part size for s3://{}/{}", bucket, key))?;
+        Some(size)
+    } else {
+        None
+    };
+    Ok((etag, part_size))
+}
+
+/// Computes the ETag S3 gives an object: the MD5 of its content, or for a
+/// multipart upload, the MD5 of the MD5s of its parts followed by the number
+/// of parts. Every part but the last one is assumed to have the same size.
+struct EtagHasher {
+    part_size: Option<u64>,
+    part: Md5,
+    part_len: u64,
+    part_digests: Vec<u8>,
+    parts: usize,
+}
+
+impl EtagHasher {
+    fn new(part_size: Option<u64>) -> Self {
+        Self {
+            part_size,
+            part: Md5::new(),
+            part_len: 0,
+            part_digests: Vec::new(),
+            parts: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        let Some(part_size) = self.part_size else {
+            self.part.update(data);
+            return;
+        };
+        while !data.is_empty() {
+            let take = data.len().min((part_size - self.part_len) as usize);
+            self.part.update(&data[..take]);
+            self.part_len += take as u64;
+            data = &data[take..];
+            if self.part_len == part_size {
+                self.finish_part();
+            }
+        }
+    }
+
+    fn finish_part(&mut self) {
+        let digest = self.part.finalize_reset();
+        self.part_digests.extend_from_slice(&digest);
+        self.part_len = 0;
+        self.parts += 1;
+    }
+
+    fn finalize(mut self) -> String {
+        if self.part_size.is_none() {
+            return hex::encode(self.part.finalize());
+        }
+        if self.part_len > 0 {
+            self.finish_part();
+        }
+        format!(
+            "{}-{}",
+            hex::encode(Md5::digest(&self.part_digests)),
+            self.parts
+        )
+    }
+}
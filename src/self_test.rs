@@ -0,0 +1,238 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::bench::synthetic_data;
+use crate::file_processing::{compute_file_hashes, ReadOptions};
+use crate::hash_algorithms::{AlgorithmOptions, HashAlgorithm, BUILTIN_ALGORITHMS};
+
+/// Chunk size of the edge case runs, small enough for files of a few chunks.
+const CHUNK_SIZE: usize = 4096;
+/// File sizes of the edge case runs, on both sides of the chunk boundaries.
+const EDGE_SIZES: [usize; 6] = [
+    0,
+    1,
+    CHUNK_SIZE - 1,
+    CHUNK_SIZE,
+    CHUNK_SIZE + 1,
+    2 * CHUNK_SIZE + 1,
+];
+
+/// An input of the known answer tests.
+#[derive(Clone, Copy)]
+enum Input {
+    Empty,
+    Abc,
+    /// The 448-bit message of the FIPS 180 examples.
+    TwoBlocks,
+    MillionA,
+    /// Bytes 0, 1, 2... up to the given length, as in the HighwayHash and
+    /// BLAKE3 reference vectors.
+    Sequence(usize),
+    Text(&'static str),
+}
+
+impl Input {
+    fn bytes(self) -> Vec<u8> {
+        match self {
+            Input::Empty => Vec::new(),
+            Input::Abc => b"abc".to_vec(),
+            Input::TwoBlocks => {
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".to_vec()
+            }
+            Input::MillionA => vec![b'a'; 1_000_000],
+            Input::Sequence(len) => (0..len).map(|i| (i % 251) as u8).collect(),
+            Input::Text(text) => text.as_bytes().to_vec(),
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Input::Empty => "empty input".to_string(),
+            Input::Abc => "\"abc\"".to_string(),
+            Input::TwoBlocks => "448-bit message".to_string(),
+            Input::MillionA => "one million \"a\"".to_string(),
+            Input::Sequence(len) => format!("byte sequence of length {}", len),
+            Input::Text(text) => format!("\"{}\"", text),
+        }
+    }
+}
+
+/// Published digests: the FIPS 180 and FIPS 202 examples for the SHA
+/// families and MD5 (RFC 1321), and the reference vectors of BLAKE3,
+/// HighwayHash (keyed with bytes 0 to 31) and Adler-32.
+const KNOWN_ANSWERS: &[(&str, Input, &str)] = &[
+    ("md5", Input::Empty, "d41d8cd98f00b204e9800998ecf8427e"),
+    ("md5", Input::Abc, "900150983cd24fb0d6963f7d28e17f72"),
+    ("md5", Input::TwoBlocks, "8215ef0796a20bcaaae116d3876c664a"),
+    ("md5", Input::MillionA, "7707d6ae4e027c70eea2a935c2296f21"),
+    ("sha1", Input::Empty, "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+    ("sha1", Input::Abc, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+    ("sha1", Input::TwoBlocks, "84983e441c3bd26ebaae4aa1f95129e5e54670f1"),
+    ("sha1", Input::MillionA, "34aa973cd4c4daa4f61eeb2bdbad27316534016f"),
+    ("sha256", Input::Empty, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+    ("sha256", Input::Abc, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+    ("sha256", Input::TwoBlocks, "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+    ("sha256", Input::MillionA, "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"),
+    ("sha384", Input::Empty, "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b"),
+    ("sha384", Input::Abc, "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"),
+    ("sha384", Input::TwoBlocks, "3391fdddfc8dc7393707a65b1b4709397cf8b1d162af05abfe8f450de5f36bc6b0455a8520bc4e6f5fe95b1fe3c8452b"),
+    ("sha384", Input::MillionA, "9d0e1809716474cb086e834e310a4a1ced149e9c00f248527972cec5704c2a5b07b8b3dc38ecc4ebae97ddd87f3d8985"),
+    ("sha512", Input::Empty, "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"),
+    ("sha512", Input::Abc, "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"),
+    ("sha512", Input::TwoBlocks, "204a8fc6dda82f0a0ced7beb8e08a41657c16ef468b228a8279be331a703c33596fd15c13b1b07f9aa1d3bea57789ca031ad85c7a71dd70354ec631238ca3445"),
+    ("sha512", Input::MillionA, "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973ebde0ff244877ea60a4cb0432ce577c31beb009c5c2c49aa2e4eadb217ad8cc09b"),
+    ("sha3-256", Input::Empty, "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"),
+    ("sha3-256", Input::Abc, "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"),
+    ("sha3-256", Input::TwoBlocks, "41c0dba2a9d6240849100376a8235e2c82e1b9998a999e21db32dd97496d3376"),
+    ("sha3-256", Input::MillionA, "5c8875ae474a3634ba4fd55ec85bffd661f32aca75c6d699d0cdcb6c115891c1"),
+    ("sha3-384", Input::Empty, "0c63a75b845e4f7d01107d852e4c2485c51a50aaaa94fc61995e71bbee983a2ac3713831264adb47fb6bd1e058d5f004"),
+    ("sha3-384", Input::Abc, "ec01498288516fc926459f58e2c6ad8df9b473cb0fc08c2596da7cf0e49be4b298d88cea927ac7f539f1edf228376d25"),
+    ("sha3-384", Input::TwoBlocks, "991c665755eb3a4b6bbdfb75c78a492e8c56a22c5c4d7e429bfdbc32b9d4ad5aa04a1f076e62fea19eef51acd0657c22"),
+    ("sha3-384", Input::MillionA, "eee9e24d78c1855337983451df97c8ad9eedf256c6334f8e948d252d5e0e76847aa0774ddb90a842190d2c558b4b8340"),
+    ("sha3-512", Input::Empty, "a69f73cca23a9ac5c8b567dc185a756e97c982164fe25859e0d1dcc1475c80a615b2123af1f5f94c11e3e9402c3ac558f500199d95b6d3e301758586281dcd26"),
+    ("sha3-512", Input::Abc, "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0"),
+    ("sha3-512", Input::TwoBlocks, "04a371e84ecfb5b8b77cb48610fca8182dd457ce6f326a0fd3d7ec2f1e91636dee691fbe0c985302ba1b0d8dc78c086346b533b49c030d99a27daf1139d6e75e"),
+    ("sha3-512", Input::MillionA, "3c3a876da14034ab60627c077bb98f7e120a2a5370212dffb3385a18d4f38859ed311d0a9d5141ce9cc5c66ee689b266a8aa18ace8282a0e0db596c90b0a7b87"),
+    ("blake3", Input::Empty, "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"),
+    ("blake3", Input::Sequence(1), "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213"),
+    ("blake3", Input::Abc, "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"),
+    ("adler32", Input::Empty, "00000001"),
+    ("adler32", Input::Text("Wikipedia"), "11e60398"),
+    ("crc32", Input::Empty, "00000000"),
+    ("crc32", Input::Text("123456789"), "cbf43926"),
+    ("highway64", Input::Sequence(0), "907a56de22c26e53"),
+    ("highway64", Input::Sequence(1), "7eab43aac7cddd78"),
+    ("highway64", Input::Sequence(2), "b8d0569ab0b53d62"),
+    ("highway128", Input::Sequence(0), "0fed268f9d8ffec733565e767f093e6f"),
+    ("highway256", Input::Sequence(0), "dd44482ac2c874f5d946017313c7351fb3aebeccb98714ff41da233145751df4"),
+    ("ssdeep", Input::Empty, "3::"),
+];
+
+/// Checks every built-in algorithm against published digests, then hashes
+/// files of sizes around the chunk boundaries through the file pipeline,
+/// read and memory-mapped, with every algorithm alone and all of them
+/// together, and compares the digests with hashing the same data in one
+/// piece. Writes a PASS or FAIL line per check to `writer` and fails if any
+/// check did.
+pub fn run_self_test(read_options: &ReadOptions, writer: &mut dyn Write) -> Result<()> {
+    let mut highway_key = [0u8; 32];
+    for (i, byte) in highway_key.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let options = AlgorithmOptions {
+        highway_key: Some(highway_key),
+        ..AlgorithmOptions::default()
+    };
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut report = |writer: &mut dyn Write, ok: bool, line: String| -> Result<()> {
+        if ok {
+            passed += 1;
+            writeln!(writer, "PASS  {}", line)?;
+        } else {
+            failed += 1;
+            writeln!(writer, "FAIL  {}", line)?;
+        }
+        Ok(())
+    };
+
+    for &(name, input, expected) in KNOWN_ANSWERS {
+        let mut algorithm = HashAlgorithm::with_options(name, &options)?;
+        algorithm.update(&input.bytes());
+        let digest = algorithm.finalize_reset();
+        let digest = algorithm.encode_digest(&digest);
+        let line = if digest == expected {
+            format!("{}  {}", algorithm, input.label())
+        } else {
+            format!(
+                "{}  {}: expected {}, got {}",
+                algorithm,
+                input.label(),
+                expected,
+                digest
+            )
+        };
+        report(writer, digest == expected, line)?;
+    }
+
+    let algorithms = BUILTIN_ALGORITHMS
+        .iter()
+        .map(|name| HashAlgorithm::with_options(name, &options))
+        .collect::<Result<Vec<_>>>()?;
+    let dir = std::env::temp_dir().join(format!("parallelhash-self-test-{}", std::process::id()));
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    let mismatches = edge_case_mismatches(&algorithms, &dir, read_options);
+    let _ = fs::remove_dir_all(&dir);
+    let mismatches = mismatches?;
+    let sizes = EDGE_SIZES.map(|size| size.to_string()).join(", ");
+    for (algorithm, mismatches) in algorithms.iter().zip(mismatches) {
+        let line = if mismatches.is_empty() {
+            format!("{}  files of {} bytes", algorithm, sizes)
+        } else {
+            format!("{}  {}", algorithm, mismatches.join("; "))
+        };
+        report(writer, mismatches.is_empty(), line)?;
+    }
+
+    writeln!(writer, "{} passed, {} failed", passed, failed)?;
+    if failed > 0 {
+        bail!("{} of {} self-tests failed", failed, passed + failed);
+    }
+    Ok(())
+}
+
+/// Hashes files of each of `EDGE_SIZES` in `dir` and returns, for each of
+/// `algorithms`, the runs whose digest differs from hashing the data in one
+/// piece.
+fn edge_case_mismatches(
+    algorithms: &[HashAlgorithm],
+    dir: &Path,
+    read_options: &ReadOptions,
+) -> Result<Vec<Vec<String>>> {
+    let mut mismatches = vec![Vec::new(); algorithms.len()];
+    let data = synthetic_data(EDGE_SIZES[EDGE_SIZES.len() - 1]);
+    for size in EDGE_SIZES {
+        let data = &data[..size];
+        let path = dir.join(format!("{}.bin", size));
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write file: {}", path.display()))?;
+        let expected = algorithms
+            .iter()
+            .map(|algorithm| {
+                let mut algorithm = algorithm.clone();
+                algorithm.update(data);
+                algorithm.finalize_reset()
+            })
+            .collect::<Vec<_>>();
+
+        for (mode, mmap_threshold) in [("read", None), ("memory-mapped", Some(0))] {
+            let read_options = ReadOptions {
+                chunk_size: CHUNK_SIZE,
+                adaptive_chunk_size: false,
+                mmap_threshold,
+                ..read_options.clone()
+            };
+            let together = compute_file_hashes(&path, algorithms, &read_options, &mut |_| Ok(()))
+                .into_result()?;
+            for (i, algorithm) in algorithms.iter().enumerate() {
+                let alone = compute_file_hashes(
+                    &path,
+                    std::slice::from_ref(algorithm),
+                    &read_options,
+                    &mut |_| Ok(()),
+                )
+                .into_result()?;
+                if together.digests[i].1 != expected[i] {
+                    mismatches[i].push(format!("{} bytes {} with all algorithms", size, mode));
+                }
+                if alone.digests[0].1 != expected[i] {
+                    mismatches[i].push(format!("{} bytes {} alone", size, mode));
+                }
+            }
+        }
+    }
+    Ok(mismatches)
+}
@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::hash_algorithms::{digests, HashAlgorithm};
+use crate::results::FileHashResult;
+use crate::utils::HashError;
+
+/// Hashes the file at `path` with each of `algorithms`, reading it
+/// `chunk_size` bytes at a time on the calling thread. This is what builds
+/// without the `native` feature hash with, e.g. in wasm32-wasi plugin hosts
+/// where no threads can be spawned.
+pub fn hash_file(path: &Path, algorithms: &[HashAlgorithm], chunk_size: usize) -> FileHashResult {
+    File::open(path)
+        .map_err(HashError::from)
+        .and_then(|file| hash_reader(path, file, algorithms, chunk_size))
+        .unwrap_or_else(|e| FileHashResult::failed(path.to_path_buf(), e))
+}
+
+/// Hashes everything `reader` yields with each of `algorithms`. `path` only
+/// names the data in the result and in errors.
+pub fn hash_reader(
+    path: &Path,
+    mut reader: impl Read,
+    algorithms: &[HashAlgorithm],
+    chunk_size: usize,
+) -> Result<FileHashResult, HashError> {
+    let mut hashers = algorithms.to_vec();
+    let mut buffer = vec![0; chunk_size.max(1)];
+    let mut size = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(source) => {
+                return Err(HashError::ReadError {
+                    path: path.to_path_buf(),
+                    offset: size,
+                    source,
+                })
+            }
+        };
+        for hasher in &mut hashers {
+            hasher.update(&buffer[..read]);
+        }
+        size += read as u64;
+    }
+    Ok(FileHashResult::new(
+        path.to_path_buf(),
+        algorithms,
+        digests(&mut hashers),
+        size,
+    ))
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::hash_algorithms::HashAlgorithm;
+use crate::output_format::{write_csv_row, Summary};
+use crate::results::FileHashResult;
+
+/// Formats of the `--stats` file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum StatsFormat {
+    /// Comma-separated values with a header row
+    #[default]
+    Csv,
+    /// One JSON object per file
+    Json,
+}
+
+/// Time taken by one file.
+#[derive(Clone, Debug)]
+pub struct FileStats {
+    pub path: PathBuf,
+    pub size: u64,
+    pub elapsed: Duration,
+}
+
+impl FileStats {
+    /// Throughput in MiB/s.
+    pub fn speed(&self) -> f64 {
+        self.size as f64 / (1024.0 * 1024.0) / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+/// Collects the time taken by each hashed file and writes them to a file
+/// once the run has finished, to spot slow files and degraded disks.
+pub struct StatsRecorder {
+    path: PathBuf,
+    format: StatsFormat,
+    files: Vec<FileStats>,
+}
+
+impl StatsRecorder {
+    pub fn new(path: PathBuf, format: StatsFormat) -> Self {
+        Self {
+            path,
+            format,
+            files: Vec::new(),
+        }
+    }
+
+    /// Records `result` if it was timed, i.e. if it is a file that was
+    /// hashed rather than an archive member, a failure or a listed item.
+    pub fn record(&mut self, result: &FileHashResult) {
+        if let Some(elapsed) = result.elapsed {
+            self.files.push(FileStats {
+                path: result.path.clone(),
+                size: result.size,
+                elapsed,
+            });
+        }
+    }
+
+    pub fn files(&self) -> &[FileStats] {
+        &self.files
+    }
+
+    /// Writes the recorded files in the order they were recorded.
+    pub fn write(&self) -> Result<()> {
+        let file = File::create(&self.path)
+            .with_context(|| format!("Failed to create stats file: {}", self.path.display()))?;
+        let mut writer = BufWriter::new(file);
+        if self.format == StatsFormat::Csv {
+            write_csv_row(
+                &mut writer,
+                &["path", "size", "seconds", "mib_per_second"].map(String::from),
+            )?;
+        }
+        for stats in &self.files {
+            match self.format {
+                StatsFormat::Csv => write_csv_row(
+                    &mut writer,
+                    &[
+                        stats.path.to_string_lossy().into_owned(),
+                        stats.size.to_string(),
+                        format!("{:.6}", stats.elapsed.as_secs_f64()),
+                        format!("{:.2}", stats.speed()),
+                    ],
+                )?,
+                StatsFormat::Json => writeln!(
+                    writer,
+                    "{}",
+                    serde_json::json!({
+                        "path": stats.path.to_string_lossy(),
+                        "size": stats.size,
+                        "seconds": stats.elapsed.as_secs_f64(),
+                        "mib_per_second": stats.speed(),
+                    })
+                )?,
+            }
+        }
+        writer
+            .flush()
+            .with_context(|| format!("Failed to write stats file: {}", self.path.display()))
+    }
+}
+
+/// Time spent updating and finalizing each algorithm, summed over all files
+/// and threads. Clones share the same totals.
+#[derive(Clone, Debug, Default)]
+pub struct AlgorithmTimings(Arc<Mutex<BTreeMap<String, Duration>>>);
+
+impl AlgorithmTimings {
+    /// Adds `elapsed` to the total of `algorithm`.
+    pub fn add(&self, algorithm: &HashAlgorithm, elapsed: Duration) {
+        let mut totals = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        *totals.entry(algorithm.to_string()).or_default() += elapsed;
+    }
+
+    /// Total of each algorithm, by name.
+    pub fn totals(&self) -> BTreeMap<String, Duration> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Writes the totals of a finished run as a JSON object to the file at
+/// `path`, or to stderr for `-`.
+pub fn write_summary_json(
+    path: &Path,
+    summary: &Summary,
+    timings: &AlgorithmTimings,
+) -> Result<()> {
+    let elapsed = summary.elapsed.as_secs_f64();
+    let algorithms = timings
+        .totals()
+        .into_iter()
+        .map(|(name, time)| (name, time.as_secs_f64().into()))
+        .collect::<serde_json::Map<_, _>>();
+    let json = serde_json::json!({
+        "files": summary.files,
+        "bytes": summary.bytes,
+        "errors": summary.errors,
+        "elapsed_seconds": elapsed,
+        "mib_per_second": summary.bytes as f64 / (1024.0 * 1024.0) / elapsed.max(1e-9),
+        "algorithm_seconds": algorithms,
+    });
+    if path.as_os_str() == "-" {
+        writeln!(io::stderr(), "{}", json)?;
+        return Ok(());
+    }
+    let mut file = File::create(path)
+        .with_context(|| format!("Failed to create summary file: {}", path.display()))?;
+    writeln!(file, "{}", json)
+        .with_context(|| format!("Failed to write summary file: {}", path.display()))
+}
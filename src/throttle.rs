@@ -0,0 +1,41 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How far reading may fall behind the allowed rate, e.g. while hashing is
+/// slower than it, and then catch up at full speed.
+const MAX_LAG: Duration = Duration::from_secs(1);
+
+/// Limits the rate at which all files together are read, so that a
+/// background scan leaves bandwidth to other users of the storage. Clones
+/// share the same budget.
+#[derive(Clone, Debug)]
+pub struct Throttle {
+    bytes_per_second: f64,
+    /// When the bytes read so far are due at the allowed rate.
+    due: Arc<Mutex<Instant>>,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1) as f64,
+            due: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Accounts for `bytes` just read, sleeping until reading them fits in
+    /// the allowed rate.
+    pub fn consume(&self, bytes: u64) {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second);
+        let now = Instant::now();
+        let due = {
+            let mut due = self.due.lock().unwrap_or_else(|e| e.into_inner());
+            // Time spent idle is only saved up for a short burst
+            let earliest = now.checked_sub(MAX_LAG).unwrap_or(now);
+            *due = (*due).max(earliest) + cost;
+            *due
+        };
+        thread::sleep(due.saturating_duration_since(now));
+    }
+}
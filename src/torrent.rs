@@ -0,0 +1,511 @@
+use anyhow::{anyhow, bail, Context, Result};
+use digest::Digest;
+use sha1::Sha1;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use crate::output::Highlight;
+use crate::path_escape::result_line;
+use crate::OutputManager;
+
+/// Size of the blocks hashed into the leaves of BitTorrent v2 merkle trees.
+const BLOCK_SIZE: u64 = 16 * 1024;
+
+/// A bencoded value.
+#[derive(Debug)]
+enum Value {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Dict(dict) => dict.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    fn int(&self) -> Option<i64> {
+        match self {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes the bencoded value at the start of `data`, returning it with the
+/// rest of `data`.
+fn decode(data: &[u8]) -> Result<(Value, &[u8])> {
+    let integer = |digits: &[u8]| -> Result<i64> {
+        std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| anyhow!("Invalid integer in torrent"))
+    };
+    match data.first() {
+        Some(b'i') => {
+            let end = data
+                .iter()
+                .position(|&b| b == b'e')
+                .ok_or_else(|| anyhow!("Unterminated integer in torrent"))?;
+            Ok((Value::Int(integer(&data[1..end])?), &data[end + 1..]))
+        }
+        Some(b'l') => {
+            let mut rest = &data[1..];
+            let mut list = Vec::new();
+            while rest.first() != Some(&b'e') {
+                let (value, next) = decode(rest)?;
+                list.push(value);
+                rest = next;
+            }
+            Ok((Value::List(list), &rest[1..]))
+        }
+        Some(b'd') => {
+            let mut rest = &data[1..];
+            let mut dict = BTreeMap::new();
+            while rest.first() != Some(&b'e') {
+                let (key, next) = decode(rest)?;
+                let Value::Bytes(key) = key else {
+                    bail!("Invalid dictionary key in torrent");
+                };
+                let (value, next) = decode(next)?;
+                dict.insert(key, value);
+                rest = next;
+            }
+            Ok((Value::Dict(dict), &rest[1..]))
+        }
+        Some(b'0'..=b'9') => {
+            let colon = data
+                .iter()
+                .position(|&b| b == b':')
+                .ok_or_else(|| anyhow!("Invalid string in torrent"))?;
+            let len = usize::try_from(integer(&data[..colon])?)?;
+            let rest = &data[colon + 1..];
+            if rest.len() < len {
+                bail!("Truncated torrent");
+            }
+            Ok((Value::Bytes(rest[..len].to_vec()), &rest[len..]))
+        }
+        _ => bail!("Invalid or truncated torrent"),
+    }
+}
+
+/// A file described by a torrent.
+struct TorrentFile {
+    path: PathBuf,
+    length: u64,
+    /// Zeros that only pad v1 pieces in hybrid torrents, not stored on disk.
+    padding: bool,
+    /// Root of the v2 merkle tree of the file.
+    pieces_root: Option<Vec<u8>>,
+}
+
+/// Pieces of a file, by how they verified.
+#[derive(Default)]
+struct PieceCounts {
+    complete: u64,
+    /// Pieces with data missing, from a missing or truncated file.
+    incomplete: u64,
+    corrupt: u64,
+}
+
+impl PieceCounts {
+    fn add(&mut self, available: bool, matches: bool) {
+        match (available, matches) {
+            (false, _) => self.incomplete += 1,
+            (true, true) => self.complete += 1,
+            (true, false) => self.corrupt += 1,
+        }
+    }
+}
+
+/// Verifies the files of `torrent` stored under `dir` against its piece
+/// hashes, and reports for each one how many of its pieces are complete,
+/// incomplete (data missing) or corrupt: OK when all of them are complete,
+/// INCOMPLETE when some are missing, and FAILED when some are corrupt. v2
+/// torrents, and hybrid ones, are verified file by file with their merkle
+/// trees; v1 torrents with the SHA-1 of pieces spanning the files end to end,
+/// so a piece shared by two files counts for both.
+pub fn verify_torrent(
+    torrent: &Path,
+    dir: &Path,
+    show_headers: bool,
+    output_manager: &mut OutputManager,
+) -> Result<()> {
+    let data = fs::read(torrent)
+        .with_context(|| format!("Failed to read torrent: {}", torrent.display()))?;
+    let (metainfo, _) =
+        decode(&data).with_context(|| format!("Invalid torrent: {}", torrent.display()))?;
+    let info = metainfo
+        .get("info")
+        .ok_or_else(|| anyhow!("Torrent has no info dictionary"))?;
+    let piece_length = info
+        .get("piece length")
+        .and_then(Value::int)
+        .and_then(|n| u64::try_from(n).ok())
+        .filter(|&n| n > 0)
+        .ok_or_else(|| anyhow!("Torrent has no piece length"))?;
+    let name = info
+        .get("name")
+        .and_then(Value::bytes)
+        .map(String::from_utf8_lossy)
+        .ok_or_else(|| anyhow!("Torrent has no name"))?;
+    let name = safe_component(&name)?;
+
+    if show_headers {
+        output_manager.write_result_header("Result  complete  incomplete  corrupt  path")?;
+    }
+    let results = if let Some(tree) = info.get("file tree") {
+        let layers = metainfo.get("piece layers");
+        let files = v2_files(tree, dir, name)?;
+        let mut results = Vec::new();
+        for file in files {
+            let layer = match (&file.pieces_root, layers) {
+                (Some(_), _) if file.length <= piece_length => Vec::new(),
+                (Some(root), Some(Value::Dict(layers))) => layers
+                    .get(root)
+                    .and_then(Value::bytes)
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| {
+                        anyhow!("Torrent has no piece layer for {}", file.path.display())
+                    })?,
+                (Some(_), _) => bail!("Torrent has no piece layers"),
+                (None, _) => Vec::new(),
+            };
+            output_manager.start_file(&file.path);
+            let counts = verify_v2_file(&file, piece_length, &layer, output_manager)?;
+            results.push((file.path, counts, file.length));
+        }
+        results
+    } else {
+        let pieces = info
+            .get("pieces")
+            .and_then(Value::bytes)
+            .filter(|pieces| pieces.len() % 20 == 0)
+            .ok_or_else(|| anyhow!("Torrent has no pieces"))?;
+        let files = v1_files(info, dir, name)?;
+        let counts = verify_v1_files(&files, piece_length, pieces, output_manager)?;
+        files
+            .into_iter()
+            .zip(counts)
+            .filter(|(file, _)| !file.padding)
+            .map(|(file, counts)| (file.path, counts, file.length))
+            .collect()
+    };
+
+    for (path, counts, length) in results {
+        // Empty files have no pieces, so only their absence shows
+        let missing = length == 0 && !path.is_file();
+        let status = if counts.corrupt > 0 {
+            output_manager.highlight("FAILED", Highlight::Failure)
+        } else if counts.incomplete > 0 || missing {
+            "INCOMPLETE".to_string()
+        } else {
+            output_manager.highlight("OK", Highlight::Success)
+        };
+        let columns = format!(
+            "{}  {}  {}  {}",
+            status, counts.complete, counts.incomplete, counts.corrupt
+        );
+        output_manager.write_result(&result_line(&columns, &path))?;
+    }
+    output_manager.finish()?;
+    Ok(())
+}
+
+/// Checks that a path element of a torrent names an entry of its directory,
+/// so that a torrent cannot point outside of the directory being verified.
+fn safe_component(name: &str) -> Result<&Path> {
+    let path = Path::new(name);
+    let mut components = path.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(path),
+        _ => bail!("Unsafe path element in torrent: {:?}", name),
+    }
+}
+
+/// The files of a v1 torrent, in the order their data is laid out in pieces.
+fn v1_files(info: &Value, dir: &Path, name: &Path) -> Result<Vec<TorrentFile>> {
+    let Some(Value::List(files)) = info.get("files") else {
+        let length = info
+            .get("length")
+            .and_then(Value::int)
+            .and_then(|n| u64::try_from(n).ok())
+            .ok_or_else(|| anyhow!("Torrent has no length"))?;
+        return Ok(vec![TorrentFile {
+            path: dir.join(name),
+            length,
+            padding: false,
+            pieces_root: None,
+        }]);
+    };
+    files
+        .iter()
+        .map(|file| {
+            let length = file
+                .get("length")
+                .and_then(Value::int)
+                .and_then(|n| u64::try_from(n).ok())
+                .ok_or_else(|| anyhow!("Torrent file has no length"))?;
+            let Some(Value::List(elements)) = file.get("path") else {
+                bail!("Torrent file has no path");
+            };
+            let mut path = dir.join(name);
+            for element in elements {
+                let element = element
+                    .bytes()
+                    .map(String::from_utf8_lossy)
+                    .ok_or_else(|| anyhow!("Invalid path in torrent"))?;
+                path.push(safe_component(&element)?);
+            }
+            let padding = file
+                .get("attr")
+                .and_then(Value::bytes)
+                .is_some_and(|attr| attr.contains(&b'p'));
+            Ok(TorrentFile {
+                path,
+                length,
+                padding,
+                pieces_root: None,
+            })
+        })
+        .collect()
+}
+
+/// The files of a v2 torrent, from its file tree. A tree with a single file
+/// at its top is a single-file torrent, stored in `dir` as is; the files of
+/// other trees are stored in a directory named after the torrent.
+fn v2_files(tree: &Value, dir: &Path, name: &Path) -> Result<Vec<TorrentFile>> {
+    fn walk(tree: &Value, path: &mut PathBuf, files: &mut Vec<TorrentFile>) -> Result<()> {
+        let Value::Dict(entries) = tree else {
+            bail!("Invalid file tree in torrent");
+        };
+        for (key, entry) in entries {
+            if key.is_empty() {
+                let length = entry
+                    .get("length")
+                    .and_then(Value::int)
+                    .and_then(|n| u64::try_from(n).ok())
+                    .ok_or_else(|| anyhow!("Torrent file has no length"))?;
+                let pieces_root = entry
+                    .get("pieces root")
+                    .and_then(Value::bytes)
+                    .map(<[u8]>::to_vec);
+                if length > 0 && pieces_root.as_ref().is_none_or(|root| root.len() != 32) {
+                    bail!("Torrent file {} has no pieces root", path.display());
+                }
+                files.push(TorrentFile {
+                    path: path.clone(),
+                    length,
+                    padding: false,
+                    pieces_root,
+                });
+            } else {
+                path.push(safe_component(&String::from_utf8_lossy(key))?);
+                walk(entry, path, files)?;
+                path.pop();
+            }
+        }
+        Ok(())
+    }
+
+    let single_file = match tree {
+        Value::Dict(entries) => {
+            entries.len() == 1 && entries.values().all(|entry| entry.get("").is_some())
+        }
+        _ => false,
+    };
+    let mut path = if single_file {
+        dir.to_path_buf()
+    } else {
+        dir.join(name)
+    };
+    let mut files = Vec::new();
+    walk(tree, &mut path, &mut files)?;
+    Ok(files)
+}
+
+/// Reads up to `buf.len()` bytes, fewer only at the end of the file.
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Hashes the pieces of a v1 torrent, which run across `files` end to end,
+/// and counts them for each file they overlap.
+fn verify_v1_files(
+    files: &[TorrentFile],
+    piece_length: u64,
+    pieces: &[u8],
+    output_manager: &mut OutputManager,
+) -> Result<Vec<PieceCounts>> {
+    let total = files.iter().map(|file| file.length).sum::<u64>();
+    if total.div_ceil(piece_length) != pieces.len() as u64 / 20 {
+        bail!(
+            "Torrent has {} pieces for {} bytes",
+            pieces.len() / 20,
+            total
+        );
+    }
+    let mut counts = files
+        .iter()
+        .map(|_| PieceCounts::default())
+        .collect::<Vec<_>>();
+    let mut buf = vec![0; piece_length as usize];
+    // The file being read, its handle (None when it cannot be read) and how
+    // much of it was consumed
+    let mut current = 0;
+    let mut handle: Option<File> = None;
+    let mut consumed = 0;
+    for (index, expected) in pieces.chunks(20).enumerate() {
+        let start = index as u64 * piece_length;
+        let len = piece_length.min(total - start) as usize;
+        let mut hasher = Sha1::new();
+        let mut available = true;
+        let mut overlapped = Vec::new();
+        let mut filled = 0;
+        while filled < len {
+            let file = &files[current];
+            if consumed == 0 && !file.padding {
+                output_manager.start_file(&file.path);
+                handle = File::open(&file.path).ok();
+            }
+            let want = (file.length - consumed).min((len - filled) as u64) as usize;
+            let chunk = &mut buf[filled..filled + want];
+            if file.padding {
+                chunk.fill(0);
+            } else {
+                let read = match &mut handle {
+                    Some(handle) => read_full(handle, chunk).unwrap_or(0),
+                    None => 0,
+                };
+                if read < want {
+                    available = false;
+                    handle = None;
+                }
+                output_manager.update_bytes(read as u64)?;
+            }
+            hasher.update(&*chunk);
+            filled += want;
+            consumed += want as u64;
+            if want > 0 {
+                overlapped.push(current);
+            }
+            if consumed == file.length {
+                current += 1;
+                consumed = 0;
+            }
+        }
+        let matches = hasher.finalize().as_slice() == expected;
+        for file in overlapped {
+            counts[file].add(available, matches);
+        }
+    }
+    Ok(counts)
+}
+
+/// Hashes a file of a v2 torrent block by block, and checks each piece
+/// against its layer of the merkle tree of the file, or the root of the tree
+/// for files of a single piece.
+fn verify_v2_file(
+    file: &TorrentFile,
+    piece_length: u64,
+    layer: &[u8],
+    output_manager: &mut OutputManager,
+) -> Result<PieceCounts> {
+    let mut counts = PieceCounts::default();
+    let Some(root) = &file.pieces_root else {
+        return Ok(counts);
+    };
+    let blocks_per_piece = (piece_length / BLOCK_SIZE).max(1) as usize;
+    let piece_count = file.length.div_ceil(piece_length) as usize;
+    if piece_count > 1 {
+        let hashes = layer.chunks(32).map(to_hash).collect::<Vec<_>>();
+        let padding = merkle_root(vec![[0; 32]; blocks_per_piece]);
+        if hashes.len() != piece_count || merkle_root_padded(hashes, padding) != root[..] {
+            bail!(
+                "Piece layer of {} does not match its root",
+                file.path.display()
+            );
+        }
+    }
+    let mut handle = File::open(&file.path).ok();
+    let mut buf = vec![0; BLOCK_SIZE as usize];
+    for piece in 0..piece_count {
+        let start = piece as u64 * piece_length;
+        let len = piece_length.min(file.length - start);
+        let mut leaves = Vec::with_capacity(blocks_per_piece);
+        let mut available = true;
+        for block in 0..len.div_ceil(BLOCK_SIZE) {
+            let want = BLOCK_SIZE.min(len - block * BLOCK_SIZE) as usize;
+            let read = match &mut handle {
+                Some(handle) => read_full(handle, &mut buf[..want]).unwrap_or(0),
+                None => 0,
+            };
+            if read < want {
+                available = false;
+                handle = None;
+            }
+            output_manager.update_bytes(read as u64)?;
+            leaves.push(to_hash(&Sha256::digest(&buf[..want])));
+        }
+        let (computed, expected) = if piece_count == 1 {
+            (merkle_root_padded(leaves, [0; 32]), &root[..])
+        } else {
+            leaves.resize(blocks_per_piece, [0; 32]);
+            (merkle_root(leaves), &layer[piece * 32..piece * 32 + 32])
+        };
+        counts.add(available, computed == expected);
+    }
+    Ok(counts)
+}
+
+fn to_hash(bytes: &[u8]) -> [u8; 32] {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(bytes);
+    hash
+}
+
+/// Root of a merkle tree whose leaves are padded with `padding` up to a
+/// power of two.
+fn merkle_root_padded(mut leaves: Vec<[u8; 32]>, padding: [u8; 32]) -> [u8; 32] {
+    leaves.resize(leaves.len().next_power_of_two(), padding);
+    merkle_root(leaves)
+}
+
+/// Root of a merkle tree of SHA-256 hashes, with a power of two leaves.
+fn merkle_root(mut layer: Vec<[u8; 32]>) -> [u8; 32] {
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| {
+                let mut hasher = Sha256::new();
+                hasher.update(pair[0]);
+                hasher.update(pair[1]);
+                to_hash(&hasher.finalize())
+            })
+            .collect();
+    }
+    layer[0]
+}
@@ -0,0 +1,132 @@
+use io_uring::{opcode, types, IoUring};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::os::unix::io::AsRawFd;
+
+use crate::buffer_pool::BufferPool;
+
+/// Number of reads kept in flight.
+const QUEUE_DEPTH: usize = 8;
+
+/// Reads a regular file through io_uring, keeping several reads at increasing
+/// offsets in flight so the device queue stays full while earlier chunks are
+/// being hashed. Chunks are still returned in file order.
+///
+/// The file is read up to the length it had when the reader was created.
+pub struct UringReader {
+    ring: IoUring,
+    file: File,
+    chunk_size: usize,
+    file_len: u64,
+    next_offset: u64,
+    next_delivery: u64,
+    /// Buffers owned by the kernel until their completion is reaped, with the
+    /// number of bytes requested, keyed by file offset.
+    in_flight: HashMap<u64, (Vec<u8>, usize)>,
+    completed: BTreeMap<u64, (Vec<u8>, usize)>,
+}
+
+impl UringReader {
+    pub fn new(file: File, chunk_size: usize) -> io::Result<Self> {
+        let file_len = file.metadata()?.len();
+        Ok(Self {
+            ring: IoUring::new(QUEUE_DEPTH as u32)?,
+            file,
+            chunk_size,
+            file_len,
+            next_offset: 0,
+            next_delivery: 0,
+            in_flight: HashMap::new(),
+            completed: BTreeMap::new(),
+        })
+    }
+
+    pub fn next_chunk(&mut self, pool: &BufferPool) -> io::Result<(Vec<u8>, Range<usize>)> {
+        while self.in_flight.len() < QUEUE_DEPTH && self.next_offset < self.file_len {
+            let len = (self.file_len - self.next_offset).min(self.chunk_size as u64) as usize;
+            self.submit(self.next_offset, len, pool.get())?;
+            self.next_offset += len as u64;
+        }
+
+        loop {
+            if let Some((buffer, len)) = self.completed.remove(&self.next_delivery) {
+                self.next_delivery += len as u64;
+                return Ok((buffer, 0..len));
+            }
+            if self.in_flight.is_empty() {
+                return Ok((pool.get(), 0..0));
+            }
+
+            self.ring.submit_and_wait(1)?;
+            let completions: Vec<(u64, i32)> = self
+                .ring
+                .completion()
+                .map(|cqe| (cqe.user_data(), cqe.result()))
+                .collect();
+            for (offset, result) in completions {
+                let (buffer, requested) = self
+                    .in_flight
+                    .remove(&offset)
+                    .expect("completion for a submitted read");
+                if result < 0 {
+                    return Err(io::Error::from_raw_os_error(-result));
+                }
+                let bytes_read = result as usize;
+                if bytes_read == 0 {
+                    // The file was truncated while being read
+                    pool.put(buffer);
+                    continue;
+                }
+                if bytes_read < requested {
+                    self.submit(
+                        offset + bytes_read as u64,
+                        requested - bytes_read,
+                        pool.get(),
+                    )?;
+                }
+                self.completed.insert(offset, (buffer, bytes_read));
+            }
+        }
+    }
+
+    fn submit(&mut self, offset: u64, len: usize, mut buffer: Vec<u8>) -> io::Result<()> {
+        let entry = opcode::Read::new(
+            types::Fd(self.file.as_raw_fd()),
+            buffer.as_mut_ptr(),
+            len as u32,
+        )
+        .offset(offset)
+        .build()
+        .user_data(offset);
+        // SAFETY: the buffer's heap allocation is kept alive in `in_flight`
+        // until the completion for this read has been reaped (see `Drop`).
+        unsafe {
+            self.ring
+                .submission()
+                .push(&entry)
+                .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+        }
+        self.in_flight.insert(offset, (buffer, len));
+        Ok(())
+    }
+}
+
+impl Drop for UringReader {
+    fn drop(&mut self) {
+        // The kernel may still write into in-flight buffers, so they must not
+        // be freed before their reads complete
+        while !self.in_flight.is_empty() {
+            if self.ring.submit_and_wait(1).is_err() {
+                // Leak the buffers rather than risk a write into freed memory
+                std::mem::forget(std::mem::take(&mut self.in_flight));
+                return;
+            }
+            let offsets: Vec<u64> = self.ring.completion().map(|cqe| cqe.user_data()).collect();
+            for offset in offsets {
+                self.in_flight.remove(&offset);
+            }
+        }
+    }
+}
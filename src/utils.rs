@@ -1,23 +1,231 @@
-use anyhow::Result;
-use std::io;
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-use crate::hash_algorithms::HashAlgorithm;
+use crate::hash_algorithms::{supports_hmac, AlgorithmOptions, HashAlgorithm, HMAC_ALGORITHMS};
 
-pub fn validate_algorithms(algorithms: &[String]) -> Result<Vec<HashAlgorithm>> {
+pub fn validate_algorithms(
+    algorithms: &[String],
+    options: &AlgorithmOptions,
+) -> Result<Vec<HashAlgorithm>> {
+    if options.hmac_key.is_some() {
+        let unsupported: Vec<_> = algorithms
+            .iter()
+            .map(String::as_str)
+            .filter(|algo| !supports_hmac(algo))
+            .collect();
+        if !unsupported.is_empty() {
+            bail!(
+                "HMAC is not supported for {} (supported: {})",
+                unsupported.join(", "),
+                HMAC_ALGORITHMS
+            );
+        }
+    }
     algorithms
         .iter()
-        .map(|algo| HashAlgorithm::new(algo))
+        .map(|algo| HashAlgorithm::with_options(algo, options))
         .collect()
 }
 
-#[derive(Debug)]
+pub fn parse_hex_key(hex_key: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(hex_key.trim()).map_err(|e| anyhow!("Invalid hex key: {}", e))?;
+    bytes.try_into().map_err(|b: Vec<u8>| {
+        anyhow!(
+            "Key must be 32 bytes (64 hex digits), got {} bytes",
+            b.len()
+        )
+    })
+}
+
+/// Parses a size in bytes with an optional unit, e.g. `500`, `10K` or
+/// `1.5GiB`. Single letters and IEC units (KiB, MiB, ...) are powers of 1024,
+/// SI units (kB, MB, ...) powers of 1000.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("expected a size such as 500, 10K or 1.5GiB, got '{}'", s))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        _ => return Err(format!("unknown size unit '{}'", unit.trim())),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Parses a read rate in bytes per second, given in MiB/s as a bare number
+/// (`50`) or with a unit as for `parse_size` (`500K`, `1.5GiB`).
+pub fn parse_throughput(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let s = s.strip_suffix("/s").unwrap_or(s);
+    if s.parse::<f64>().is_ok() {
+        parse_size(&format!("{}M", s))
+    } else {
+        parse_size(s)
+    }
+}
+
+/// Parses a point in time given either as a UTC timestamp (`2024-05-01`,
+/// `2024-05-01 12:00:00` or RFC 3339) or as a duration before now (`7d`,
+/// `12h 30m`).
+pub fn parse_time(s: &str) -> Result<SystemTime, String> {
+    let s = s.trim();
+    if let Ok(ago) = humantime::parse_duration(s) {
+        return SystemTime::now()
+            .checked_sub(ago)
+            .ok_or_else(|| format!("duration too long: '{}'", s));
+    }
+    let timestamp = if s.len() == 10 {
+        format!("{} 00:00:00", s)
+    } else {
+        s.to_string()
+    };
+    humantime::parse_rfc3339_weak(&timestamp).map_err(|_| {
+        format!(
+            "expected a timestamp such as 2024-05-01 or a duration such as 7d, got '{}'",
+            s
+        )
+    })
+}
+
+/// Reads a list of paths from `source`, or from standard input for `-`, one
+/// path per line or, with `nul_delimited`, separated by NUL bytes as written
+/// by `find -print0`. Empty entries are skipped.
+pub fn read_path_list(source: &Path, nul_delimited: bool) -> Result<Vec<PathBuf>> {
+    let mut list = Vec::new();
+    if source.as_os_str() == "-" {
+        io::stdin()
+            .read_to_end(&mut list)
+            .context("Failed to read the path list from standard input")?;
+    } else {
+        list = fs::read(source)
+            .with_context(|| format!("Failed to read path list: {}", source.display()))?;
+    }
+    let delimiter = if nul_delimited { b'\0' } else { b'\n' };
+    Ok(list
+        .split(|&byte| byte == delimiter)
+        .map(|entry| match entry {
+            [entry @ .., b'\r'] if !nul_delimited => entry,
+            entry => entry,
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+/// Converts raw bytes, e.g. a file name read from a list, to a path.
+#[cfg(unix)]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bytes).into()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+/// Why a file could not be hashed or its results written, sorted into the
+/// failures callers may want to handle differently.
+#[derive(Debug, thiserror::Error)]
 pub enum HashError {
-    FileNotFound(io::Error),
+    /// The file does not exist, or a URL answered 404 or 410.
+    #[error("File not found: {0}")]
+    NotFound(io::Error),
+    #[error(transparent)]
+    PermissionDenied(io::Error),
+    /// Reading failed after the first `offset` bytes of the file.
+    #[error("Failed to read from file: {} at byte {offset}", path.display())]
+    ReadError {
+        path: PathBuf,
+        offset: u64,
+        #[source]
+        source: io::Error,
+    },
+    /// A thread hashing the file panicked.
+    #[error("Hash worker thread panicked while hashing {}", .0.display())]
+    WorkerPanic(PathBuf),
+    /// The results could not be written to the output.
+    #[error("Failed to write the results")]
+    OutputError(#[source] io::Error),
+    /// The run was stopped with a `CancellationToken`.
+    #[error("Cancelled")]
+    Cancelled,
+    /// Any other failure, such as a malformed archive or a failed download.
+    #[error(transparent)]
     Other(anyhow::Error),
 }
 
+impl HashError {
+    /// Short name of the kind of failure, as written to `--errors-to`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HashError::NotFound(_) => "not_found",
+            HashError::PermissionDenied(_) => "permission_denied",
+            HashError::ReadError { .. } => "read_error",
+            HashError::WorkerPanic(_) => "worker_panic",
+            HashError::OutputError(_) => "output_error",
+            HashError::Cancelled => "cancelled",
+            HashError::Other(_) => "other",
+        }
+    }
+
+    /// Whether reading the file again may succeed, as after an I/O error or
+    /// a timeout on a flaky network filesystem.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HashError::ReadError { source, .. } => is_transient(source),
+            HashError::Other(e) => e
+                .chain()
+                .filter_map(|cause| cause.downcast_ref::<io::Error>())
+                .any(is_transient),
+            _ => false,
+        }
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    if matches!(
+        err.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    ) {
+        return true;
+    }
+    #[cfg(target_os = "linux")]
+    if matches!(err.raw_os_error(), Some(libc::EIO | libc::ESTALE)) {
+        return true;
+    }
+    false
+}
+
 impl From<anyhow::Error> for HashError {
+    /// Keeps the kind of a `HashError` that went through `anyhow`.
     fn from(err: anyhow::Error) -> Self {
-        HashError::Other(err)
+        err.downcast().unwrap_or_else(HashError::Other)
+    }
+}
+
+impl From<io::Error> for HashError {
+    /// Sorts an error opening or reading a file by its kind.
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => HashError::NotFound(err),
+            io::ErrorKind::PermissionDenied => HashError::PermissionDenied(err),
+            _ => HashError::Other(err.into()),
+        }
     }
 }
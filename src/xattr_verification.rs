@@ -0,0 +1,110 @@
+use anyhow::Result;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::file_processing::{compute_file_hashes, ReadOptions};
+use crate::hash_algorithms::{AlgorithmOptions, HashAlgorithm};
+use crate::output::{format_digests, Highlight};
+use crate::path_escape::result_line;
+use crate::utils::HashError;
+use crate::xattrs;
+use crate::OutputManager;
+
+/// Hashes the files under `dir` again with the algorithms found in the
+/// extended attributes written by `--write-xattr`, and reports each one as
+/// OK, FAILED when its content changed while its size and modification time
+/// did not (the signature of bit rot), or MODIFIED when it was changed since
+/// it was hashed. Files without stored hashes are skipped.
+pub fn verify_xattrs(
+    dir: &Path,
+    options: &AlgorithmOptions,
+    show_headers: bool,
+    follow_symlinks: bool,
+    read_options: &ReadOptions,
+    output_manager: &mut OutputManager,
+) -> Result<()> {
+    if show_headers {
+        output_manager.write_result_header("Result  path")?;
+    }
+
+    for entry in WalkDir::new(dir).follow_links(follow_symlinks) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Error accessing entry: {}", e);
+                continue;
+            }
+        };
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let stored = match xattrs::read(path) {
+            Ok(Some(stored)) => stored,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("{:#}", e);
+                continue;
+            }
+        };
+        let algorithms: Result<Vec<HashAlgorithm>> = stored
+            .hashes
+            .iter()
+            .map(|(name, _)| HashAlgorithm::with_options(name, options))
+            .collect();
+        let algorithms = match algorithms {
+            Ok(algorithms) => algorithms,
+            Err(e) => {
+                eprintln!("Cannot verify {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        // Taken before hashing, like when the attributes were written
+        let metadata = match path.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                if !output_manager.log_error(path, &e)? {
+                    eprintln!("Error processing file {}: {}", path.display(), e);
+                }
+                continue;
+            }
+        };
+
+        output_manager.start_file(path);
+        let computed = match compute_file_hashes(path, &algorithms, read_options, &mut |bytes| {
+            output_manager.update_bytes(bytes)
+        })
+        .into_result()
+        {
+            Ok(computed) => format_digests(&computed.digests),
+            Err(e) => {
+                if !output_manager.log_error(path, &e)? {
+                    let message = match &e {
+                        HashError::NotFound(e) => e.to_string(),
+                        e => e.to_string(),
+                    };
+                    eprintln!("Error processing file {}: {}", path.display(), message);
+                }
+                continue;
+            }
+        };
+
+        let unchanged = stored
+            .hashes
+            .iter()
+            .zip(&computed)
+            .all(|((_, stored), computed)| stored == computed);
+        let status = if unchanged {
+            output_manager.highlight("OK", Highlight::Success)
+        } else if stored.metadata_matches(&metadata) {
+            output_manager.highlight("FAILED", Highlight::Failure)
+        } else {
+            "MODIFIED".to_string()
+        };
+        output_manager.write_result(&result_line(&status, path))?;
+    }
+
+    output_manager.finish()?;
+    Ok(())
+}
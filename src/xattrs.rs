@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::hash_algorithms::HashAlgorithm;
+
+/// Namespace of the extended attributes written with `--write-xattr`.
+const PREFIX: &str = "user.parallelhash.";
+
+/// Name of the attribute holding the digest of `algorithm`, e.g.
+/// `user.parallelhash.sha2-256`.
+fn attribute_name(algorithm: &HashAlgorithm) -> String {
+    format!("{}{}", PREFIX, algorithm.to_string().to_lowercase())
+}
+
+/// Modification time in nanoseconds since the Unix epoch, as stored in the
+/// `mtime` attribute.
+fn mtime_ns(metadata: &Metadata) -> Option<i64> {
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    i64::try_from(mtime.as_nanos()).ok()
+}
+
+/// Hashes stored by `write`, with the size and modification time of the file
+/// at the time.
+#[derive(Debug, Default)]
+pub struct StoredHashes {
+    /// Algorithm names and digests, sorted by name.
+    pub hashes: Vec<(String, String)>,
+    pub size: Option<u64>,
+    pub mtime_ns: Option<i64>,
+}
+
+impl StoredHashes {
+    /// Whether the file still has the size and modification time it had when
+    /// it was hashed.
+    pub fn metadata_matches(&self, metadata: &Metadata) -> bool {
+        self.size == Some(metadata.len())
+            && self
+                .mtime_ns
+                .is_some_and(|mtime| Some(mtime) == mtime_ns(metadata))
+    }
+}
+
+/// Stores `digests` in extended attributes of the file at `path`, one per
+/// algorithm and formatted as in the output, along with the size and
+/// modification time the file had before it was hashed (taken from
+/// `metadata`).
+#[cfg(unix)]
+pub fn write(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    digests: &[Vec<u8>],
+    metadata: &Metadata,
+) -> Result<()> {
+    let mut attributes: Vec<(String, String)> = algorithms
+        .iter()
+        .zip(digests)
+        .map(|(algorithm, digest)| (attribute_name(algorithm), algorithm.encode_digest(digest)))
+        .collect();
+    attributes.push((format!("{}size", PREFIX), metadata.len().to_string()));
+    if let Some(mtime) = mtime_ns(metadata) {
+        attributes.push((format!("{}mtime", PREFIX), mtime.to_string()));
+    }
+    for (name, value) in attributes {
+        xattr::set(path, &name, value.as_bytes())
+            .with_context(|| format!("Failed to write {} on {}", name, path.display()))?;
+    }
+    Ok(())
+}
+
+/// Reads the hashes stored by `write` on the file at `path`, or `None` if it
+/// has none.
+#[cfg(unix)]
+pub fn read(path: &Path) -> Result<Option<StoredHashes>> {
+    let names = xattr::list(path)
+        .with_context(|| format!("Failed to list attributes of {}", path.display()))?;
+    let mut stored = StoredHashes::default();
+    for name in names {
+        let Some(field) = name.to_str().and_then(|name| name.strip_prefix(PREFIX)) else {
+            continue;
+        };
+        let Some(value) = xattr::get(path, &name)
+            .with_context(|| format!("Failed to read {} on {}", field, path.display()))?
+        else {
+            continue;
+        };
+        let value = String::from_utf8_lossy(&value).into_owned();
+        match field {
+            "size" => stored.size = value.parse().ok(),
+            "mtime" => stored.mtime_ns = value.parse().ok(),
+            algorithm => stored.hashes.push((algorithm.to_string(), value)),
+        }
+    }
+    stored.hashes.sort();
+    Ok((!stored.hashes.is_empty()).then_some(stored))
+}
+
+#[cfg(not(unix))]
+pub fn read(_path: &Path) -> Result<Option<StoredHashes>> {
+    anyhow::bail!("Extended attributes are only available on Unix")
+}
+
+#[cfg(not(unix))]
+pub fn write(
+    _path: &Path,
+    _algorithms: &[HashAlgorithm],
+    _digests: &[Vec<u8>],
+    _metadata: &Metadata,
+) -> Result<()> {
+    anyhow::bail!("Extended attributes are only available on Unix")
+}